@@ -0,0 +1,194 @@
+use bytes::{Buf, BufMut, BytesMut};
+use serde::{Deserialize, Serialize};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::Packet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub enum Encoding {
+    Bincode = 0,
+    Json = 1,
+}
+
+impl TryFrom<u8> for Encoding {
+    type Error = &'static str;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Encoding::Bincode),
+            1 => Ok(Encoding::Json),
+            _ => Err("Invalid encoding"),
+        }
+    }
+}
+
+impl From<Encoding> for u8 {
+    fn from(val: Encoding) -> Self {
+        match val {
+            Encoding::Bincode => 0,
+            Encoding::Json => 1,
+        }
+    }
+}
+
+impl Encoding {
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, std::io::Error> {
+        match self {
+            Encoding::Bincode => bincode::serialize(value).map_err(invalid_data),
+            Encoding::Json => serde_json::to_vec(value).map_err(invalid_data),
+        }
+    }
+    pub fn decode<'a, T: Deserialize<'a>>(self, bytes: &'a [u8]) -> Result<T, std::io::Error> {
+        match self {
+            Encoding::Bincode => bincode::deserialize(bytes).map_err(invalid_data),
+            Encoding::Json => serde_json::from_slice(bytes).map_err(invalid_data),
+        }
+    }
+}
+
+fn invalid_data(e: impl std::error::Error + Send + Sync + 'static) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+}
+
+/// Bytes before the body: a big endian `u32` length followed by the encoding byte. The length
+/// counts the encoding byte and the body.
+const HEADER_LEN: usize = 5;
+/// Frames longer than this are refused.
+pub const MAX_FRAME_LEN: usize = 1 << 20;
+
+/// Frames a [`Packet`] as `[len: u32][encoding: u8][body]`.
+///
+/// Outgoing packets use the codec's encoding, incoming ones are read in the encoding named by
+/// their frame so both sides can pick their own.
+#[derive(Debug, Clone, Copy)]
+pub struct PacketCodec {
+    pub encoding: Encoding,
+}
+
+impl PacketCodec {
+    pub fn new(encoding: Encoding) -> Self {
+        PacketCodec { encoding }
+    }
+}
+
+impl Decoder for PacketCodec {
+    type Item = Packet;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Packet>, std::io::Error> {
+        if src.len() < HEADER_LEN {
+            return Ok(None);
+        }
+        let len = u32::from_be_bytes([src[0], src[1], src[2], src[3]]) as usize;
+        if len == 0 || len > MAX_FRAME_LEN {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid frame length {len}"),
+            ));
+        }
+        if src.len() < 4 + len {
+            src.reserve(4 + len - src.len());
+            return Ok(None);
+        }
+        src.advance(4);
+        let frame = src.split_to(len);
+        let encoding = Encoding::try_from(frame[0])
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        encoding.decode(&frame[1..]).map(Some)
+    }
+}
+
+impl Encoder<Packet> for PacketCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, packet: Packet, dst: &mut BytesMut) -> Result<(), std::io::Error> {
+        let body = self.encoding.encode(&packet)?;
+        let len = body.len() + 1;
+        if len > MAX_FRAME_LEN {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("frame of {len} bytes is too long"),
+            ));
+        }
+        dst.reserve(4 + len);
+        dst.put_u32(len as u32);
+        dst.put_u8(self.encoding.into());
+        dst.extend_from_slice(&body);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ClientMessage, Connection, ConnectionId, SerialNumber};
+
+    fn packet(ser: u64) -> Packet {
+        Packet {
+            pld: ClientMessage::Ready.into(),
+            ser: SerialNumber(ser),
+            con: ConnectionId(7),
+        }
+    }
+
+    #[test]
+    fn test_partial_frames() {
+        for encoding in [Encoding::Bincode, Encoding::Json] {
+            let mut codec = PacketCodec::new(encoding);
+            let mut buf = BytesMut::new();
+            codec.encode(packet(1), &mut buf).unwrap();
+            codec.encode(packet(2), &mut buf).unwrap();
+            let mut partial = buf.split_to(3);
+            assert!(codec.decode(&mut partial).unwrap().is_none());
+            partial.unsplit(buf);
+            assert_eq!(
+                codec.decode(&mut partial).unwrap().unwrap().ser,
+                SerialNumber(1)
+            );
+            assert_eq!(
+                codec.decode(&mut partial).unwrap().unwrap().ser,
+                SerialNumber(2)
+            );
+            assert!(codec.decode(&mut partial).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_bad_frames() {
+        let mut codec = PacketCodec::new(Encoding::Json);
+        let mut buf = BytesMut::from(&[0, 0, 0, 2, 9, b'{'][..]);
+        assert!(codec.decode(&mut buf).is_err());
+        let mut buf = BytesMut::from(&[0xff, 0xff, 0xff, 0xff, 0][..]);
+        assert!(codec.decode(&mut buf).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_duplex_connection() {
+        let (client, server) = tokio::io::duplex(1024);
+        let (client_read, client_write) = tokio::io::split(client);
+        let (server_read, server_write) = tokio::io::split(server);
+        let mut client =
+            Connection::framed(client_read, client_write, ConnectionId(1), Encoding::Json);
+        let mut server = Connection::framed(
+            server_read,
+            server_write,
+            ConnectionId(2),
+            Encoding::Bincode,
+        );
+        assert_eq!(
+            client.send(ClientMessage::Ready.into()).await.unwrap(),
+            SerialNumber(0)
+        );
+        assert_eq!(
+            client.send(ClientMessage::Ready.into()).await.unwrap(),
+            SerialNumber(1)
+        );
+        let packet = server.recv_packet().await.unwrap().unwrap();
+        assert_eq!((packet.ser, packet.con), (SerialNumber(0), ConnectionId(1)));
+        server.recv().await.unwrap();
+        server.send(ClientMessage::Ready.into()).await.unwrap();
+        client.recv().await.unwrap();
+        drop(client);
+        assert!(server.recv().await.is_err());
+    }
+}
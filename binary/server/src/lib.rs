@@ -0,0 +1,262 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::mpsc,
+};
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+pub mod clock;
+pub mod codec;
+pub mod lobby;
+pub mod protocol;
+pub mod records;
+pub mod session;
+pub mod table;
+pub mod websocket;
+
+pub use codec::{Encoding, PacketCodec};
+pub use protocol::{ClientMessage, Payload, ServerMessage};
+
+use protocol::{ErrorMessage, SessionToken, PROTOCOL_VERSION};
+use rahjong::rules::jp::round::ReasonKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ConnectionId(pub u64);
+
+impl ConnectionId {
+    /// A fresh id, unique for the whole process whatever the transport.
+    pub fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        ConnectionId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SerialNumber(pub u64);
+/// How a client opened its connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Greeting {
+    Hello {
+        name: String,
+    },
+    Resume {
+        session: SessionToken,
+        acked: SerialNumber,
+    },
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Packet {
+    pub pld: Payload,
+    pub ser: SerialNumber,
+    pub con: ConnectionId,
+}
+
+pub struct Connection<I, O> {
+    pull: I,
+    push: O,
+    ser: SerialNumber,
+    id: ConnectionId,
+}
+
+pub type TcpStreamPull = FramedRead<tokio::net::tcp::OwnedReadHalf, PacketCodec>;
+pub type TcpStreamPush = FramedWrite<tokio::net::tcp::OwnedWriteHalf, PacketCodec>;
+pub type TcpConnection = Connection<TcpStreamPull, TcpStreamPush>;
+
+impl TcpConnection {
+    pub fn tcp(stream: tokio::net::TcpStream, id: ConnectionId, encoding: Encoding) -> Self {
+        let (read, write) = stream.into_split();
+        Connection::framed(read, write, id, encoding)
+    }
+}
+
+/// Accept framed TCP clients on `listener` and admit them to the lobby, this runs for as long as
+/// the server does.
+pub async fn serve_tcp(
+    listener: tokio::net::TcpListener,
+    inputs: mpsc::UnboundedSender<lobby::LobbyInput>,
+) {
+    loop {
+        let (stream, peer) = accept(&listener).await;
+        let connection = TcpConnection::tcp(stream, ConnectionId::next(), Encoding::Bincode);
+        let inputs = inputs.clone();
+        tokio::spawn(async move {
+            if let Err(e) = lobby::admit(connection, inputs).await {
+                eprintln!("{peer}: {e}");
+            }
+        });
+    }
+}
+
+/// The next client on `listener`. A failed accept is logged and skipped, it is retried after a
+/// pause so running out of file descriptors doesn't spin the loop.
+pub(crate) async fn accept(
+    listener: &tokio::net::TcpListener,
+) -> (tokio::net::TcpStream, std::net::SocketAddr) {
+    loop {
+        match listener.accept().await {
+            Ok(accepted) => return accepted,
+            Err(e) => {
+                eprintln!("accepting a client: {e}");
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
+
+impl<R, W> Connection<FramedRead<R, PacketCodec>, FramedWrite<W, PacketCodec>>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    /// Frame any pair of byte streams, packets are written with `encoding` and read in
+    /// whatever encoding the other side picked.
+    pub fn framed(read: R, write: W, id: ConnectionId, encoding: Encoding) -> Self {
+        Connection::new(
+            FramedRead::new(read, PacketCodec::new(encoding)),
+            FramedWrite::new(write, PacketCodec::new(encoding)),
+            id,
+        )
+    }
+}
+
+impl<I, O> Connection<I, O>
+where
+    I: Stream<Item = Result<Packet, std::io::Error>> + Unpin,
+    O: Sink<Packet, Error = std::io::Error> + Unpin,
+{
+    pub fn new(pull: I, push: O, id: ConnectionId) -> Self {
+        Connection {
+            pull,
+            push,
+            ser: SerialNumber(0),
+            id,
+        }
+    }
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+    /// The serial number the next packet will be sent with.
+    pub fn serial(&self) -> SerialNumber {
+        self.ser
+    }
+    pub async fn send(&mut self, pld: Payload) -> Result<SerialNumber, std::io::Error> {
+        let ser = self.ser;
+        let packet = Packet {
+            pld,
+            ser,
+            con: self.id,
+        };
+        self.push.send(packet).await?;
+        self.ser.0 += 1;
+        Ok(ser)
+    }
+    /// The next packet, `None` once the other side closed the connection.
+    pub async fn recv_packet(&mut self) -> Option<Result<Packet, std::io::Error>> {
+        self.pull.next().await
+    }
+    pub async fn recv(&mut self) -> Result<Payload, std::io::Error> {
+        match self.recv_packet().await {
+            Some(packet) => packet.map(|p| p.pld),
+            None => Err(std::io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+    /// Continue numbering packets from `ser`, for a connection that resumes a session.
+    pub fn resume(&mut self, ser: SerialNumber) {
+        self.ser = ser;
+    }
+    /// Wait for the client's hello or resume and check the protocol version it speaks.
+    pub async fn accept(&mut self) -> Result<Greeting, std::io::Error> {
+        let (version, greeting) = match self.recv().await? {
+            Payload::Client(ClientMessage::Hello { version, name }) => {
+                (version, Greeting::Hello { name })
+            }
+            Payload::Client(ClientMessage::Resume {
+                version,
+                session,
+                acked,
+            }) => (version, Greeting::Resume { session, acked }),
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "expected a hello",
+                ))
+            }
+        };
+        if version != PROTOCOL_VERSION {
+            let error = ErrorMessage {
+                kind: ReasonKind::UnsupportedVersion,
+                message: format!("protocol version {PROTOCOL_VERSION} is required"),
+            };
+            self.send(ServerMessage::from(error).into()).await?;
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unsupported protocol version {version}"),
+            ));
+        }
+        Ok(greeting)
+    }
+    /// Welcome an accepted client to its session.
+    pub async fn welcome(&mut self, session: SessionToken) -> Result<(), std::io::Error> {
+        let welcome = ServerMessage::Welcome {
+            version: PROTOCOL_VERSION,
+            connection: self.id,
+            session,
+        };
+        self.send(welcome.into()).await?;
+        Ok(())
+    }
+}
+
+impl<I, O> Connection<I, O>
+where
+    I: Stream<Item = Result<Packet, std::io::Error>> + Unpin + Send + 'static,
+    O: Sink<Packet, Error = std::io::Error> + Unpin + Send + 'static,
+{
+    /// Hand the connection to a task of its own. Client messages are wrapped by `message` and
+    /// passed to `inputs`, `left` is passed once the connection closes. Returns where to send
+    /// the messages for this client.
+    pub fn spawn<T: Send + 'static>(
+        self,
+        inputs: mpsc::UnboundedSender<T>,
+        message: impl Fn(ClientMessage) -> T + Send + 'static,
+        left: T,
+    ) -> mpsc::UnboundedSender<ServerMessage> {
+        let (outbox, messages) = mpsc::unbounded_channel();
+        self.pump(messages, inputs, message, left);
+        outbox
+    }
+    /// Like [`Connection::spawn`] with an outbox made beforehand, so it can be handed out
+    /// before the first client message comes in.
+    pub fn pump<T: Send + 'static>(
+        mut self,
+        mut messages: mpsc::UnboundedReceiver<ServerMessage>,
+        inputs: mpsc::UnboundedSender<T>,
+        message: impl Fn(ClientMessage) -> T + Send + 'static,
+        left: T,
+    ) {
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    outgoing = messages.recv() => {
+                        let Some(outgoing) = outgoing else { break };
+                        if self.send(outgoing.into()).await.is_err() {
+                            break;
+                        }
+                    }
+                    payload = self.recv() => match payload {
+                        Ok(Payload::Client(incoming)) => {
+                            if inputs.send(message(incoming)).is_err() {
+                                break;
+                            }
+                        }
+                        Ok(Payload::Server(_)) => {}
+                        Err(_) => break,
+                    },
+                }
+            }
+            let _ = inputs.send(left);
+        });
+    }
+}
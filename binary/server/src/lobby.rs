@@ -0,0 +1,1064 @@
+//! The lobby holds the connected clients and the rooms they gather in.
+//!
+//! A room is created with a ruleset and joined by its code. Once every seat is taken and every
+//! seated client is ready, the room starts a [`Table`] and passes the game messages of its seats
+//! on to it. Empty seats are given to [`Greedy`](rahjong::bot::Greedy) bots once a room waited
+//! long enough, and clients in the queue are put in a room of their own as soon as there are four
+//! of them.
+//!
+//! Spectators join a room by its code too. They are never seated, so nothing they send reaches
+//! the table.
+//!
+//! Finished games are saved to the lobby's [`RecordStore`] if it has one, clients look them up by
+//! player name and download them as replays.
+//!
+//! Clients keep their place for a grace period when their connection drops, their table plays
+//! for them meanwhile. Coming back with the token of their [`Session`] picks up where they left.
+use std::collections::HashMap;
+
+use futures_util::{Sink, Stream};
+
+use rahjong::{
+    player::{Player, WindSet},
+    rules::jp::round::{Reason, ReasonKind},
+    tile::Wind,
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    protocol::{
+        ErrorMessage, RoomCode, RoomInfo, RoomSettings, SeatInfo, SessionToken, SpectatorView,
+        TableId,
+    },
+    records::{GameRecord, RecordStore},
+    session::{Resumed, Session},
+    table::{Table, TableInput},
+    ClientMessage, Connection, ConnectionId, Greeting, Packet, SerialNumber, ServerMessage,
+};
+
+/// How long a client whose connection dropped keeps its place.
+pub const GRACE: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum LobbyInput {
+    /// A client finished the handshake, `outbox` writes to its connection.
+    Connected {
+        id: ConnectionId,
+        name: String,
+        session: SessionToken,
+        outbox: mpsc::UnboundedSender<ServerMessage>,
+    },
+    /// A client came back on the connection `connection`.
+    Resume {
+        connection: ConnectionId,
+        session: SessionToken,
+        acked: SerialNumber,
+        outbox: mpsc::UnboundedSender<ServerMessage>,
+        reply: oneshot::Sender<Result<Resumed, Reason>>,
+    },
+    Message(ConnectionId, ClientMessage),
+    /// The connection `connection` of a client closed, it may come back.
+    Disconnected {
+        id: ConnectionId,
+        connection: ConnectionId,
+    },
+    /// A client is gone for good.
+    Left(ConnectionId),
+    /// The table of a room finished its round.
+    TableDone {
+        code: RoomCode,
+        record: Box<GameRecord>,
+    },
+}
+
+struct Client {
+    name: String,
+    session: Session,
+    /// The connection the client is on, `None` while it is away.
+    connection: Option<ConnectionId>,
+    away_since: Option<Instant>,
+    room: Option<RoomCode>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum RoomSeat {
+    #[default]
+    Empty,
+    Human(ConnectionId),
+    /// Played by a greedy bot once the table starts, see [`Table::seat_bot`].
+    Bot,
+}
+
+struct Room {
+    settings: RoomSettings,
+    members: Vec<ConnectionId>,
+    spectators: Vec<(ConnectionId, SpectatorView)>,
+    seats: WindSet<RoomSeat>,
+    ready: WindSet<bool>,
+    /// When the room started waiting for players, bots are seated counting from here.
+    since: Instant,
+    table: Option<mpsc::UnboundedSender<TableInput>>,
+}
+
+impl Room {
+    fn new(settings: RoomSettings, now: Instant) -> Self {
+        Room {
+            settings,
+            members: Vec::new(),
+            spectators: Vec::new(),
+            seats: WindSet::default(),
+            ready: WindSet::default(),
+            since: now,
+            table: None,
+        }
+    }
+    fn seat_of(&self, id: ConnectionId) -> Option<Player> {
+        self.seats
+            .iter()
+            .find(|(_, s)| **s == RoomSeat::Human(id))
+            .map(|(seat, _)| seat)
+    }
+    fn has_humans(&self) -> bool {
+        self.seats
+            .iter()
+            .any(|(_, s)| matches!(s, RoomSeat::Human(_)))
+    }
+    fn can_start(&self) -> bool {
+        self.table.is_none()
+            && self.seats.iter().all(|(seat, s)| match s {
+                RoomSeat::Empty => false,
+                RoomSeat::Human(_) => *self.ready.get(seat),
+                RoomSeat::Bot => true,
+            })
+    }
+}
+
+pub struct Lobby {
+    clients: HashMap<ConnectionId, Client>,
+    rooms: HashMap<RoomCode, Room>,
+    queue: Vec<ConnectionId>,
+    rooms_created: u64,
+    tables: u64,
+    grace: Duration,
+    records: Option<RecordStore>,
+    inbox: mpsc::UnboundedReceiver<LobbyInput>,
+    sender: mpsc::UnboundedSender<LobbyInput>,
+}
+
+impl Default for Lobby {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lobby {
+    pub fn new() -> Self {
+        let (sender, inbox) = mpsc::unbounded_channel();
+        Lobby {
+            clients: HashMap::new(),
+            rooms: HashMap::new(),
+            queue: Vec::new(),
+            rooms_created: 0,
+            tables: 0,
+            grace: GRACE,
+            records: None,
+            inbox,
+            sender,
+        }
+    }
+    /// Keep the places of disconnected clients for `grace` instead of [`GRACE`].
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+    /// Save finished games to `records` and serve them to clients.
+    pub fn with_records(mut self, records: RecordStore) -> Self {
+        self.records = Some(records);
+        self
+    }
+    pub fn sender(&self) -> mpsc::UnboundedSender<LobbyInput> {
+        self.sender.clone()
+    }
+    /// Every open room.
+    pub fn rooms(&self) -> Vec<RoomInfo> {
+        let mut rooms: Vec<RoomInfo> = self
+            .rooms
+            .iter()
+            .map(|(code, room)| self.room_info(code, room))
+            .collect();
+        rooms.sort_by(|a, b| a.code.0.cmp(&b.code.0));
+        rooms
+    }
+    /// Handle inputs and seat bots until the lobby is dropped.
+    pub async fn run(mut self) {
+        let mut ticks = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            tokio::select! {
+                input = self.inbox.recv() => {
+                    let Some(input) = input else { break };
+                    self.input(input, Instant::now());
+                }
+                now = ticks.tick() => self.tick(now),
+            }
+        }
+    }
+    pub fn input(&mut self, input: LobbyInput, now: Instant) {
+        match input {
+            LobbyInput::Connected {
+                id,
+                name,
+                session,
+                outbox,
+            } => {
+                let client = Client {
+                    name,
+                    session: Session::spawn(id, session, outbox),
+                    connection: Some(id),
+                    away_since: None,
+                    room: None,
+                };
+                self.clients.insert(id, client);
+            }
+            LobbyInput::Resume {
+                connection,
+                session,
+                acked,
+                outbox,
+                reply,
+            } => {
+                let Some((id, client)) = self
+                    .clients
+                    .iter_mut()
+                    .find(|(_, c)| *c.session.token() == session)
+                else {
+                    let reason =
+                        Reason::new(ReasonKind::UnknownSession, "No session with this token");
+                    let _ = reply.send(Err(reason));
+                    return;
+                };
+                let id = *id;
+                client.connection = Some(connection);
+                client.away_since = None;
+                client.session.attach(outbox, acked, reply);
+                self.tell_table(id, TableInput::Back);
+            }
+            LobbyInput::Message(id, message) => self.handle(id, message, now),
+            LobbyInput::Disconnected { id, connection } => {
+                let Some(client) = self.clients.get_mut(&id) else {
+                    return;
+                };
+                if client.connection != Some(connection) {
+                    // the client is already back on another connection
+                    return;
+                }
+                client.connection = None;
+                client.away_since = Some(now);
+                self.dequeue(id);
+                self.tell_table(id, TableInput::Away);
+            }
+            LobbyInput::Left(id) => {
+                self.dequeue(id);
+                self.leave_room(id);
+                self.clients.remove(&id);
+            }
+            LobbyInput::TableDone { code, record } => {
+                if let Some(records) = &mut self.records {
+                    let (_, written) = records.save(*record);
+                    let room = code.0.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = written.await.unwrap_or_else(|e| Err(e.into())) {
+                            eprintln!("saving the game of room {room}: {e}");
+                        }
+                    });
+                }
+                if let Some(room) = self.rooms.get_mut(&code) {
+                    room.table = None;
+                    room.ready = WindSet::default();
+                    room.since = now;
+                    self.broadcast_room(&code);
+                }
+            }
+        }
+    }
+    /// Let go of the clients that were away too long, then seat bots in the rooms that waited
+    /// long enough and start them if that filled them.
+    pub fn tick(&mut self, now: Instant) {
+        let gone: Vec<ConnectionId> = self
+            .clients
+            .iter()
+            .filter(|(_, c)| c.away_since.is_some_and(|since| now >= since + self.grace))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in gone {
+            self.input(LobbyInput::Left(id), now);
+        }
+        let due: Vec<RoomCode> = self
+            .rooms
+            .iter()
+            .filter(|(_, room)| {
+                room.table.is_none()
+                    && room.has_humans()
+                    && room
+                        .settings
+                        .bots_after
+                        .is_some_and(|secs| now >= room.since + Duration::from_secs(secs))
+                    && room.seats.iter().any(|(_, s)| *s == RoomSeat::Empty)
+            })
+            .map(|(code, _)| code.clone())
+            .collect();
+        for code in due {
+            let room = self.rooms.get_mut(&code).expect("room is open");
+            for wind in Wind::enumerate() {
+                if *room.seats.get(wind) == RoomSeat::Empty {
+                    room.seats.insert(wind, RoomSeat::Bot);
+                }
+            }
+            self.broadcast_room(&code);
+            self.try_start(&code);
+        }
+    }
+    fn handle(&mut self, id: ConnectionId, message: ClientMessage, now: Instant) {
+        if !self.clients.contains_key(&id) {
+            return;
+        }
+        match message {
+            ClientMessage::Hello { .. } | ClientMessage::Resume { .. } => {
+                self.error(id, Reason::invalid_operation("Already welcomed"))
+            }
+            ClientMessage::CreateRoom(settings) => {
+                if self.in_room(id) {
+                    return;
+                }
+                let code = self.room_code();
+                self.rooms.insert(code.clone(), Room::new(settings, now));
+                self.join_room(id, code);
+            }
+            ClientMessage::ListRooms => {
+                let rooms = self.rooms();
+                self.send(id, ServerMessage::Rooms(rooms));
+            }
+            ClientMessage::JoinRoom(code) => {
+                if self.in_room(id) {
+                    return;
+                }
+                if !self.rooms.contains_key(&code) {
+                    let reason = Reason::new(ReasonKind::UnknownTable, "No room with this code");
+                    self.error(id, reason);
+                    return;
+                }
+                self.join_room(id, code);
+            }
+            ClientMessage::Spectate { room: code, view } => {
+                if self.in_room(id) {
+                    return;
+                }
+                let Some(room) = self.rooms.get_mut(&code) else {
+                    let reason = Reason::new(ReasonKind::UnknownTable, "No room with this code");
+                    self.error(id, reason);
+                    return;
+                };
+                room.spectators.push((id, view));
+                if let Some(table) = &room.table {
+                    let outbox = self.clients[&id].session.outbox();
+                    let _ = table.send(TableInput::Spectate { id, view, outbox });
+                }
+                if let Some(client) = self.clients.get_mut(&id) {
+                    client.room = Some(code.clone());
+                }
+                self.broadcast_room(&code);
+            }
+            ClientMessage::History { player } => {
+                let Some(records) = &self.records else {
+                    self.error(id, Reason::invalid_operation("Games are not recorded"));
+                    return;
+                };
+                let games = records.games_of(&player);
+                self.send(id, ServerMessage::History { player, games });
+            }
+            ClientMessage::Download(record) => {
+                let Some(records) = &self.records else {
+                    self.error(id, Reason::invalid_operation("Games are not recorded"));
+                    return;
+                };
+                match records.load(record) {
+                    Ok(Some(record)) => self.send(id, ServerMessage::Replay(Box::new(record))),
+                    Ok(None) => {
+                        let reason = Reason::new(ReasonKind::UnknownRecord, "No game with this id");
+                        self.error(id, reason);
+                    }
+                    Err(e) => {
+                        eprintln!("loading game {}: {e}", record.0);
+                        self.error(id, Reason::invalid_operation("The game could not be read"));
+                    }
+                }
+            }
+            ClientMessage::TakeSeat(seat) => self.take_seat(id, seat),
+            ClientMessage::LeaveRoom => self.leave_room(id),
+            ClientMessage::Queue => {
+                if self.in_room(id) || self.queue.contains(&id) {
+                    return;
+                }
+                self.queue.push(id);
+                self.match_queue(now);
+                self.notify_queue();
+            }
+            ClientMessage::Ready => {
+                let Some((code, seat)) = self.seated(id) else {
+                    self.error(id, Reason::new(ReasonKind::NotSeated, "Take a seat first"));
+                    return;
+                };
+                let room = self.rooms.get_mut(&code).expect("room is open");
+                if let Some(table) = &room.table {
+                    let _ = table.send(TableInput::Message(seat, ClientMessage::Ready));
+                    return;
+                }
+                room.ready.insert(seat, true);
+                self.broadcast_room(&code);
+                self.try_start(&code);
+            }
+            message @ (ClientMessage::Action(_)
+            | ClientMessage::Sync
+            | ClientMessage::Chat(_)
+            | ClientMessage::Resign) => {
+                let table = self
+                    .seated(id)
+                    .and_then(|(code, seat)| Some((self.rooms.get(&code)?.table.as_ref()?, seat)));
+                match table {
+                    Some((table, seat)) => {
+                        let _ = table.send(TableInput::Message(seat, message));
+                    }
+                    None => self.error(
+                        id,
+                        Reason::new(ReasonKind::NotSeated, "Not playing at a table"),
+                    ),
+                }
+            }
+        }
+    }
+    /// Refuse room changes while the client is in a room, `true` if it is.
+    fn in_room(&self, id: ConnectionId) -> bool {
+        let in_room = self.clients[&id].room.is_some();
+        if in_room {
+            self.error(id, Reason::invalid_operation("Leave the room first"));
+        }
+        in_room
+    }
+    fn seated(&self, id: ConnectionId) -> Option<(RoomCode, Player)> {
+        self.clients[&id].room.as_ref().and_then(|code| {
+            let seat = self.rooms.get(code)?.seat_of(id)?;
+            Some((code.clone(), seat))
+        })
+    }
+    /// Pass `input` on to the table the client plays at, if it does.
+    fn tell_table(&self, id: ConnectionId, input: impl FnOnce(Player) -> TableInput) {
+        let Some((code, seat)) = self.seated(id) else {
+            return;
+        };
+        if let Some(table) = &self.rooms[&code].table {
+            let _ = table.send(input(seat));
+        }
+    }
+    fn join_room(&mut self, id: ConnectionId, code: RoomCode) {
+        self.dequeue(id);
+        let room = self.rooms.get_mut(&code).expect("room is open");
+        room.members.push(id);
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.room = Some(code.clone());
+        }
+        self.broadcast_room(&code);
+    }
+    fn take_seat(&mut self, id: ConnectionId, seat: Player) {
+        let Some(code) = self.clients[&id].room.clone() else {
+            self.error(id, Reason::new(ReasonKind::NotSeated, "Join a room first"));
+            return;
+        };
+        let room = self.rooms.get_mut(&code).expect("room is open");
+        if room.spectators.iter().any(|(s, _)| *s == id) {
+            let reason = Reason::invalid_operation("Spectators can't take a seat");
+            self.error(id, reason);
+            return;
+        }
+        if room.table.is_some() || *room.seats.get(seat) != RoomSeat::Empty {
+            let reason = Reason::new(ReasonKind::TableFull, "This seat is taken");
+            self.error(id, reason);
+            return;
+        }
+        if let Some(old) = room.seat_of(id) {
+            room.seats.insert(old, RoomSeat::Empty);
+            room.ready.insert(old, false);
+        }
+        room.seats.insert(seat, RoomSeat::Human(id));
+        room.ready.insert(seat, false);
+        self.broadcast_room(&code);
+    }
+    /// Take a client out of its room, the room is closed once nobody is left in it.
+    fn leave_room(&mut self, id: ConnectionId) {
+        let Some(code) = self.clients.get_mut(&id).and_then(|c| c.room.take()) else {
+            return;
+        };
+        let room = self.rooms.get_mut(&code).expect("room is open");
+        room.members.retain(|m| *m != id);
+        if room.spectators.iter().any(|(s, _)| *s == id) {
+            room.spectators.retain(|(s, _)| *s != id);
+            if let Some(table) = &room.table {
+                let _ = table.send(TableInput::StopSpectating(id));
+            }
+        }
+        if let Some(seat) = room.seat_of(id) {
+            room.seats.insert(seat, RoomSeat::Empty);
+            room.ready.insert(seat, false);
+            if let Some(table) = &room.table {
+                let _ = table.send(TableInput::Left(seat));
+            }
+        }
+        if room.members.is_empty() {
+            let room = self.rooms.remove(&code).expect("room is open");
+            for (spectator, _) in room.spectators {
+                if let Some(client) = self.clients.get_mut(&spectator) {
+                    client.room = None;
+                }
+            }
+        } else {
+            self.broadcast_room(&code);
+        }
+    }
+    fn dequeue(&mut self, id: ConnectionId) {
+        let before = self.queue.len();
+        self.queue.retain(|q| *q != id);
+        if self.queue.len() != before {
+            self.notify_queue();
+        }
+    }
+    fn notify_queue(&self) {
+        for id in &self.queue {
+            self.send(
+                *id,
+                ServerMessage::Queued {
+                    waiting: self.queue.len(),
+                },
+            );
+        }
+    }
+    /// Seat every four queued clients at a room of their own, they are ready right away.
+    fn match_queue(&mut self, now: Instant) {
+        while self.queue.len() >= 4 {
+            let matched: Vec<ConnectionId> = self.queue.drain(..4).collect();
+            let code = self.room_code();
+            let settings = RoomSettings {
+                bots_after: None,
+                ..Default::default()
+            };
+            let mut room = Room::new(settings, now);
+            for (wind, id) in Wind::enumerate().zip(matched) {
+                room.members.push(id);
+                room.seats.insert(wind, RoomSeat::Human(id));
+                room.ready.insert(wind, true);
+                if let Some(client) = self.clients.get_mut(&id) {
+                    client.room = Some(code.clone());
+                }
+            }
+            self.rooms.insert(code.clone(), room);
+            self.broadcast_room(&code);
+            self.try_start(&code);
+        }
+    }
+    fn try_start(&mut self, code: &RoomCode) {
+        if !self.rooms.get(code).is_some_and(Room::can_start) {
+            return;
+        }
+        let room = self.rooms.get_mut(code).expect("room is open");
+        self.tables += 1;
+        let mut table = Table::new(
+            TableId(self.tables),
+            room.settings.ruleset.round(Wind::East),
+        );
+        if let Some(time) = room.settings.time {
+            table = table.with_time_control(time);
+        }
+        table = table.with_spectator_delay(room.settings.spectator_delay);
+        let mut humans = Vec::new();
+        let mut players: WindSet<Option<String>> = WindSet::default();
+        for (seat, s) in room.seats.iter() {
+            let seated = match s {
+                RoomSeat::Human(id) => {
+                    humans.push(seat);
+                    players.insert(seat, Some(self.clients[id].name.clone()));
+                    table.seat_outbox(seat, self.clients[id].session.outbox())
+                }
+                RoomSeat::Bot => table.seat_bot(seat),
+                RoomSeat::Empty => unreachable!("only full rooms start"),
+            };
+            seated.expect("a new table has every seat free");
+        }
+        let sender = table.sender();
+        for (id, view) in &room.spectators {
+            let outbox = self.clients[id].session.outbox();
+            let _ = sender.send(TableInput::Spectate {
+                id: *id,
+                view: *view,
+                outbox,
+            });
+        }
+        for seat in humans {
+            let _ = sender.send(TableInput::Message(seat, ClientMessage::Ready));
+        }
+        room.table = Some(sender);
+        let lobby = self.sender.clone();
+        let done = code.clone();
+        let ruleset = room.settings.ruleset.clone();
+        tokio::spawn(async move {
+            let finished = table.run().await;
+            let record = Box::new(GameRecord::new(ruleset, players, &finished));
+            let done = LobbyInput::TableDone { code: done, record };
+            let _ = lobby.send(done);
+        });
+        self.broadcast_room(code);
+    }
+    /// A fresh code, scrambled so the codes of rooms opened one after another look unrelated.
+    fn room_code(&mut self) -> RoomCode {
+        const LETTERS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+        loop {
+            self.rooms_created += 1;
+            let mut n = self.rooms_created.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32;
+            let code: String = (0..5)
+                .map(|_| {
+                    let letter = LETTERS[(n % LETTERS.len() as u64) as usize] as char;
+                    n /= LETTERS.len() as u64;
+                    letter
+                })
+                .collect();
+            let code = RoomCode(code);
+            if !self.rooms.contains_key(&code) {
+                return code;
+            }
+        }
+    }
+    fn room_info(&self, code: &RoomCode, room: &Room) -> RoomInfo {
+        let seats = room
+            .seats
+            .iter()
+            .map(|(seat, s)| {
+                let info = match s {
+                    RoomSeat::Empty => SeatInfo::Empty,
+                    RoomSeat::Human(id) => SeatInfo::Human {
+                        name: self.clients[id].name.clone(),
+                        ready: *room.ready.get(seat),
+                    },
+                    RoomSeat::Bot => SeatInfo::Bot,
+                };
+                (seat, info)
+            })
+            .collect();
+        RoomInfo {
+            code: code.clone(),
+            settings: room.settings.clone(),
+            seats,
+            members: room.members.len(),
+            spectators: room.spectators.len(),
+            started: room.table.is_some(),
+        }
+    }
+    fn broadcast_room(&self, code: &RoomCode) {
+        let Some(room) = self.rooms.get(code) else {
+            return;
+        };
+        let info = self.room_info(code, room);
+        let spectators = room.spectators.iter().map(|(id, _)| id);
+        for id in room.members.iter().chain(spectators) {
+            self.send(*id, ServerMessage::Room(info.clone()));
+        }
+    }
+    fn send(&self, id: ConnectionId, message: ServerMessage) {
+        if let Some(client) = self.clients.get(&id) {
+            let _ = client.session.outbox().send(message);
+        }
+    }
+    fn error(&self, id: ConnectionId, reason: Reason) {
+        self.send(id, ErrorMessage::from(reason).into());
+    }
+}
+
+/// Greet a new connection and hand it to the lobby behind `lobby`, whether it opens a session or
+/// resumes one.
+pub async fn admit<I, O>(
+    mut connection: Connection<I, O>,
+    lobby: mpsc::UnboundedSender<LobbyInput>,
+) -> Result<(), std::io::Error>
+where
+    I: Stream<Item = Result<Packet, std::io::Error>> + Unpin + Send + 'static,
+    O: Sink<Packet, Error = std::io::Error> + Unpin + Send + 'static,
+{
+    let greeting = connection.accept().await?;
+    let connection_id = connection.id();
+    let (outbox, messages) = mpsc::unbounded_channel();
+    let id = match greeting {
+        Greeting::Hello { name } => {
+            let session = SessionToken::random();
+            connection.welcome(session.clone()).await?;
+            let _ = lobby.send(LobbyInput::Connected {
+                id: connection_id,
+                name,
+                session,
+                outbox,
+            });
+            connection_id
+        }
+        Greeting::Resume { session, acked } => {
+            let (reply, resumed) = oneshot::channel();
+            let _ = lobby.send(LobbyInput::Resume {
+                connection: connection_id,
+                session: session.clone(),
+                acked,
+                outbox,
+                reply,
+            });
+            let resumed = match resumed.await {
+                Ok(Ok(resumed)) => resumed,
+                Ok(Err(reason)) => {
+                    connection
+                        .send(ServerMessage::from(ErrorMessage::from(reason)).into())
+                        .await?;
+                    return Ok(());
+                }
+                Err(_) => return Ok(()),
+            };
+            connection.welcome(session).await?;
+            connection.resume(resumed.from);
+            if resumed.stale {
+                let _ = lobby.send(LobbyInput::Message(resumed.id, ClientMessage::Sync));
+            }
+            resumed.id
+        }
+    };
+    connection.pump(
+        messages,
+        lobby,
+        move |message| LobbyInput::Message(id, message),
+        LobbyInput::Disconnected {
+            id,
+            connection: connection_id,
+        },
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        protocol::{Event, Ruleset, PROTOCOL_VERSION},
+        records::RecordId,
+        table::{
+            tests::{next, pair, TestConnection},
+            Finished, Outcome,
+        },
+        Payload,
+    };
+
+    fn connect(lobby: &mut Lobby, id: u64) -> mpsc::UnboundedReceiver<ServerMessage> {
+        let (outbox, messages) = mpsc::unbounded_channel();
+        let connected = LobbyInput::Connected {
+            id: ConnectionId(id),
+            name: format!("player {id}"),
+            session: SessionToken::random(),
+            outbox,
+        };
+        lobby.input(connected, Instant::now());
+        messages
+    }
+
+    fn send(lobby: &mut Lobby, id: u64, message: ClientMessage) {
+        lobby.input(
+            LobbyInput::Message(ConnectionId(id), message),
+            Instant::now(),
+        );
+    }
+
+    /// The last room update sent so far, messages pass through the session task first.
+    async fn last_room(messages: &mut mpsc::UnboundedReceiver<ServerMessage>) -> Option<RoomInfo> {
+        tokio::task::yield_now().await;
+        let mut room = None;
+        while let Ok(message) = messages.try_recv() {
+            if let ServerMessage::Room(info) = message {
+                room = Some(info);
+            }
+        }
+        room
+    }
+
+    async fn started(messages: &mut mpsc::UnboundedReceiver<ServerMessage>) -> Player {
+        let mut seat = None;
+        loop {
+            match messages.recv().await.unwrap() {
+                ServerMessage::Seated { seat: s, .. } => seat = Some(s),
+                ServerMessage::Event(Event::Started { .. }) => return seat.unwrap(),
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rooms() {
+        let mut lobby = Lobby::new();
+        let mut a = connect(&mut lobby, 1);
+        let mut b = connect(&mut lobby, 2);
+        send(
+            &mut lobby,
+            1,
+            ClientMessage::CreateRoom(RoomSettings::default()),
+        );
+        let room = last_room(&mut a).await.unwrap();
+        assert_eq!(room.members, 1);
+        send(&mut lobby, 2, ClientMessage::ListRooms);
+        let Some(ServerMessage::Rooms(rooms)) = b.recv().await else {
+            panic!("expected the rooms");
+        };
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(rooms[0].code, room.code);
+
+        send(&mut lobby, 2, ClientMessage::JoinRoom(RoomCode("?".into())));
+        assert!(
+            matches!(b.recv().await, Some(ServerMessage::Error(e)) if e.kind == ReasonKind::UnknownTable)
+        );
+        send(&mut lobby, 2, ClientMessage::JoinRoom(room.code.clone()));
+        send(&mut lobby, 1, ClientMessage::TakeSeat(Player::EAST));
+        last_room(&mut b).await;
+        send(&mut lobby, 2, ClientMessage::TakeSeat(Player::EAST));
+        assert!(
+            matches!(b.recv().await, Some(ServerMessage::Error(e)) if e.kind == ReasonKind::TableFull)
+        );
+        send(&mut lobby, 2, ClientMessage::TakeSeat(Player::SOUTH));
+        let room = last_room(&mut a).await.unwrap();
+        assert_eq!(room.members, 2);
+        assert!(matches!(
+            room.seats.get(Player::SOUTH),
+            SeatInfo::Human { ready: false, .. }
+        ));
+
+        send(&mut lobby, 1, ClientMessage::LeaveRoom);
+        let room = last_room(&mut b).await.unwrap();
+        assert_eq!(*room.seats.get(Player::EAST), SeatInfo::Empty);
+        lobby.input(LobbyInput::Left(ConnectionId(2)), Instant::now());
+        assert!(lobby.rooms().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bots_fill_seats() {
+        let mut lobby = Lobby::new();
+        let mut a = connect(&mut lobby, 1);
+        let settings = RoomSettings {
+            bots_after: Some(10),
+            ..Default::default()
+        };
+        let now = Instant::now();
+        send(&mut lobby, 1, ClientMessage::CreateRoom(settings));
+        send(&mut lobby, 1, ClientMessage::TakeSeat(Player::WEST));
+        send(&mut lobby, 1, ClientMessage::Ready);
+        lobby.tick(now + Duration::from_secs(5));
+        assert!(!lobby.rooms()[0].started);
+        lobby.tick(now + Duration::from_secs(11));
+        let room = lobby.rooms().pop().unwrap();
+        assert!(room.started);
+        assert_eq!(
+            room.seats
+                .iter()
+                .filter(|(_, s)| **s == SeatInfo::Bot)
+                .count(),
+            3
+        );
+        assert_eq!(started(&mut a).await, Player::WEST);
+    }
+
+    #[tokio::test]
+    async fn test_queue() {
+        let mut lobby = Lobby::new();
+        let mut clients: Vec<_> = (1..=4).map(|id| connect(&mut lobby, id)).collect();
+        for id in 1..=3 {
+            send(&mut lobby, id, ClientMessage::Queue);
+        }
+        assert!(lobby.rooms().is_empty());
+        assert!(matches!(
+            clients[0].recv().await,
+            Some(ServerMessage::Queued { waiting: 1 })
+        ));
+        send(&mut lobby, 4, ClientMessage::Queue);
+        let rooms = lobby.rooms();
+        assert_eq!(rooms.len(), 1);
+        assert!(rooms[0].started);
+        for (client, wind) in clients.iter_mut().zip(Wind::enumerate()) {
+            assert_eq!(started(client).await, wind.into());
+        }
+    }
+
+    async fn packet(client: &mut TestConnection) -> (SerialNumber, ServerMessage) {
+        let packet = client.recv_packet().await.unwrap().unwrap();
+        match packet.pld {
+            Payload::Server(message) => (packet.ser, message),
+            Payload::Client(message) => panic!("unexpected {message:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconnect() {
+        let lobby = Lobby::new();
+        let inputs = lobby.sender();
+        tokio::spawn(lobby.run());
+        let hello = ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            name: "a".into(),
+        };
+
+        let (mut a, server) = pair(1);
+        tokio::spawn(admit(server, inputs.clone()));
+        a.send(hello.clone().into()).await.unwrap();
+        let (ser, ServerMessage::Welcome { session, .. }) = packet(&mut a).await else {
+            panic!("expected a welcome");
+        };
+        assert_eq!(ser, SerialNumber(0));
+        a.send(ClientMessage::CreateRoom(RoomSettings::default()).into())
+            .await
+            .unwrap();
+        let (ser, ServerMessage::Room(room)) = packet(&mut a).await else {
+            panic!("expected the room");
+        };
+        assert_eq!(ser, SerialNumber(1));
+        drop(a);
+
+        // the room changes while a is away
+        let (mut b, server) = pair(2);
+        tokio::spawn(admit(server, inputs.clone()));
+        b.send(hello.into()).await.unwrap();
+        next(&mut b).await;
+        b.send(ClientMessage::JoinRoom(room.code.clone()).into())
+            .await
+            .unwrap();
+        assert!(matches!(next(&mut b).await, ServerMessage::Room(r) if r.members == 2));
+
+        let (mut a, server) = pair(3);
+        tokio::spawn(admit(server, inputs.clone()));
+        let resume = ClientMessage::Resume {
+            version: PROTOCOL_VERSION,
+            session: session.clone(),
+            acked: SerialNumber(1),
+        };
+        a.send(resume.into()).await.unwrap();
+        let (ser, welcome) = packet(&mut a).await;
+        assert_eq!(ser, SerialNumber(0));
+        assert!(matches!(welcome, ServerMessage::Welcome { session: s, .. } if s == session));
+        let (ser, missed) = packet(&mut a).await;
+        assert_eq!(ser, SerialNumber(2));
+        assert!(matches!(missed, ServerMessage::Room(r) if r.members == 2));
+
+        let (mut c, server) = pair(4);
+        tokio::spawn(admit(server, inputs));
+        let resume = ClientMessage::Resume {
+            version: PROTOCOL_VERSION,
+            session: SessionToken("unknown".into()),
+            acked: SerialNumber(0),
+        };
+        c.send(resume.into()).await.unwrap();
+        assert!(
+            matches!(next(&mut c).await, ServerMessage::Error(e) if e.kind == ReasonKind::UnknownSession)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_grace() {
+        let mut lobby = Lobby::new().with_grace(Duration::from_secs(30));
+        let mut a = connect(&mut lobby, 1);
+        let now = Instant::now();
+        send(
+            &mut lobby,
+            1,
+            ClientMessage::CreateRoom(RoomSettings::default()),
+        );
+        assert!(last_room(&mut a).await.is_some());
+        let disconnected = LobbyInput::Disconnected {
+            id: ConnectionId(1),
+            connection: ConnectionId(1),
+        };
+        lobby.input(disconnected, now);
+        lobby.tick(now + Duration::from_secs(20));
+        assert_eq!(lobby.rooms().len(), 1);
+        lobby.tick(now + Duration::from_secs(31));
+        assert!(lobby.rooms().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_spectator_cannot_play() {
+        let mut lobby = Lobby::new();
+        let mut a = connect(&mut lobby, 1);
+        let mut b = connect(&mut lobby, 2);
+        send(
+            &mut lobby,
+            1,
+            ClientMessage::CreateRoom(RoomSettings::default()),
+        );
+        let code = last_room(&mut a).await.unwrap().code;
+        let spectate = ClientMessage::Spectate {
+            room: code,
+            view: SpectatorView::Full,
+        };
+        send(&mut lobby, 2, spectate);
+        let room = last_room(&mut b).await.unwrap();
+        assert_eq!((room.members, room.spectators), (1, 1));
+        send(&mut lobby, 2, ClientMessage::TakeSeat(Player::EAST));
+        assert!(
+            matches!(b.recv().await, Some(ServerMessage::Error(e)) if e.kind == ReasonKind::InvalidOperation)
+        );
+        send(
+            &mut lobby,
+            2,
+            ClientMessage::Action(rahjong::rules::jp::round::Action::Pass),
+        );
+        assert!(
+            matches!(b.recv().await, Some(ServerMessage::Error(e)) if e.kind == ReasonKind::NotSeated)
+        );
+        // the room closes with its last player, spectators are let go
+        send(&mut lobby, 1, ClientMessage::LeaveRoom);
+        assert!(lobby.rooms().is_empty());
+        send(&mut lobby, 2, ClientMessage::ListRooms);
+        assert!(matches!(b.recv().await, Some(ServerMessage::Rooms(r)) if r.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_history() {
+        let dir = std::env::temp_dir().join(format!("rahjong-lobby-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut lobby = Lobby::new().with_records(RecordStore::open(&dir).unwrap());
+        let mut a = connect(&mut lobby, 1);
+        let mut round = Ruleset::Riichi.round(Wind::East);
+        round.start().unwrap();
+        let finished = Finished {
+            round,
+            outcome: Outcome::Abandoned,
+        };
+        let players = WindSet::from_fn(|p| (p == Player::EAST).then(|| "player 1".to_owned()));
+        let done = LobbyInput::TableDone {
+            code: RoomCode("ABCDE".into()),
+            record: Box::new(GameRecord::new(Ruleset::Riichi, players, &finished)),
+        };
+        lobby.input(done, Instant::now());
+        let history = ClientMessage::History {
+            player: "player 1".into(),
+        };
+        send(&mut lobby, 1, history);
+        let Some(ServerMessage::History { games, .. }) = a.recv().await else {
+            panic!("expected the history");
+        };
+        assert_eq!(games.len(), 1);
+        send(&mut lobby, 1, ClientMessage::Download(games[0].id));
+        let Some(ServerMessage::Replay(record)) = a.recv().await else {
+            panic!("expected the replay");
+        };
+        assert_eq!(record.seed, finished.round.seed());
+        send(&mut lobby, 1, ClientMessage::Download(RecordId(99)));
+        assert!(
+            matches!(a.recv().await, Some(ServerMessage::Error(e)) if e.kind == ReasonKind::UnknownRecord)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
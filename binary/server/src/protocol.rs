@@ -0,0 +1,355 @@
+//! Messages exchanged with the clients.
+//!
+//! A client opens with [`ClientMessage::Hello`] naming the protocol version it speaks, the
+//! server answers with [`ServerMessage::Welcome`] or an error of kind
+//! [`ReasonKind::UnsupportedVersion`].
+//!
+//! The welcome hands out a [`SessionToken`]. A client whose connection dropped opens the next one
+//! with [`ClientMessage::Resume`] instead, naming the token and the serial number of the last
+//! message it got. The welcome is always serial 0 and the messages of a session are numbered
+//! from 1 across all of its connections, so the server can send on what was missed.
+use rahjong::{
+    discard::Discard,
+    player::{Player, WindSet},
+    rules::jp::{
+        round::{Action, PlayerSight, Reason, ReasonKind, Round, RoundOptions},
+        win::Win,
+        yaku::Score,
+        Jp,
+    },
+    tile::{TileId, Wind},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    records::{GameRecord, RecordId, RecordSummary},
+    table::SPECTATOR_DELAY,
+    ConnectionId, SerialNumber,
+};
+
+pub const PROTOCOL_VERSION: u16 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TableId(pub u64);
+
+/// Proves a client owns a session when it reconnects.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SessionToken(pub String);
+
+impl SessionToken {
+    pub fn random() -> Self {
+        SessionToken(format!("{:032x}", rand::random::<u128>()))
+    }
+}
+
+/// The short code players share to join a room.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RoomCode(pub String);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Ruleset {
+    Riichi,
+    Custom(RoundOptions),
+}
+
+impl Ruleset {
+    pub fn round(&self, dealer: Wind) -> Round {
+        match self {
+            Ruleset::Riichi => Round::new(dealer),
+            Ruleset::Custom(options) => Round::with_options(Jp, dealer, *options),
+        }
+    }
+    /// The round dealt from `seed`, to replay a recorded game.
+    pub fn round_with_seed(&self, dealer: Wind, seed: u64) -> Round {
+        let options = match self {
+            Ruleset::Riichi => RoundOptions::default(),
+            Ruleset::Custom(options) => *options,
+        };
+        Round::with_seed(Jp, dealer, options, seed)
+    }
+}
+
+/// How long players may think, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeControl {
+    /// Time for every decision, what is left of it does not carry over.
+    pub decision: u64,
+    /// Time every seat may dip into once a decision took longer, for the whole round.
+    pub bank: u64,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        Self {
+            decision: 10_000,
+            bank: 30_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomSettings {
+    pub ruleset: Ruleset,
+    /// Seconds after which empty seats are taken by bots, never when `None`.
+    pub bots_after: Option<u64>,
+    /// Untimed when `None`.
+    pub time: Option<TimeControl>,
+    /// How many actions the full view of spectators lags behind.
+    pub spectator_delay: usize,
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        Self {
+            ruleset: Ruleset::Riichi,
+            bots_after: Some(30),
+            time: Some(TimeControl::default()),
+            spectator_delay: SPECTATOR_DELAY,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SeatInfo {
+    #[default]
+    Empty,
+    Human {
+        name: String,
+        ready: bool,
+    },
+    Bot,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomInfo {
+    pub code: RoomCode,
+    pub settings: RoomSettings,
+    pub seats: WindSet<SeatInfo>,
+    /// How many clients are in the room, seated or not.
+    pub members: usize,
+    pub spectators: usize,
+    pub started: bool,
+}
+
+/// What a spectator is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpectatorView {
+    /// What the seat sees, as it happens.
+    Seat(Player),
+    /// Every hand, some actions behind the table.
+    Full,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Payload {
+    Client(ClientMessage),
+    Server(ServerMessage),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ClientMessage {
+    Hello {
+        version: u16,
+        name: String,
+    },
+    Resume {
+        version: u16,
+        session: SessionToken,
+        /// The last serial number received, the ones after it are sent again if they are
+        /// still known.
+        acked: SerialNumber,
+    },
+    /// Ask for a fresh sight of the round.
+    Sync,
+    CreateRoom(RoomSettings),
+    ListRooms,
+    JoinRoom(RoomCode),
+    TakeSeat(Player),
+    LeaveRoom,
+    /// Wait to be matched with three other players.
+    Queue,
+    /// Watch the game in a room without taking part.
+    Spectate {
+        room: RoomCode,
+        view: SpectatorView,
+    },
+    Ready,
+    Action(Action),
+    Chat(String),
+    Resign,
+    /// List the finished games `player` sat in.
+    History {
+        player: String,
+    },
+    /// Fetch a finished game to replay it.
+    Download(RecordId),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ServerMessage {
+    Welcome {
+        version: u16,
+        connection: ConnectionId,
+        session: SessionToken,
+    },
+    Rooms(Vec<RoomInfo>),
+    /// The room the client is in, sent on joining and whenever it changes.
+    Room(RoomInfo),
+    Queued {
+        waiting: usize,
+    },
+    /// What every seat saw after the first `actions` actions, for spectators with the full view.
+    FullView {
+        actions: usize,
+        sights: Box<WindSet<PlayerSight>>,
+    },
+    /// The finished games of `player`, the latest first.
+    History {
+        player: String,
+        games: Vec<RecordSummary>,
+    },
+    Replay(Box<GameRecord>),
+    Seated {
+        table: TableId,
+        seat: Player,
+    },
+    /// Everything the receiving seat is allowed to see, sent on joining and whenever the
+    /// client has to catch up.
+    Sight(Box<PlayerSight>),
+    Event(Event),
+    /// What the receiving seat may do now.
+    LegalActions(Vec<Action>),
+    /// `player` has `millis` to act and `bank` more in their time bank, the default action is
+    /// played for them once both ran out.
+    Timer {
+        player: Player,
+        millis: u64,
+        bank: u64,
+    },
+    Chat {
+        from: Player,
+        text: String,
+    },
+    Error(ErrorMessage),
+}
+
+/// Something that happened at the table, already filtered for the receiving seat.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Event {
+    Started {
+        dealer: Player,
+    },
+    /// `tile` is only filled in for the seat that drew it.
+    Draw {
+        player: Player,
+        tile: Option<TileId>,
+    },
+    Discard(Discard),
+    /// A chi, pon, kan, riichi or nukidora.
+    Call {
+        player: Player,
+        action: Action,
+    },
+    /// A seat won, one event for each winner of the same discard.
+    Win {
+        player: Player,
+        win: Win,
+        /// Who dealt in, `None` for a tsumo.
+        from: Option<Player>,
+        score: Score,
+        payments: WindSet<i32>,
+    },
+    /// The hand ended without a winner, `tenpai` seats showed a ready hand.
+    DrawGame {
+        tenpai: Vec<Player>,
+        payments: WindSet<i32>,
+    },
+    Resign(Player),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorMessage {
+    pub kind: ReasonKind,
+    pub message: String,
+}
+
+impl From<Reason> for ErrorMessage {
+    fn from(reason: Reason) -> Self {
+        ErrorMessage {
+            kind: reason.kind(),
+            message: reason.expr().to_owned(),
+        }
+    }
+}
+
+impl From<ErrorMessage> for ServerMessage {
+    fn from(error: ErrorMessage) -> Self {
+        ServerMessage::Error(error)
+    }
+}
+
+impl From<ClientMessage> for Payload {
+    fn from(msg: ClientMessage) -> Self {
+        Payload::Client(msg)
+    }
+}
+
+impl From<ServerMessage> for Payload {
+    fn from(msg: ServerMessage) -> Self {
+        Payload::Server(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Encoding;
+    use rahjong::{
+        rules::jp::{riichi::Richii, round::Round},
+        tile::Wind,
+    };
+
+    fn round_trip(payload: Payload) {
+        for encoding in [Encoding::Bincode, Encoding::Json] {
+            let bytes = encoding.encode(&payload).unwrap();
+            let decoded: Payload = encoding.decode(&bytes).unwrap();
+            assert_eq!(format!("{decoded:?}"), format!("{payload:?}"));
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut round = Round::new(Wind::East);
+        round.start().unwrap();
+        let sight = round.player_sight(Player::EAST);
+        let tile = sight.draw.unwrap().tile();
+        let discard = Player::EAST.discard(tile);
+        round_trip(ServerMessage::Sight(Box::new(sight)).into());
+        round_trip(
+            ServerMessage::Event(Event::Call {
+                player: Player::EAST,
+                action: Action::Richii(Richii { discard }),
+            })
+            .into(),
+        );
+        round_trip(
+            ServerMessage::Event(Event::DrawGame {
+                tenpai: vec![Player::SOUTH],
+                payments: WindSet::default(),
+            })
+            .into(),
+        );
+        round_trip(
+            ServerMessage::LegalActions(vec![Action::Pass, Action::Discard(discard)]).into(),
+        );
+        round_trip(ServerMessage::from(ErrorMessage::from(Reason::invalid_operation("no"))).into());
+        round_trip(
+            ClientMessage::Hello {
+                version: PROTOCOL_VERSION,
+                name: "east".into(),
+            }
+            .into(),
+        );
+        round_trip(ClientMessage::Action(Action::Discard(discard)).into());
+    }
+}
@@ -0,0 +1,256 @@
+//! Finished games, kept on disk.
+//!
+//! Every game is one JSON file named after its id in the store's directory. The directory is
+//! read once when the store opens to index the games by player name, after that only single
+//! games are read back. Games are written on the blocking pool and served from memory until
+//! their file is in place.
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rahjong::{
+    player::{Player, WindSet},
+    rules::jp::round::{Action, Reason, Round},
+    tile::Wind,
+};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use crate::{
+    protocol::Ruleset,
+    table::{Finished, Outcome},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct RecordId(pub u64);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub id: RecordId,
+    /// Seconds since the Unix epoch.
+    pub finished_at: u64,
+    pub ruleset: Ruleset,
+    pub seed: u64,
+    pub dealer: Wind,
+    /// The name at every seat, `None` for bots.
+    pub players: WindSet<Option<String>>,
+    pub actions: Vec<(Player, Action)>,
+    pub outcome: Outcome,
+    pub scores: WindSet<i32>,
+}
+
+/// A game as listed in a player's history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordSummary {
+    pub id: RecordId,
+    pub finished_at: u64,
+    pub players: WindSet<Option<String>>,
+    pub scores: WindSet<i32>,
+}
+
+impl GameRecord {
+    /// Record a finished table, the id is given out when it is saved.
+    pub fn new(ruleset: Ruleset, players: WindSet<Option<String>>, finished: &Finished) -> Self {
+        let finished_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        GameRecord {
+            id: RecordId(0),
+            finished_at,
+            ruleset,
+            seed: finished.round.seed(),
+            dealer: finished.round.dealer(),
+            players,
+            actions: finished.round.history().to_vec(),
+            outcome: finished.outcome.clone(),
+            scores: finished.outcome.payments(),
+        }
+    }
+    pub fn summary(&self) -> RecordSummary {
+        RecordSummary {
+            id: self.id,
+            finished_at: self.finished_at,
+            players: self.players.clone(),
+            scores: self.scores,
+        }
+    }
+    /// Deal the round again and play the recorded actions on it.
+    pub fn replay(&self) -> Result<Round, Reason> {
+        let mut round = self.ruleset.round_with_seed(self.dealer, self.seed);
+        round.start()?;
+        for (player, action) in &self.actions {
+            round.apply(action, *player)?;
+        }
+        Ok(round)
+    }
+}
+
+pub struct RecordStore {
+    dir: PathBuf,
+    next: u64,
+    summaries: HashMap<RecordId, RecordSummary>,
+    by_player: HashMap<String, Vec<RecordId>>,
+    /// The saved games whose file is still being written.
+    writing: Arc<Mutex<HashMap<RecordId, GameRecord>>>,
+}
+
+impl RecordStore {
+    /// Open the store in `dir`, creating the directory if needed. Files that can't be read as a
+    /// game are left out with a warning.
+    pub fn open(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let mut store = RecordStore {
+            dir,
+            next: 1,
+            summaries: HashMap::new(),
+            by_player: HashMap::new(),
+            writing: Arc::default(),
+        };
+        for entry in fs::read_dir(&store.dir)? {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    eprintln!("listing {}: {e}", store.dir.display());
+                    continue;
+                }
+            };
+            if path.extension().is_some_and(|e| e == "json") {
+                match read(&path) {
+                    Ok(record) => store.index(&record),
+                    Err(e) => eprintln!("skipping {}: {e}", path.display()),
+                }
+            }
+        }
+        Ok(store)
+    }
+    /// Give a record a fresh id and write it on the blocking pool, the record can be loaded
+    /// right away. The handle tells whether the file was written.
+    pub fn save(&mut self, mut record: GameRecord) -> (RecordId, JoinHandle<std::io::Result<()>>) {
+        record.id = RecordId(self.next);
+        self.index(&record);
+        let id = record.id;
+        let path = self.path(id);
+        let writing = self.writing.clone();
+        writing.lock().unwrap().insert(id, record.clone());
+        let written = tokio::task::spawn_blocking(move || {
+            let written = write(&path, &record);
+            writing.lock().unwrap().remove(&record.id);
+            written
+        });
+        (id, written)
+    }
+    pub fn load(&self, id: RecordId) -> std::io::Result<Option<GameRecord>> {
+        if !self.summaries.contains_key(&id) {
+            return Ok(None);
+        }
+        if let Some(record) = self.writing.lock().unwrap().get(&id) {
+            return Ok(Some(record.clone()));
+        }
+        read(&self.path(id)).map(Some)
+    }
+    /// The games `player` sat in, the latest first.
+    pub fn games_of(&self, player: &str) -> Vec<RecordSummary> {
+        let Some(ids) = self.by_player.get(player) else {
+            return Vec::new();
+        };
+        ids.iter()
+            .rev()
+            .map(|id| self.summaries[id].clone())
+            .collect()
+    }
+    fn index(&mut self, record: &GameRecord) {
+        self.next = self.next.max(record.id.0 + 1);
+        for (_, name) in record.players.iter() {
+            if let Some(name) = name {
+                let ids = self.by_player.entry(name.clone()).or_default();
+                let at = ids.partition_point(|id| *id < record.id);
+                ids.insert(at, record.id);
+            }
+        }
+        self.summaries.insert(record.id, record.summary());
+    }
+    fn path(&self, id: RecordId) -> PathBuf {
+        self.dir.join(format!("{}.json", id.0))
+    }
+}
+
+fn write(path: &Path, record: &GameRecord) -> std::io::Result<()> {
+    // write aside first so a crash never leaves half a record behind
+    let partial = path.with_extension("partial");
+    let json = serde_json::to_vec(record).map_err(std::io::Error::other)?;
+    fs::write(&partial, json)?;
+    fs::rename(&partial, path)
+}
+
+fn read(path: &Path) -> std::io::Result<GameRecord> {
+    let json = fs::read(path)?;
+    serde_json::from_slice(&json)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finished(actions: usize) -> Finished {
+        let mut round = Round::new(Wind::East);
+        round.start().unwrap();
+        for _ in 0..actions {
+            let player = (0..4)
+                .map(|i| Player::from(Wind::from_index(i)))
+                .find(|p| round.default_action(*p).is_some())
+                .unwrap();
+            let action = round.default_action(player).unwrap();
+            round.apply(&action, player).unwrap();
+        }
+        Finished {
+            round,
+            outcome: Outcome::Resigned(Player::NORTH),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store() {
+        let dir = std::env::temp_dir().join(format!("rahjong-records-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let players = WindSet::from_fn(|p| (p != Player::NORTH).then(|| format!("{:?}", p.wind())));
+        let mut store = RecordStore::open(&dir).unwrap();
+        let (first, written) = store.save(GameRecord::new(
+            Ruleset::Riichi,
+            players.clone(),
+            &finished(5),
+        ));
+        written.await.unwrap().unwrap();
+        let played = finished(9);
+        let (second, written) = store.save(GameRecord::new(Ruleset::Riichi, players, &played));
+        assert_ne!(first, second);
+        // the game is there before its file is
+        assert!(store.load(second).unwrap().is_some());
+        written.await.unwrap().unwrap();
+
+        // a store opened on the same directory finds both games and skips what isn't one
+        fs::write(dir.join("99.json"), "{").unwrap();
+        let store = RecordStore::open(&dir).unwrap();
+        let games = store.games_of("East");
+        assert_eq!(
+            games.iter().map(|g| g.id).collect::<Vec<_>>(),
+            [second, first]
+        );
+        assert!(store.games_of("North").is_empty());
+        let record = store.load(second).unwrap().unwrap();
+        assert_eq!(record.outcome, Outcome::Resigned(Player::NORTH));
+        assert_eq!(record.actions.len(), 9);
+        let replayed = record.replay().unwrap();
+        // the replay deals the same tiles and ends where the game did
+        let sight =
+            |round: &Round| serde_json::to_value(round.player_sight(Player::SOUTH)).unwrap();
+        assert_eq!(sight(&replayed), sight(&played.round));
+        assert!(store.load(RecordId(99)).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,190 @@
+//! A session outlives the connections of one client.
+//!
+//! Everything sent to a client goes through its session, which numbers the messages and keeps the
+//! last [`RESEND_LIMIT`] of them. When the client comes back on a new connection the session sends
+//! on what the client missed, or reports it stale when that is more than it kept.
+use std::collections::VecDeque;
+
+use rahjong::rules::jp::round::Reason;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{protocol::SessionToken, ConnectionId, SerialNumber, ServerMessage};
+
+/// How many sent messages a session keeps around for a client that comes back.
+pub const RESEND_LIMIT: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resumed {
+    /// The id the client got when it first connected, its messages are still known by it.
+    pub id: ConnectionId,
+    /// The serial number the new connection continues from.
+    pub from: SerialNumber,
+    /// Some missed messages were forgotten, the client needs a fresh sight.
+    pub stale: bool,
+}
+
+struct Attach {
+    connection: mpsc::UnboundedSender<ServerMessage>,
+    acked: SerialNumber,
+    reply: oneshot::Sender<Result<Resumed, Reason>>,
+}
+
+#[derive(Debug)]
+pub struct Session {
+    token: SessionToken,
+    outbox: mpsc::UnboundedSender<ServerMessage>,
+    attach: mpsc::UnboundedSender<Attach>,
+}
+
+impl Session {
+    /// Start a session writing to `connection`, it runs until the session and every
+    /// [`Session::outbox`] are dropped.
+    pub fn spawn(
+        id: ConnectionId,
+        token: SessionToken,
+        connection: mpsc::UnboundedSender<ServerMessage>,
+    ) -> Self {
+        let (outbox, messages) = mpsc::unbounded_channel();
+        let (attach, attaches) = mpsc::unbounded_channel();
+        tokio::spawn(run(id, messages, attaches, connection));
+        Session {
+            token,
+            outbox,
+            attach,
+        }
+    }
+    pub fn token(&self) -> &SessionToken {
+        &self.token
+    }
+    /// Where to send messages for this client, whatever connection it is on.
+    pub fn outbox(&self) -> mpsc::UnboundedSender<ServerMessage> {
+        self.outbox.clone()
+    }
+    /// Move the session to a new connection. The messages after `acked` are sent on it right
+    /// away if they are still known, `reply` is answered before any of them.
+    pub fn attach(
+        &self,
+        connection: mpsc::UnboundedSender<ServerMessage>,
+        acked: SerialNumber,
+        reply: oneshot::Sender<Result<Resumed, Reason>>,
+    ) {
+        let attach = Attach {
+            connection,
+            acked,
+            reply,
+        };
+        if let Err(mpsc::error::SendError(attach)) = self.attach.send(attach) {
+            let _ = attach
+                .reply
+                .send(Err(Reason::invalid_operation("The session ended")));
+        }
+    }
+}
+
+async fn run(
+    id: ConnectionId,
+    mut messages: mpsc::UnboundedReceiver<ServerMessage>,
+    mut attaches: mpsc::UnboundedReceiver<Attach>,
+    connection: mpsc::UnboundedSender<ServerMessage>,
+) {
+    let mut connection = Some(connection);
+    // the welcome is serial 0 on every connection
+    let mut next = SerialNumber(1);
+    let mut sent: VecDeque<(SerialNumber, ServerMessage)> = VecDeque::new();
+    loop {
+        tokio::select! {
+            // what was sent before a client came back goes out before it is resumed
+            biased;
+            message = messages.recv() => {
+                let Some(message) = message else { break };
+                if connection.as_ref().is_some_and(|c| c.send(message.clone()).is_err()) {
+                    connection = None;
+                }
+                sent.push_back((next, message));
+                next.0 += 1;
+                if sent.len() > RESEND_LIMIT {
+                    sent.pop_front();
+                }
+            }
+            attach = attaches.recv() => {
+                let Some(attach) = attach else { break };
+                let oldest = sent.front().map_or(next, |(ser, _)| *ser);
+                let missed = SerialNumber(attach.acked.0 + 1);
+                let stale = missed < oldest || missed > next;
+                let from = if stale { next } else { missed };
+                let _ = attach.reply.send(Ok(Resumed { id, from, stale }));
+                if !stale {
+                    for (_, message) in sent.iter().filter(|(ser, _)| *ser >= from) {
+                        let _ = attach.connection.send(message.clone());
+                    }
+                }
+                connection = Some(attach.connection);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat(text: &str) -> ServerMessage {
+        ServerMessage::Chat {
+            from: rahjong::player::Player::EAST,
+            text: text.into(),
+        }
+    }
+
+    async fn attach(session: &Session, acked: u64) -> (Resumed, Vec<String>) {
+        let (connection, mut messages) = mpsc::unbounded_channel();
+        let (reply, resumed) = oneshot::channel();
+        session.attach(connection, SerialNumber(acked), reply);
+        let resumed = resumed.await.unwrap().unwrap();
+        // a marker through the session so everything resent has arrived
+        session.outbox().send(chat("marker")).unwrap();
+        let mut texts = Vec::new();
+        while let Some(ServerMessage::Chat { text, .. }) = messages.recv().await {
+            if text == "marker" {
+                break;
+            }
+            texts.push(text);
+        }
+        (resumed, texts)
+    }
+
+    #[tokio::test]
+    async fn test_resume() {
+        let (connection, first) = mpsc::unbounded_channel();
+        let session = Session::spawn(ConnectionId(7), SessionToken::random(), connection);
+        drop(first);
+        for i in 1..=3 {
+            session.outbox().send(chat(&i.to_string())).unwrap();
+        }
+        let (resumed, texts) = attach(&session, 1).await;
+        assert_eq!(resumed.id, ConnectionId(7));
+        assert_eq!(resumed.from, SerialNumber(2));
+        assert!(!resumed.stale);
+        assert_eq!(texts, ["2", "3"]);
+        // the marker was serial 4
+        let (resumed, texts) = attach(&session, 4).await;
+        assert_eq!(resumed.from, SerialNumber(5));
+        assert!(texts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resume_stale() {
+        let (connection, first) = mpsc::unbounded_channel();
+        let session = Session::spawn(ConnectionId(7), SessionToken::random(), connection);
+        drop(first);
+        for i in 0..RESEND_LIMIT + 10 {
+            session.outbox().send(chat(&i.to_string())).unwrap();
+        }
+        let (resumed, texts) = attach(&session, 3).await;
+        assert!(resumed.stale);
+        assert_eq!(resumed.from, SerialNumber(RESEND_LIMIT as u64 + 11));
+        assert!(texts.is_empty());
+        // claiming more than was ever sent is stale too
+        let (resumed, _) = attach(&session, 10_000).await;
+        assert!(resumed.stale);
+    }
+}
@@ -0,0 +1,1033 @@
+//! A table hosts one round for four seats.
+//!
+//! Every seat runs a task of its own that forwards the client's messages to the table and
+//! writes out what the table sends back, so the table itself never waits on a connection.
+//! Actions are checked by [`Round::apply`] and every seat is only sent its own
+//! [`Round::player_sight`], the other hands never leave the server.
+//!
+//! A seat whose client is away keeps its place, the table plays the default action for it until
+//! it is back. With a [`TimeControl`] the same happens to a seat that ran out of time.
+//!
+//! Spectators only ever receive. Those following a seat get what the seat gets but for its legal
+//! actions, those with the full view get every seat's sight only once the table is
+//! [`SPECTATOR_DELAY`] actions further.
+use std::{collections::VecDeque, sync::Arc};
+
+use futures_util::{Sink, Stream};
+use rahjong::{
+    bot::{Greedy, Strategy},
+    player::{Player, WindSet},
+    rules::jp::{
+        round::{Action, Reason, ReasonKind, Round, RoundEnd, RoundState},
+        win::Agari,
+    },
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    protocol::{ErrorMessage, Event, SpectatorView, TableId, TimeControl},
+    ClientMessage, Connection, ConnectionId, Packet, ServerMessage,
+};
+
+/// How many actions the full view of spectators lags behind by default.
+pub const SPECTATOR_DELAY: usize = 8;
+
+/// How often a running table looks at its timers.
+const TICK: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub enum TableInput {
+    Message(Player, ClientMessage),
+    /// The client of a seat left for good, a bot takes over once the round started.
+    Left(Player),
+    /// The client of a seat lost its connection and may come back.
+    Away(Player),
+    Back(Player),
+    Spectate {
+        id: ConnectionId,
+        view: SpectatorView,
+        outbox: mpsc::UnboundedSender<ServerMessage>,
+    },
+    StopSpectating(ConnectionId),
+}
+
+#[derive(Default)]
+pub enum Seat {
+    #[default]
+    Empty,
+    Human(mpsc::UnboundedSender<ServerMessage>),
+    /// Plays its strategy whenever it is asked for an action.
+    Bot(Box<dyn Strategy + Send>),
+}
+
+impl std::fmt::Debug for Seat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Seat::Empty => f.write_str("Empty"),
+            Seat::Human(outbox) => f.debug_tuple("Human").field(outbox).finish(),
+            Seat::Bot(_) => f.write_str("Bot"),
+        }
+    }
+}
+
+impl Seat {
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Seat::Empty)
+    }
+    fn outbox(&self) -> Option<&mpsc::UnboundedSender<ServerMessage>> {
+        match self {
+            Seat::Human(outbox) => Some(outbox),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Outcome {
+    /// One seat won, or more on the same discard.
+    Win {
+        wins: Vec<Agari>,
+        payments: WindSet<i32>,
+    },
+    /// The wall ran out, or the hand was aborted.
+    DrawGame {
+        tenpai: Vec<Player>,
+        payments: WindSet<i32>,
+    },
+    Resigned(Player),
+    /// Every player left before the end.
+    Abandoned,
+}
+
+impl Outcome {
+    /// Points each seat won or lost.
+    pub fn payments(&self) -> WindSet<i32> {
+        match self {
+            Outcome::Win { payments, .. } | Outcome::DrawGame { payments, .. } => *payments,
+            _ => WindSet::default(),
+        }
+    }
+    /// How `round` ended, `None` while it is played.
+    pub fn of_round(round: &Round) -> Option<Outcome> {
+        let riichi = round.riichi_payments();
+        let outcome = match round.end()? {
+            RoundEnd::Won(wins) => Outcome::Win {
+                wins: wins.clone(),
+                payments: round.end()?.payments(),
+            },
+            RoundEnd::Exhausted => {
+                let noten = round.noten_payments();
+                Outcome::DrawGame {
+                    tenpai: round.tenpai(),
+                    payments: WindSet::from_fn(|seat| noten.get(seat) + riichi.get(seat)),
+                }
+            }
+            RoundEnd::Aborted(_) => Outcome::DrawGame {
+                tenpai: Vec::new(),
+                payments: riichi,
+            },
+        };
+        Some(outcome)
+    }
+}
+
+/// What a table leaves behind once it stopped.
+pub struct Finished {
+    pub round: Round,
+    pub outcome: Outcome,
+}
+
+pub struct Table {
+    id: TableId,
+    round: Round,
+    seats: WindSet<Seat>,
+    ready: WindSet<bool>,
+    away: WindSet<bool>,
+    time: Option<TimeControl>,
+    clock: Arc<dyn Clock>,
+    /// When each seat was asked to decide, for the seats the table waits on.
+    asked: WindSet<Option<Instant>>,
+    bank: WindSet<Duration>,
+    spectators: Vec<(
+        ConnectionId,
+        SpectatorView,
+        mpsc::UnboundedSender<ServerMessage>,
+    )>,
+    spectator_delay: usize,
+    /// Full views not shown to spectators yet, the oldest first.
+    delayed: VecDeque<ServerMessage>,
+    /// The last full view spectators were shown.
+    shown: Option<ServerMessage>,
+    resigned: Option<Player>,
+    inbox: mpsc::UnboundedReceiver<TableInput>,
+    sender: mpsc::UnboundedSender<TableInput>,
+}
+
+impl Table {
+    pub fn new(id: TableId, round: Round) -> Self {
+        let (sender, inbox) = mpsc::unbounded_channel();
+        Table {
+            id,
+            round,
+            seats: WindSet::default(),
+            ready: WindSet::default(),
+            away: WindSet::default(),
+            time: None,
+            clock: Arc::new(SystemClock),
+            asked: WindSet::default(),
+            bank: WindSet::default(),
+            spectators: Vec::new(),
+            spectator_delay: SPECTATOR_DELAY,
+            delayed: VecDeque::new(),
+            shown: None,
+            resigned: None,
+            inbox,
+            sender,
+        }
+    }
+    /// Time every decision, seats that run out of time play the default action.
+    pub fn with_time_control(mut self, time: TimeControl) -> Self {
+        let bank = Duration::from_millis(time.bank);
+        self.time = Some(time);
+        self.bank = WindSet::from_fn(|_| bank);
+        self
+    }
+    pub fn with_spectator_delay(mut self, actions: usize) -> Self {
+        self.spectator_delay = actions;
+        self
+    }
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+    pub fn id(&self) -> TableId {
+        self.id
+    }
+    pub fn is_full(&self) -> bool {
+        self.seats.iter().all(|(_, s)| !s.is_empty())
+    }
+    /// Where to send messages for this table, for seats that are not [`Table::seat`]ed
+    /// connections.
+    pub fn sender(&self) -> mpsc::UnboundedSender<TableInput> {
+        self.sender.clone()
+    }
+    /// Seat a connection, it is told its seat right away.
+    pub fn seat<I, O>(&mut self, seat: Player, connection: Connection<I, O>) -> Result<(), Reason>
+    where
+        I: Stream<Item = Result<Packet, std::io::Error>> + Unpin + Send + 'static,
+        O: Sink<Packet, Error = std::io::Error> + Unpin + Send + 'static,
+    {
+        if !self.seats.get(seat).is_empty() {
+            return Err(Reason::new(ReasonKind::TableFull, "This seat is taken"));
+        }
+        let outbox = connection.spawn(
+            self.sender.clone(),
+            move |message| TableInput::Message(seat, message),
+            TableInput::Left(seat),
+        );
+        self.seat_outbox(seat, outbox)
+    }
+    /// Seat a client whose messages are passed in through [`Table::sender`].
+    pub fn seat_outbox(
+        &mut self,
+        seat: Player,
+        outbox: mpsc::UnboundedSender<ServerMessage>,
+    ) -> Result<(), Reason> {
+        if !self.seats.get(seat).is_empty() {
+            return Err(Reason::new(ReasonKind::TableFull, "This seat is taken"));
+        }
+        let _ = outbox.send(ServerMessage::Seated {
+            table: self.id,
+            seat,
+        });
+        self.seats.insert(seat, Seat::Human(outbox));
+        Ok(())
+    }
+    /// Seat a [`Greedy`] bot, it is always ready.
+    pub fn seat_bot(&mut self, seat: Player) -> Result<(), Reason> {
+        self.seat_strategy(seat, self.greedy())
+    }
+    /// Seat a bot that plays `strategy`.
+    pub fn seat_strategy(
+        &mut self,
+        seat: Player,
+        strategy: Box<dyn Strategy + Send>,
+    ) -> Result<(), Reason> {
+        if !self.seats.get(seat).is_empty() {
+            return Err(Reason::new(ReasonKind::TableFull, "This seat is taken"));
+        }
+        self.seats.insert(seat, Seat::Bot(strategy));
+        self.ready.insert(seat, true);
+        Ok(())
+    }
+    /// Run the table until the round ends, someone resigns or every human left.
+    pub async fn run(mut self) -> Finished {
+        let mut ticks = tokio::time::interval(TICK);
+        while !self.is_over() {
+            tokio::select! {
+                input = self.inbox.recv() => {
+                    let Some(input) = input else { break };
+                    self.input(input);
+                }
+                _ = ticks.tick() => self.tick(),
+            }
+        }
+        // the round is over, nothing is left to hide
+        while let Some(view) = self.delayed.pop_front() {
+            self.show(view);
+        }
+        let outcome = if let Some(outcome) = Outcome::of_round(&self.round) {
+            outcome
+        } else if let Some(player) = self.resigned {
+            Outcome::Resigned(player)
+        } else {
+            Outcome::Abandoned
+        };
+        Finished {
+            round: self.round,
+            outcome,
+        }
+    }
+    pub fn is_over(&self) -> bool {
+        self.resigned.is_some()
+            || self.round.is_end()
+            || self.seats.iter().all(|(_, s)| s.outbox().is_none())
+    }
+    pub fn round(&self) -> &Round {
+        &self.round
+    }
+    pub fn input(&mut self, input: TableInput) {
+        match input {
+            TableInput::Message(player, message) => self.handle(player, message),
+            TableInput::Left(player) => {
+                if matches!(self.round.state(), RoundState::Init) {
+                    self.seats.insert(player, Seat::Empty);
+                    self.ready.insert(player, false);
+                } else {
+                    self.seats.insert(player, Seat::Bot(self.greedy()));
+                }
+                self.play_bots();
+                self.sync();
+            }
+            TableInput::Away(player) => {
+                self.away.insert(player, true);
+                self.play_bots();
+                self.sync();
+            }
+            TableInput::Back(player) => self.away.insert(player, false),
+            TableInput::Spectate { id, view, outbox } => {
+                match view {
+                    SpectatorView::Seat(seat)
+                        if !matches!(self.round.state(), RoundState::Init) =>
+                    {
+                        let sight = Box::new(self.round.player_sight(seat));
+                        let _ = outbox.send(ServerMessage::Sight(sight));
+                    }
+                    SpectatorView::Full => {
+                        if let Some(shown) = &self.shown {
+                            let _ = outbox.send(shown.clone());
+                        }
+                    }
+                    _ => {}
+                }
+                self.spectators.push((id, view, outbox));
+            }
+            TableInput::StopSpectating(id) => self.spectators.retain(|(s, _, _)| *s != id),
+        }
+    }
+    /// Play the default action for every seat that ran out of time.
+    pub fn tick(&mut self) {
+        let Some(time) = self.time else {
+            return;
+        };
+        let now = self.clock.now();
+        let decision = Duration::from_millis(time.decision);
+        let expired: Vec<Player> = self
+            .asked
+            .iter()
+            .filter(|(seat, asked)| {
+                asked.is_some_and(|asked| now >= asked + decision + *self.bank.get(*seat))
+            })
+            .map(|(seat, _)| seat)
+            .collect();
+        if expired.is_empty() {
+            return;
+        }
+        for seat in expired {
+            self.asked.insert(seat, None);
+            self.bank.insert(seat, Duration::ZERO);
+            if let Some(action) = self.round.default_action(seat) {
+                self.act(seat, action);
+            }
+        }
+        self.play_bots();
+        self.sync();
+    }
+    fn handle(&mut self, player: Player, message: ClientMessage) {
+        match message {
+            ClientMessage::Ready => {
+                self.ready.insert(player, true);
+                let all_ready = self.ready.iter().all(|(_, r)| *r);
+                if all_ready && self.is_full() && matches!(self.round.state(), RoundState::Init) {
+                    self.start();
+                }
+            }
+            ClientMessage::Action(action) => {
+                if self.act(player, action) {
+                    self.charge(player);
+                    self.play_bots();
+                    self.sync();
+                }
+            }
+            ClientMessage::Sync => self.sync_seat(player),
+            ClientMessage::Chat(text) => self.broadcast(ServerMessage::Chat { from: player, text }),
+            ClientMessage::Resign => {
+                self.broadcast(ServerMessage::Event(Event::Resign(player)));
+                self.resigned = Some(player);
+            }
+            _ => self.error(player, Reason::invalid_operation("Already seated")),
+        }
+    }
+    fn start(&mut self) {
+        if let Err(reason) = self.round.start() {
+            let error = ErrorMessage::from(reason);
+            self.broadcast(error.into());
+            return;
+        }
+        self.broadcast(ServerMessage::Event(Event::Started {
+            dealer: self.round.dealer().into(),
+        }));
+        self.record_view();
+        self.play_bots();
+        self.sync();
+    }
+    /// Apply and announce an action, `false` if it was refused.
+    fn act(&mut self, player: Player, action: Action) -> bool {
+        let draw = self.round.draw();
+        if let Err(reason) = self.round.apply(&action, player) {
+            self.error(player, reason);
+            return false;
+        }
+        match action {
+            // a win is announced once every seat decided
+            Action::Pass | Action::Tsumo(_) | Action::Ron(_) | Action::Chankan(_) => {}
+            Action::Discard(discard) => {
+                self.broadcast(ServerMessage::Event(Event::Discard(discard)))
+            }
+            action => self.broadcast(ServerMessage::Event(Event::Call { player, action })),
+        }
+        if let (RoundState::WaitDiscard(to), Some(drawn)) = (*self.round.state(), self.round.draw())
+        {
+            if draw != Some(drawn) {
+                for (seat, _) in self.seats.iter() {
+                    let tile = (seat == to).then_some(drawn.tile());
+                    for outbox in self.followers(seat) {
+                        let _ = outbox.send(ServerMessage::Event(Event::Draw { player: to, tile }));
+                    }
+                }
+                for outbox in self.full_spectators() {
+                    let _ = outbox.send(ServerMessage::Event(Event::Draw {
+                        player: to,
+                        tile: None,
+                    }));
+                }
+            }
+        }
+        match Outcome::of_round(&self.round) {
+            Some(Outcome::Win { wins, .. }) => {
+                for agari in wins {
+                    self.broadcast(ServerMessage::Event(Event::Win {
+                        player: agari.player,
+                        win: agari.win,
+                        from: agari.from,
+                        score: agari.score,
+                        payments: agari.payments,
+                    }));
+                }
+            }
+            Some(Outcome::DrawGame { tenpai, payments }) => {
+                self.broadcast(ServerMessage::Event(Event::DrawGame { tenpai, payments }))
+            }
+            _ => {}
+        }
+        self.record_view();
+        true
+    }
+    /// Hold back the full view after the last action, and show the one that is now old enough.
+    fn record_view(&mut self) {
+        let sights = WindSet::from_fn(|seat| self.round.player_sight(seat));
+        self.delayed.push_back(ServerMessage::FullView {
+            actions: self.round.history().len(),
+            sights: Box::new(sights),
+        });
+        while self.delayed.len() > self.spectator_delay {
+            let view = self.delayed.pop_front().expect("more views than the delay");
+            self.show(view);
+        }
+    }
+    fn show(&mut self, view: ServerMessage) {
+        for outbox in self.full_spectators() {
+            let _ = outbox.send(view.clone());
+        }
+        self.shown = Some(view);
+    }
+    /// The seat's own client and the spectators following it.
+    fn followers(
+        &self,
+        seat: Player,
+    ) -> impl Iterator<Item = &mpsc::UnboundedSender<ServerMessage>> {
+        self.seats.get(seat).outbox().into_iter().chain(
+            self.spectators
+                .iter()
+                .filter(move |(_, view, _)| *view == SpectatorView::Seat(seat))
+                .map(|(_, _, outbox)| outbox),
+        )
+    }
+    fn full_spectators(&self) -> impl Iterator<Item = &mpsc::UnboundedSender<ServerMessage>> {
+        self.spectators
+            .iter()
+            .filter(|(_, view, _)| *view == SpectatorView::Full)
+            .map(|(_, _, outbox)| outbox)
+    }
+    fn greedy(&self) -> Box<dyn Strategy + Send> {
+        Box::new(Greedy {
+            round_wind: self.round.round_wind(),
+        })
+    }
+
+    /// Let the bots and the seats that are away act until a human is needed, a seat that is
+    /// away plays the default action.
+    fn play_bots(&mut self) {
+        if matches!(self.round.state(), RoundState::Init) {
+            return;
+        }
+        while !self.round.is_end() {
+            let next = self
+                .seats
+                .iter()
+                .filter(|(seat, s)| matches!(s, Seat::Bot(_)) || *self.away.get(*seat))
+                .map(|(seat, _)| (seat, self.round.legal_actions(seat)))
+                .find(|(_, actions)| !actions.is_empty());
+            let Some((seat, actions)) = next else {
+                break;
+            };
+            let action = match self.seats.get_mut(seat) {
+                Seat::Bot(strategy) => {
+                    let sight = self.round.player_sight(seat);
+                    strategy.choose(seat, &sight, &actions)
+                }
+                _ => match self.round.default_action(seat) {
+                    Some(action) => action,
+                    None => break,
+                },
+            };
+            if !self.act(seat, action) {
+                break;
+            }
+        }
+    }
+    /// Take the time `player` spent over the decision time out of their bank.
+    fn charge(&mut self, player: Player) {
+        let (Some(time), Some(asked)) = (self.time, self.asked.get_mut(player).take()) else {
+            return;
+        };
+        let spent = self.clock.now() - asked;
+        let over = spent.saturating_sub(Duration::from_millis(time.decision));
+        let bank = self.bank.get(player).saturating_sub(over);
+        self.bank.insert(player, bank);
+    }
+    /// Send every seat what it can see of the round and what it may do, and start the clock of
+    /// the seats the table now waits on.
+    fn sync(&mut self) {
+        for (seat, _) in self.seats.iter() {
+            self.sync_seat(seat);
+        }
+        self.start_timers();
+    }
+    fn start_timers(&mut self) {
+        let Some(time) = self.time else {
+            return;
+        };
+        let now = self.clock.now();
+        let waiting: Vec<Player> = self
+            .seats
+            .iter()
+            .map(|(seat, _)| seat)
+            .filter(|seat| {
+                self.seats.get(*seat).outbox().is_some()
+                    && !*self.away.get(*seat)
+                    && !self.round.legal_actions(*seat).is_empty()
+            })
+            .collect();
+        for (seat, _) in self.seats.iter() {
+            if !waiting.contains(&seat) {
+                self.asked.insert(seat, None);
+            }
+        }
+        for seat in waiting {
+            if self.asked.get(seat).is_some() {
+                continue;
+            }
+            self.asked.insert(seat, Some(now));
+            self.broadcast(ServerMessage::Timer {
+                player: seat,
+                millis: time.decision,
+                bank: self.bank.get(seat).as_millis() as u64,
+            });
+        }
+    }
+    fn sync_seat(&self, seat: Player) {
+        if matches!(self.round.state(), RoundState::Init) {
+            return;
+        }
+        let sight = Box::new(self.round.player_sight(seat));
+        for outbox in self.followers(seat) {
+            let _ = outbox.send(ServerMessage::Sight(sight.clone()));
+        }
+        if let Some(outbox) = self.seats.get(seat).outbox() {
+            let _ = outbox.send(ServerMessage::LegalActions(self.round.legal_actions(seat)));
+        }
+    }
+    /// Send a public message to every seat and spectator.
+    fn broadcast(&self, message: ServerMessage) {
+        let seats = self.seats.iter().filter_map(|(_, s)| s.outbox());
+        let spectators = self.spectators.iter().map(|(_, _, outbox)| outbox);
+        for outbox in seats.chain(spectators) {
+            let _ = outbox.send(message.clone());
+        }
+    }
+    fn error(&self, player: Player, reason: Reason) {
+        if let Some(outbox) = self.seats.get(player).outbox() {
+            let _ = outbox.send(ErrorMessage::from(reason).into());
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        clock::ManualClock,
+        protocol::{SessionToken, PROTOCOL_VERSION},
+        ConnectionId, Encoding, Payload,
+    };
+    use rahjong::{bot::Tsumogiri, rules::jp::win::Win, tile::Wind};
+    use tokio::io::{DuplexStream, ReadHalf, WriteHalf};
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    pub(crate) type TestConnection = Connection<
+        FramedRead<ReadHalf<DuplexStream>, crate::PacketCodec>,
+        FramedWrite<WriteHalf<DuplexStream>, crate::PacketCodec>,
+    >;
+
+    /// A client connection and the server end of it.
+    pub(crate) fn pair(id: u64) -> (TestConnection, TestConnection) {
+        let (client, server) = tokio::io::duplex(1 << 16);
+        let (client_read, client_write) = tokio::io::split(client);
+        let (server_read, server_write) = tokio::io::split(server);
+        (
+            Connection::framed(client_read, client_write, ConnectionId(id), Encoding::Json),
+            Connection::framed(
+                server_read,
+                server_write,
+                ConnectionId(id),
+                Encoding::Bincode,
+            ),
+        )
+    }
+
+    pub(crate) async fn next(client: &mut TestConnection) -> ServerMessage {
+        match client.recv().await.unwrap() {
+            Payload::Server(message) => message,
+            Payload::Client(message) => panic!("unexpected {message:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_table() {
+        let mut table = Table::new(TableId(1), Round::new(Wind::East));
+        let mut clients = Vec::new();
+        for (i, wind) in Wind::enumerate().enumerate() {
+            let (mut client, mut server) = pair(i as u64);
+            let hello = ClientMessage::Hello {
+                version: PROTOCOL_VERSION,
+                name: format!("{wind:?}"),
+            };
+            client.send(hello.into()).await.unwrap();
+            server.accept().await.unwrap();
+            server.welcome(SessionToken::random()).await.unwrap();
+            assert!(matches!(
+                next(&mut client).await,
+                ServerMessage::Welcome { .. }
+            ));
+            table.seat(wind.into(), server).unwrap();
+            assert!(
+                matches!(next(&mut client).await, ServerMessage::Seated { seat, .. } if seat == wind.into())
+            );
+            clients.push(client);
+        }
+        let (_, taken) = pair(4);
+        assert!(table.seat(Player::EAST, taken).is_err());
+        let table = tokio::spawn(table.run());
+        for client in &mut clients {
+            client.send(ClientMessage::Ready.into()).await.unwrap();
+        }
+        let mut discard = None;
+        for (i, client) in clients.iter_mut().enumerate() {
+            assert!(matches!(
+                next(client).await,
+                ServerMessage::Event(Event::Started { .. })
+            ));
+            let ServerMessage::Sight(sight) = next(client).await else {
+                panic!("expected a sight");
+            };
+            assert_eq!(sight.self_deck.hand.len(), 13);
+            assert_eq!(
+                sight
+                    .other_hand_size
+                    .iter()
+                    .filter(|(_, s)| **s == 13)
+                    .count(),
+                3
+            );
+            let ServerMessage::LegalActions(actions) = next(client).await else {
+                panic!("expected legal actions");
+            };
+            if i == 0 {
+                let discards = actions
+                    .iter()
+                    .filter(|a| matches!(a, Action::Discard(_)))
+                    .count();
+                assert_eq!(discards, 14);
+                discard = Some(Player::EAST.discard(sight.draw.unwrap().tile()));
+            } else {
+                assert!(actions.is_empty());
+            }
+        }
+        let discard = discard.unwrap();
+        clients[1]
+            .send(ClientMessage::Action(Action::Discard(discard)).into())
+            .await
+            .unwrap();
+        match next(&mut clients[1]).await {
+            ServerMessage::Error(error) => assert_eq!(error.kind, ReasonKind::InvalidOperation),
+            message => panic!("unexpected {message:?}"),
+        }
+        clients[0]
+            .send(ClientMessage::Action(Action::Discard(discard)).into())
+            .await
+            .unwrap();
+        for client in &mut clients {
+            assert!(
+                matches!(next(client).await, ServerMessage::Event(Event::Discard(d)) if d == discard)
+            );
+        }
+        for client in &mut clients[1..] {
+            client
+                .send(ClientMessage::Action(Action::Pass).into())
+                .await
+                .unwrap();
+        }
+        // skip the syncs after every pass until south draws
+        for (i, client) in clients.iter_mut().enumerate() {
+            let (player, tile) = loop {
+                if let ServerMessage::Event(Event::Draw { player, tile }) = next(client).await {
+                    break (player, tile);
+                }
+            };
+            assert_eq!(player, Player::SOUTH);
+            assert_eq!(tile.is_some(), i == 1);
+        }
+        clients[0].send(ClientMessage::Resign.into()).await.unwrap();
+        let finished = table.await.unwrap();
+        assert_eq!(finished.outcome, Outcome::Resigned(Player::EAST));
+        assert_eq!(finished.round.history().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_away() {
+        let mut table = Table::new(TableId(1), Round::new(Wind::East));
+        let (outbox, mut messages) = mpsc::unbounded_channel();
+        table.seat_outbox(Player::EAST, outbox).unwrap();
+        for wind in [Wind::South, Wind::West, Wind::North] {
+            table
+                .seat_strategy(wind.into(), Box::new(Tsumogiri))
+                .unwrap();
+        }
+        let inputs = table.sender();
+        let table = tokio::spawn(table.run());
+        inputs
+            .send(TableInput::Message(Player::EAST, ClientMessage::Ready))
+            .unwrap();
+        inputs.send(TableInput::Away(Player::EAST)).unwrap();
+        // nobody is left to wait for, the table plays east to the end of the round
+        let Finished { round, outcome } = table.await.unwrap();
+        assert!(round.is_end());
+        let Outcome::DrawGame { payments, .. } = outcome else {
+            panic!("the wall should run out");
+        };
+        assert_eq!(payments.iter().map(|(_, p)| p).sum::<i32>(), 0);
+        assert!(round.history().iter().any(
+            |(player, action)| *player == Player::EAST && matches!(action, Action::Discard(_))
+        ));
+        let mut events = 0;
+        while let Ok(message) = messages.try_recv() {
+            events += matches!(message, ServerMessage::Event(_)) as usize;
+        }
+        assert!(events > 4);
+    }
+
+    #[test]
+    fn test_left_before_start() {
+        let mut table = Table::new(TableId(1), Round::new(Wind::East));
+        let (outbox, _east) = mpsc::unbounded_channel();
+        table.seat_outbox(Player::EAST, outbox).unwrap();
+        let (outbox, _south) = mpsc::unbounded_channel();
+        table.seat_outbox(Player::SOUTH, outbox).unwrap();
+        for wind in [Wind::West, Wind::North] {
+            table.seat_bot(wind.into()).unwrap();
+        }
+        table.input(TableInput::Message(Player::SOUTH, ClientMessage::Ready));
+        table.input(TableInput::Left(Player::SOUTH));
+        // whoever takes the seat next has to be ready on their own
+        let (outbox, _south) = mpsc::unbounded_channel();
+        table.seat_outbox(Player::SOUTH, outbox).unwrap();
+        table.input(TableInput::Message(Player::EAST, ClientMessage::Ready));
+        assert!(matches!(table.round().state(), RoundState::Init));
+        table.input(TableInput::Message(Player::SOUTH, ClientMessage::Ready));
+        assert!(!matches!(table.round().state(), RoundState::Init));
+    }
+
+    #[test]
+    fn test_sync_hides_draw() {
+        let mut table = Table::new(TableId(1), Round::new(Wind::East));
+        let (outbox, mut east) = mpsc::unbounded_channel();
+        table.seat_outbox(Player::EAST, outbox).unwrap();
+        let (outbox, mut south) = mpsc::unbounded_channel();
+        table.seat_outbox(Player::SOUTH, outbox).unwrap();
+        for wind in [Wind::West, Wind::North] {
+            table.seat_bot(wind.into()).unwrap();
+        }
+        table.input(TableInput::Message(Player::EAST, ClientMessage::Ready));
+        table.input(TableInput::Message(Player::SOUTH, ClientMessage::Ready));
+        let mut synced = |seat: Player, messages: &mut mpsc::UnboundedReceiver<ServerMessage>| {
+            while messages.try_recv().is_ok() {}
+            table.input(TableInput::Message(seat, ClientMessage::Sync));
+            loop {
+                if let ServerMessage::Sight(sight) = messages.try_recv().unwrap() {
+                    break sight;
+                }
+            }
+        };
+        // only the dealer sees the tile they drew
+        let sight = synced(Player::SOUTH, &mut south);
+        assert_eq!(sight.to_discard, Some(Player::EAST));
+        assert!(sight.draw.is_none());
+        assert!(synced(Player::EAST, &mut east).draw.is_some());
+    }
+
+    /// Play a seeded round where east is a greedy bot behind a client and the others discard
+    /// what they draw, returns the table's end and the wins it announced.
+    async fn play_greedy_east(seed: u64) -> (Finished, Vec<Event>) {
+        use rahjong::rules::jp::{round::RoundOptions, Jp};
+        let round = Round::with_seed(Jp, Wind::East, RoundOptions::default(), seed);
+        let mut table = Table::new(TableId(1), round);
+        let (outbox, mut messages) = mpsc::unbounded_channel();
+        table.seat_outbox(Player::EAST, outbox).unwrap();
+        for wind in [Wind::South, Wind::West, Wind::North] {
+            table
+                .seat_strategy(wind.into(), Box::new(Tsumogiri))
+                .unwrap();
+        }
+        let inputs = table.sender();
+        let table = tokio::spawn(table.run());
+        inputs
+            .send(TableInput::Message(Player::EAST, ClientMessage::Ready))
+            .unwrap();
+        let mut greedy = Greedy::default();
+        let mut sight = None;
+        let mut wins = Vec::new();
+        while let Some(message) = messages.recv().await {
+            match message {
+                ServerMessage::Sight(seen) => sight = Some(seen),
+                ServerMessage::LegalActions(actions) if !actions.is_empty() => {
+                    let sight = sight.as_ref().expect("a sight comes first");
+                    let action = greedy.choose(Player::EAST, sight, &actions);
+                    let message = ClientMessage::Action(action);
+                    inputs
+                        .send(TableInput::Message(Player::EAST, message))
+                        .unwrap();
+                }
+                ServerMessage::Event(event @ Event::Win { .. }) => wins.push(event),
+                _ => {}
+            }
+        }
+        (table.await.unwrap(), wins)
+    }
+
+    #[tokio::test]
+    async fn test_tsumo() {
+        // east draws the winning tile with this wall
+        let (Finished { round, outcome }, wins) = play_greedy_east(2).await;
+        assert!(round.is_end());
+        let Outcome::Win {
+            wins: agari,
+            payments,
+        } = outcome
+        else {
+            panic!("east should win");
+        };
+        assert_eq!(agari.len(), 1);
+        assert_eq!(agari[0].player, Player::EAST);
+        assert_eq!(agari[0].from, None);
+        assert_eq!(payments.iter().map(|(_, p)| p).sum::<i32>(), 0);
+        assert!(payments
+            .iter()
+            .all(|(seat, p)| (seat == Player::EAST) == (*p > 0)));
+        assert!(matches!(
+            wins.as_slice(),
+            [Event::Win { player, win: Win::Tsumo { .. }, .. }] if *player == Player::EAST
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ron() {
+        // east wins on a tile north lets go with this wall
+        let (Finished { outcome, .. }, wins) = play_greedy_east(0).await;
+        let Outcome::Win {
+            wins: agari,
+            payments,
+        } = outcome
+        else {
+            panic!("east should win");
+        };
+        assert_eq!(agari[0].from, Some(Player::NORTH));
+        let points = agari[0].score.ron(true) as i32;
+        assert_eq!(*payments.get(Player::EAST), points);
+        assert_eq!(*payments.get(Player::NORTH), -points);
+        assert_eq!(*payments.get(Player::SOUTH), 0);
+        assert!(matches!(
+            wins.as_slice(),
+            [Event::Win { win: Win::Ron { .. }, payments: paid, .. }] if *paid == payments
+        ));
+    }
+
+    fn timers(messages: &mut mpsc::UnboundedReceiver<ServerMessage>) -> Vec<(Player, u64)> {
+        let mut timers = Vec::new();
+        while let Ok(message) = messages.try_recv() {
+            if let ServerMessage::Timer { player, bank, .. } = message {
+                timers.push((player, bank));
+            }
+        }
+        timers
+    }
+
+    #[test]
+    fn test_time_control() {
+        let clock = ManualClock::new();
+        let time = TimeControl {
+            decision: 1_000,
+            bank: 2_000,
+        };
+        let mut table = Table::new(TableId(1), Round::new(Wind::East))
+            .with_time_control(time)
+            .with_clock(clock.clone());
+        let (outbox, mut messages) = mpsc::unbounded_channel();
+        table.seat_outbox(Player::EAST, outbox).unwrap();
+        for wind in [Wind::South, Wind::West, Wind::North] {
+            table
+                .seat_strategy(wind.into(), Box::new(Tsumogiri))
+                .unwrap();
+        }
+        table.input(TableInput::Message(Player::EAST, ClientMessage::Ready));
+        assert_eq!(timers(&mut messages), [(Player::EAST, 2_000)]);
+
+        // half a second over the decision time comes out of the bank
+        clock.advance(Duration::from_millis(1_500));
+        let discard = table.round().default_action(Player::EAST).unwrap();
+        table.input(TableInput::Message(
+            Player::EAST,
+            ClientMessage::Action(discard),
+        ));
+        assert_eq!(timers(&mut messages).last(), Some(&(Player::EAST, 1_500)));
+
+        clock.advance(Duration::from_millis(2_400));
+        table.tick();
+        assert!(timers(&mut messages).is_empty());
+        let played = table.round().history().len();
+        clock.advance(Duration::from_millis(200));
+        table.tick();
+        assert!(table.round().history().len() > played);
+        assert!(table.round().history()[played..]
+            .iter()
+            .any(|(player, _)| *player == Player::EAST));
+        assert_eq!(timers(&mut messages).last(), Some(&(Player::EAST, 0)));
+    }
+
+    #[test]
+    fn test_spectators() {
+        let mut table = Table::new(TableId(1), Round::new(Wind::East)).with_spectator_delay(2);
+        let (outbox, _messages) = mpsc::unbounded_channel();
+        table.seat_outbox(Player::EAST, outbox).unwrap();
+        for wind in [Wind::South, Wind::West, Wind::North] {
+            table.seat_bot(wind.into()).unwrap();
+        }
+        let (outbox, mut full) = mpsc::unbounded_channel();
+        let view = SpectatorView::Full;
+        table.input(TableInput::Spectate {
+            id: ConnectionId(10),
+            view,
+            outbox,
+        });
+        let (outbox, mut south) = mpsc::unbounded_channel();
+        let view = SpectatorView::Seat(Player::SOUTH);
+        table.input(TableInput::Spectate {
+            id: ConnectionId(11),
+            view,
+            outbox,
+        });
+        table.input(TableInput::Message(Player::EAST, ClientMessage::Ready));
+        for _ in 0..3 {
+            let discard = table.round().default_action(Player::EAST).unwrap();
+            let message = ClientMessage::Action(discard);
+            table.input(TableInput::Message(Player::EAST, message));
+        }
+        let played = table.round().history().len();
+
+        let mut views = 0;
+        while let Ok(message) = full.try_recv() {
+            match message {
+                ServerMessage::FullView { actions, sights } => {
+                    views += 1;
+                    assert!(actions + 2 <= played);
+                    assert_eq!(sights.get(Player::WEST).self_deck.hand.len(), 13);
+                }
+                ServerMessage::Event(Event::Draw { tile, .. }) => assert!(tile.is_none()),
+                ServerMessage::Sight(_) | ServerMessage::LegalActions(_) => {
+                    panic!("the full view only comes delayed")
+                }
+                _ => {}
+            }
+        }
+        assert!(views > 0);
+
+        let mut south_draws = 0;
+        while let Ok(message) = south.try_recv() {
+            match message {
+                ServerMessage::Sight(sight) => assert!(sight.self_deck.hand.len() >= 13),
+                ServerMessage::Event(Event::Draw { player, tile }) => {
+                    assert_eq!(tile.is_some(), player == Player::SOUTH);
+                    south_draws += tile.is_some() as usize;
+                }
+                ServerMessage::LegalActions(_) => panic!("spectators can't act"),
+                _ => {}
+            }
+        }
+        assert!(south_draws > 0);
+    }
+}
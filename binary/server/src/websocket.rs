@@ -0,0 +1,126 @@
+//! Packets over WebSocket, for browser clients.
+//!
+//! A packet takes one message: JSON in a text message, bincode in a binary one. Either is read,
+//! and packets are written in the encoding the client last used, so the rest of the server sees
+//! a [`Connection`] like any other.
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    task::{ready, Context, Poll},
+};
+
+use futures_util::{
+    stream::{SplitSink, SplitStream},
+    Sink, SinkExt, Stream, StreamExt,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
+
+use crate::{accept, lobby, lobby::LobbyInput, Connection, ConnectionId, Encoding, Packet};
+
+pub struct WsPull<S> {
+    stream: SplitStream<WebSocketStream<S>>,
+    encoding: Arc<AtomicU8>,
+}
+
+pub struct WsPush<S> {
+    sink: SplitSink<WebSocketStream<S>, Message>,
+    encoding: Arc<AtomicU8>,
+}
+
+pub type WsConnection<S = TcpStream> = Connection<WsPull<S>, WsPush<S>>;
+
+impl<S: AsyncRead + AsyncWrite + Unpin> WsConnection<S> {
+    /// Packets are written with `encoding` until the client sends one of its own.
+    pub fn websocket(ws: WebSocketStream<S>, id: ConnectionId, encoding: Encoding) -> Self {
+        let (sink, stream) = ws.split();
+        let encoding = Arc::new(AtomicU8::new(encoding.into()));
+        Connection::new(
+            WsPull {
+                stream,
+                encoding: encoding.clone(),
+            },
+            WsPush { sink, encoding },
+            id,
+        )
+    }
+}
+
+fn ws_error(e: tokio_tungstenite::tungstenite::Error) -> std::io::Error {
+    std::io::Error::other(e)
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Stream for WsPull<S> {
+    type Item = Result<Packet, std::io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let (encoding, body) = match ready!(self.stream.poll_next_unpin(cx)) {
+                None | Some(Ok(Message::Close(_))) => return Poll::Ready(None),
+                Some(Err(e)) => return Poll::Ready(Some(Err(ws_error(e)))),
+                Some(Ok(Message::Text(text))) => (Encoding::Json, text.into_bytes()),
+                Some(Ok(Message::Binary(bytes))) => (Encoding::Bincode, bytes),
+                // pings are answered by tungstenite
+                Some(Ok(_)) => continue,
+            };
+            self.encoding.store(encoding.into(), Ordering::Relaxed);
+            return Poll::Ready(Some(encoding.decode(&body)));
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Sink<Packet> for WsPush<S> {
+    type Error = std::io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_ready_unpin(cx).map_err(ws_error)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, packet: Packet) -> Result<(), Self::Error> {
+        let encoding = Encoding::try_from(self.encoding.load(Ordering::Relaxed))
+            .expect("only encodings are stored");
+        let body = encoding.encode(&packet)?;
+        let message = match encoding {
+            Encoding::Json => Message::Text(String::from_utf8(body).expect("JSON is UTF-8")),
+            Encoding::Bincode => Message::Binary(body),
+        };
+        self.sink.start_send_unpin(message).map_err(ws_error)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_flush_unpin(cx).map_err(ws_error)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_close_unpin(cx).map_err(ws_error)
+    }
+}
+
+/// Accept WebSocket clients on `listener` and admit them to the lobby, this runs for as long as
+/// the server does.
+pub async fn serve(listener: TcpListener, inputs: mpsc::UnboundedSender<LobbyInput>) {
+    loop {
+        let (stream, peer) = accept(&listener).await;
+        let inputs = inputs.clone();
+        tokio::spawn(async move {
+            let ws = match tokio_tungstenite::accept_async(stream).await {
+                Ok(ws) => ws,
+                Err(e) => {
+                    eprintln!("{peer}: {e}");
+                    return;
+                }
+            };
+            let connection = WsConnection::websocket(ws, ConnectionId::next(), Encoding::Json);
+            if let Err(e) = lobby::admit(connection, inputs).await {
+                eprintln!("{peer}: {e}");
+            }
+        });
+    }
+}
@@ -0,0 +1,109 @@
+use futures_util::{SinkExt, StreamExt};
+use rahjong_server::{
+    lobby::Lobby,
+    protocol::{RoomSettings, PROTOCOL_VERSION},
+    serve_tcp,
+    websocket::{self, WsConnection},
+    ClientMessage, ConnectionId, Encoding, Packet, Payload, SerialNumber, ServerMessage,
+    TcpConnection,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream};
+
+/// A lobby served over TCP and WebSocket, returns both addresses.
+async fn server() -> (String, String) {
+    let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let ws = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addrs = (
+        tcp.local_addr().unwrap().to_string(),
+        format!("ws://{}", ws.local_addr().unwrap()),
+    );
+    let lobby = Lobby::new();
+    tokio::spawn(serve_tcp(tcp, lobby.sender()));
+    tokio::spawn(websocket::serve(ws, lobby.sender()));
+    tokio::spawn(lobby.run());
+    addrs
+}
+
+fn hello(name: &str) -> Packet {
+    Packet {
+        pld: ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            name: name.into(),
+        }
+        .into(),
+        ser: SerialNumber(0),
+        con: ConnectionId(0),
+    }
+}
+
+fn server_message(packet: Packet) -> ServerMessage {
+    match packet.pld {
+        Payload::Server(message) => message,
+        Payload::Client(message) => panic!("unexpected {message:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_frames_follow_the_client() {
+    let (_, ws) = server().await;
+    let (mut json, _) = tokio_tungstenite::connect_async(&ws).await.unwrap();
+    let text = serde_json::to_string(&hello("json")).unwrap();
+    json.send(Message::Text(text)).await.unwrap();
+    let Some(Ok(Message::Text(reply))) = json.next().await else {
+        panic!("expected a text message");
+    };
+    let packet: Packet = serde_json::from_str(&reply).unwrap();
+    assert!(matches!(
+        server_message(packet),
+        ServerMessage::Welcome { .. }
+    ));
+
+    let (mut binary, _) = tokio_tungstenite::connect_async(&ws).await.unwrap();
+    let bytes = bincode::serialize(&hello("bincode")).unwrap();
+    binary.send(Message::Binary(bytes)).await.unwrap();
+    let Some(Ok(Message::Binary(reply))) = binary.next().await else {
+        panic!("expected a binary message");
+    };
+    let packet: Packet = bincode::deserialize(&reply).unwrap();
+    assert!(matches!(
+        server_message(packet),
+        ServerMessage::Welcome { .. }
+    ));
+}
+
+async fn next(connection: &mut WsConnection<MaybeTlsStream<TcpStream>>) -> ServerMessage {
+    server_message(connection.recv_packet().await.unwrap().unwrap())
+}
+
+#[tokio::test]
+async fn test_tcp_and_websocket_share_rooms() {
+    let (tcp, ws) = server().await;
+    let (stream, _) = tokio_tungstenite::connect_async(&ws).await.unwrap();
+    let mut browser = WsConnection::websocket(stream, ConnectionId(0), Encoding::Json);
+    browser.send(hello("browser").pld).await.unwrap();
+    assert!(matches!(
+        next(&mut browser).await,
+        ServerMessage::Welcome { .. }
+    ));
+    browser
+        .send(ClientMessage::CreateRoom(RoomSettings::default()).into())
+        .await
+        .unwrap();
+    let ServerMessage::Room(room) = next(&mut browser).await else {
+        panic!("expected the room");
+    };
+
+    let stream = TcpStream::connect(&tcp).await.unwrap();
+    let mut terminal = TcpConnection::tcp(stream, ConnectionId(0), Encoding::Bincode);
+    terminal.send(hello("terminal").pld).await.unwrap();
+    assert!(matches!(
+        server_message(terminal.recv_packet().await.unwrap().unwrap()),
+        ServerMessage::Welcome { .. }
+    ));
+    terminal
+        .send(ClientMessage::JoinRoom(room.code.clone()).into())
+        .await
+        .unwrap();
+    assert!(matches!(next(&mut browser).await, ServerMessage::Room(r) if r.members == 2));
+}
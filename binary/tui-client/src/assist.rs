@@ -0,0 +1,244 @@
+//! The discard assistant: what each discard leaves the hand with, and how dangerous it is against
+//! the seats in riichi.
+//!
+//! The danger is a rough read of the table rather than a defense model. The discards of a riichi
+//! seat and everything discarded after its riichi can't deal in, suji of those and honors seen
+//! three times rarely do, and the middle tiles are the worst.
+use rahjong::{
+    hand::{decompose::FaceCounts, Gang, Melded},
+    player::Player,
+    rules::jp::round::PlayerSight,
+    tile::{Honer, Num, Suit, SuitKind, TileFace, TileId},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Danger {
+    /// Genbutsu, the riichi seat can't ron on it.
+    Safe,
+    Low,
+    Medium,
+    High,
+}
+
+/// What discarding `tile` leaves.
+#[derive(Debug, Clone)]
+pub struct Hint {
+    pub tile: TileId,
+    pub shanten: i8,
+    /// The faces that bring the hand a step closer, the waits once it is ready.
+    pub waits: Vec<TileFace>,
+    /// How many tiles of the waits are not in sight.
+    pub ukeire: usize,
+    /// Against every seat in riichi.
+    pub danger: Vec<(Player, Danger)>,
+}
+
+/// A hint for every tile `seat` holds, the drawn tile last. Empty unless it is the turn of the
+/// seat.
+pub fn hints(sight: &PlayerSight, seat: Player) -> Vec<Hint> {
+    let Some(draw) = sight.draw.filter(|_| sight.to_discard == Some(seat)) else {
+        return Vec::new();
+    };
+    let tiles: Vec<TileId> = (sight.self_deck.hand.tiles.iter())
+        .chain([draw.tile()])
+        .collect();
+    let seen = seen(sight, seat);
+    let riichi: Vec<Player> = (sight.riichi.iter())
+        .filter(|(other, riichi)| *other != seat && riichi.is_some())
+        .map(|(other, _)| other)
+        .collect();
+    tiles
+        .iter()
+        .map(|tile| {
+            let mut counts = FaceCounts::new(tiles.iter().copied());
+            counts.remove(tile.face());
+            let (shanten, waits) = effective(&counts);
+            let ukeire = waits
+                .iter()
+                .map(|face| 4usize.saturating_sub(seen.get(*face) as usize))
+                .sum();
+            let danger = (riichi.iter())
+                .map(|other| (*other, danger(sight, *other, tile.face(), &seen)))
+                .collect();
+            Hint {
+                tile: *tile,
+                shanten,
+                waits,
+                ukeire,
+                danger,
+            }
+        })
+        .collect()
+}
+
+fn faces() -> impl Iterator<Item = TileFace> {
+    let suits = SuitKind::enumerate()
+        .flat_map(|kind| Num::enumerate().map(move |num| TileFace::from_suit(Suit { kind, num })));
+    suits.chain(Honer::enumerate().map(TileFace::from_honor))
+}
+
+/// The shanten of a `3n + 1` tile hand and the faces that lower it.
+fn effective(counts: &FaceCounts) -> (i8, Vec<TileFace>) {
+    let shanten = counts.shanten();
+    let waits = faces()
+        .filter(|face| counts.get(*face) < 4)
+        .filter(|face| {
+            let mut counts = *counts;
+            counts.add(*face);
+            counts.shanten() < shanten
+        })
+        .collect();
+    (shanten, waits)
+}
+
+/// Every tile `seat` can see: its own hand, the rivers, the open melds and the dora indicators.
+fn seen(sight: &PlayerSight, seat: Player) -> FaceCounts {
+    let mut seen = FaceCounts::new(sight.self_deck.hand.tiles.iter());
+    let mut add = |face: TileFace, n: usize| (0..n).for_each(|_| seen.add(face));
+    sight.draw.iter().for_each(|d| add(d.tile().face(), 1));
+    sight.discards.iter().for_each(|d| add(d.tile().face(), 1));
+    sight.dora_indicators.iter().for_each(|t| add(t.face(), 1));
+    let melded = (sight.other_hand_melded.iter())
+        .filter(|(other, _)| *other != seat)
+        .map(|(_, melded)| melded)
+        .chain([&sight.self_deck.melded]);
+    for melded in melded {
+        for (face, n) in meld_faces(melded) {
+            add(face, n);
+        }
+    }
+    seen
+}
+
+/// The tiles of open melds that came from the hand, the called ones are still in the rivers.
+fn meld_faces(melded: &Melded) -> Vec<(TileFace, usize)> {
+    let mut faces = Vec::new();
+    for tiles in (melded.chi.iter().map(|c| c.tiles)).chain(melded.peng.iter().map(|p| p.tiles)) {
+        faces.extend(tiles.iter().map(|t| (t.face(), 1)));
+    }
+    for gang in &melded.gang {
+        let n = match gang {
+            Gang::AnGang { .. } | Gang::Kong { .. } => 4,
+            Gang::MingGang { .. } => 3,
+            // the peng tiles and the added one
+            Gang::JiaGang { .. } => 3,
+        };
+        faces.push((gang.face(), n));
+    }
+    faces
+}
+
+/// How dangerous `face` is against `riichi`.
+fn danger(sight: &PlayerSight, riichi: Player, face: TileFace, seen: &FaceCounts) -> Danger {
+    let safe = genbutsu(sight, riichi);
+    if safe.contains(&face) {
+        return Danger::Safe;
+    }
+    let Some(suit) = face.try_into_suit() else {
+        return match seen.get(face) {
+            3.. => Danger::Low,
+            _ => Danger::Medium,
+        };
+    };
+    // a ryanmen wait on the face needs the tile three away, which the riichi seat would not
+    // have let go of
+    let num = suit.num as u8;
+    let suji: Vec<TileFace> = [num.checked_sub(3), Some(num + 3)]
+        .into_iter()
+        .flatten()
+        .filter_map(Num::try_from_u8)
+        .map(|num| {
+            TileFace::from_suit(Suit {
+                kind: suit.kind,
+                num,
+            })
+        })
+        .collect();
+    if suji.iter().all(|face| safe.contains(face)) {
+        Danger::Low
+    } else if face.is_terminal() {
+        Danger::Medium
+    } else {
+        Danger::High
+    }
+}
+
+/// The faces `riichi` can't ron on: its own discards and those after its riichi.
+fn genbutsu(sight: &PlayerSight, riichi: Player) -> Vec<TileFace> {
+    let declared = sight.riichi.get(riichi).as_ref().map(|r| r.discard);
+    let mut after = false;
+    let mut safe = Vec::new();
+    for discard in sight.discards.iter() {
+        if discard.source() == riichi || after {
+            safe.push(discard.tile().face());
+        }
+        after |= Some(*discard) == declared;
+    }
+    safe
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rahjong::{
+        discard::{Discard, DiscardSet},
+        draw::Draw,
+        hand::Hand,
+        player::WindSet,
+        rules::jp::riichi::Richii,
+        tile::*,
+    };
+
+    #[test]
+    fn test_hints() {
+        // 123m 456p 789s 11z 5m6m, drawing 9p: discarding 9p keeps tenpai on 4m7m
+        let hand = Hand::from_faces([C1, C2, C3, D4, D5, D6, B7, B8, B9, EAST, EAST, C5, C6]);
+        let draw = TileId::from_face_idx(D9, TileIndex::T0);
+        let riichi = Discard::new(Player::SOUTH, TileId::from_face_idx(C1, TileIndex::T3));
+        let mut discards = DiscardSet::new();
+        discards.add(Discard::new(
+            Player::SOUTH,
+            TileId::from_face_idx(C4, TileIndex::T3),
+        ));
+        discards.add(riichi);
+        discards.add(Discard::new(
+            Player::WEST,
+            TileId::from_face_idx(D9, TileIndex::T3),
+        ));
+        let mut sight = PlayerSight {
+            dealer: Wind::East,
+            wall_rest: 50,
+            self_deck: Default::default(),
+            other_hand_size: WindSet::from_fn(|_| 13),
+            other_hand_melded: Default::default(),
+            bonus: Default::default(),
+            discards,
+            dora_indicators: vec![TileId::from_face_idx(C7, TileIndex::T3)],
+            riichi: Default::default(),
+            draw: Some(Draw::new(draw)),
+            to_discard: Some(Player::EAST),
+        };
+        sight.self_deck.hand = hand;
+        sight
+            .riichi
+            .insert(Player::SOUTH, Some(Richii { discard: riichi }));
+
+        let hints = hints(&sight, Player::EAST);
+        assert_eq!(hints.len(), 14);
+        let nine = hints.iter().find(|h| h.tile == draw).unwrap();
+        assert_eq!(nine.shanten, 0);
+        assert_eq!(nine.waits, vec![C4, C7]);
+        // a 4m and a 7m are out already
+        assert_eq!(nine.ukeire, 6);
+        // discarded after the riichi
+        assert_eq!(nine.danger, vec![(Player::SOUTH, Danger::Safe)]);
+        let hint = |face| hints.iter().find(|h| h.tile.face() == face).unwrap();
+        assert_eq!(hint(C1).danger, vec![(Player::SOUTH, Danger::Safe)]);
+        assert_eq!(hint(B7).danger, vec![(Player::SOUTH, Danger::High)]);
+        assert_eq!(hint(C6).shanten, 1);
+        // suji of the 4m
+        let seen = seen(&sight, Player::EAST);
+        assert_eq!(danger(&sight, Player::SOUTH, C7, &seen), Danger::Low);
+        assert_eq!(danger(&sight, Player::SOUTH, EAST, &seen), Danger::Medium);
+    }
+}
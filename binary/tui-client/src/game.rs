@@ -0,0 +1,419 @@
+//! A round played in the terminal, the seats without a human are played by bots.
+//!
+//! Several humans can share the terminal. The table shows one of them at a time, and when
+//! another one has to act the keyboard is handed over to them.
+use rahjong::{
+    bot::{Greedy, Strategy},
+    player::{Player, WindSet},
+    rules::jp::{
+        round::{Action, PlayerSight, Reason, Round, RoundEnd, RoundState},
+        win::Agari,
+    },
+    tile::{TileId, Wind},
+};
+use rahjong_server::table::Outcome;
+
+use crate::widget::{hand_order, seats_after};
+
+pub const STARTING_POINTS: i32 = 25_000;
+
+/// How a round ended.
+#[derive(Debug, Clone)]
+pub struct RoundResult {
+    /// The winners with their hands' worth, empty for a draw.
+    pub wins: Vec<Agari>,
+    pub tenpai: Vec<Player>,
+    pub payments: WindSet<i32>,
+}
+
+impl RoundResult {
+    /// The result of a `round` that ended, riichi sticks of a draw are paid to the table.
+    pub fn of_round(round: &Round) -> Option<Self> {
+        let result = match Outcome::of_round(round)? {
+            Outcome::Win { wins, payments } => RoundResult {
+                wins,
+                tenpai: Vec::new(),
+                payments,
+            },
+            Outcome::DrawGame { tenpai, payments } => RoundResult {
+                wins: Vec::new(),
+                tenpai,
+                payments,
+            },
+            Outcome::Resigned(_) | Outcome::Abandoned => return None,
+        };
+        Some(result)
+    }
+}
+
+pub struct LocalGame {
+    round: Round,
+    /// The seats played by humans.
+    humans: Vec<Player>,
+    /// The human the table is shown to.
+    seat: Player,
+    /// The selected tile, counting the hand in the order it is shown and the drawn tile last.
+    cursor: usize,
+    scores: WindSet<i32>,
+    round_wind: Wind,
+    honba: u8,
+    /// Riichi sticks left on the table by draws, the next winner takes them.
+    deposits: u8,
+    result: Option<RoundResult>,
+    /// Makes the strategy of a seat without a human from the round wind.
+    new_bot: fn(Wind) -> Box<dyn Strategy>,
+    /// The strategies of this round, the human seats don't use theirs.
+    bots: WindSet<Box<dyn Strategy>>,
+}
+
+fn greedy(round_wind: Wind) -> Box<dyn Strategy> {
+    Box::new(Greedy { round_wind })
+}
+
+impl LocalGame {
+    /// A game where the human plays `seat`.
+    pub fn new(seat: Player) -> Self {
+        Self::with_humans(vec![seat])
+    }
+    /// A game where humans play `humans` in turns on the same terminal, it is shown to the first
+    /// of them.
+    pub fn with_humans(humans: Vec<Player>) -> Self {
+        assert!(!humans.is_empty(), "a human plays a seat");
+        let mut game = LocalGame {
+            round: Round::new(Wind::East),
+            seat: humans[0],
+            humans,
+            cursor: 0,
+            scores: WindSet::from_fn(|_| STARTING_POINTS),
+            round_wind: Wind::East,
+            honba: 0,
+            deposits: 0,
+            result: None,
+            new_bot: greedy,
+            bots: WindSet::from_fn(|_| greedy(Wind::East)),
+        };
+        game.deal();
+        game
+    }
+    /// Play the seats without a human with the strategies `new_bot` makes, [`Greedy`] bots play
+    /// them otherwise.
+    pub fn with_bots(mut self, new_bot: fn(Wind) -> Box<dyn Strategy>) -> Self {
+        self.new_bot = new_bot;
+        self.bots = WindSet::from_fn(|_| new_bot(self.round_wind));
+        self
+    }
+    pub fn round(&self) -> &Round {
+        &self.round
+    }
+    /// The human seat the table is shown to.
+    pub fn seat(&self) -> Player {
+        self.seat
+    }
+    pub fn humans(&self) -> &[Player] {
+        &self.humans
+    }
+    /// Another human the table waits on, the keyboard is to be handed over to them.
+    pub fn handover(&self) -> Option<Player> {
+        if self.result.is_some() || self.has_choice(self.seat) {
+            return None;
+        }
+        let after = |p: &&Player| seats_after(self.seat, **p);
+        let mut others: Vec<&Player> = self.humans.iter().filter(|p| **p != self.seat).collect();
+        others.sort_by_key(after);
+        others.into_iter().copied().find(|p| self.has_choice(*p))
+    }
+    /// Show the table to `seat` from now on.
+    pub fn take_over(&mut self, seat: Player) {
+        assert!(self.humans.contains(&seat), "only humans take over");
+        self.seat = seat;
+        self.turn_started();
+    }
+    pub fn sight(&self) -> PlayerSight {
+        self.round.player_sight(self.seat)
+    }
+    pub fn scores(&self) -> &WindSet<i32> {
+        &self.scores
+    }
+    pub fn round_wind(&self) -> Wind {
+        self.round_wind
+    }
+    /// Counters on the table, one more for every draw or dealer win in a row.
+    pub fn honba(&self) -> u8 {
+        self.honba
+    }
+    /// Riichi sticks on the table.
+    pub fn deposits(&self) -> u8 {
+        self.deposits
+    }
+    pub fn result(&self) -> Option<&RoundResult> {
+        self.result.as_ref()
+    }
+    pub fn is_my_turn(&self) -> bool {
+        matches!(self.round.state(), RoundState::WaitDiscard(p) if *p == self.seat)
+    }
+    /// The tile under the cursor.
+    pub fn selected(&self) -> Option<TileId> {
+        self.tiles().get(self.cursor).copied()
+    }
+    /// Put the cursor on `tile`, `false` if the seat doesn't hold it.
+    pub fn select(&mut self, tile: TileId) -> bool {
+        let Some(cursor) = self.tiles().iter().position(|t| *t == tile) else {
+            return false;
+        };
+        self.cursor = cursor;
+        true
+    }
+    /// Move the cursor `by` tiles, wrapping around the ends.
+    pub fn move_cursor(&mut self, by: isize) {
+        let len = self.tiles().len() as isize;
+        if len > 0 {
+            self.cursor = (self.cursor as isize + by).rem_euclid(len) as usize;
+        }
+    }
+    /// What the human may do now besides discarding.
+    pub fn choices(&self) -> Vec<Action> {
+        if self.result.is_some() {
+            return Vec::new();
+        }
+        self.round
+            .legal_actions(self.seat)
+            .into_iter()
+            .filter(|a| !matches!(a, Action::Discard(_)))
+            .collect()
+    }
+    /// Play one of the [`LocalGame::choices`].
+    pub fn act(&mut self, action: Action) -> Result<(), Reason> {
+        self.apply(action, self.seat)
+    }
+    /// Discard the selected tile.
+    pub fn discard(&mut self) -> Result<(), Reason> {
+        let Some(tile) = self.selected().filter(|_| self.is_my_turn()) else {
+            return Err(Reason::invalid_operation("Not your turn now"));
+        };
+        self.apply(Action::Discard(self.seat.discard(tile)), self.seat)
+    }
+    /// Play the next action that isn't up to the human, `false` when the table waits on them or
+    /// the round is over.
+    pub fn step(&mut self) -> bool {
+        if self.result.is_some() {
+            return false;
+        }
+        for player in Wind::enumerate().map(Player::from) {
+            let actions = self.round.legal_actions(player);
+            if actions.is_empty() {
+                continue;
+            }
+            let action = if self.humans.contains(&player) {
+                // a pass is all a human could do, so it is done for them
+                if self.has_choice(player) {
+                    continue;
+                }
+                Action::Pass
+            } else {
+                let sight = self.round.player_sight(player);
+                self.bots.get_mut(player).choose(player, &sight, &actions)
+            };
+            self.apply(action, player)
+                .expect("the bots play legal actions");
+            return true;
+        }
+        false
+    }
+    /// Deal the next round, the scores carry over. The dealer keeps the deal when they won or
+    /// were tenpai, and a counter is added unless another seat won.
+    pub fn next_round(&mut self) {
+        let mut dealer = self.round.dealer();
+        let sticks = -self
+            .round
+            .riichi_payments()
+            .iter()
+            .map(|(_, p)| p)
+            .sum::<i32>()
+            / 1000;
+        let renchan = match self.round.end() {
+            Some(RoundEnd::Won(wins)) => {
+                self.deposits = 0;
+                wins.iter().any(|w| w.player == dealer.into())
+            }
+            Some(RoundEnd::Aborted(_)) => {
+                self.deposits = self.deposits.saturating_add(sticks as u8);
+                true
+            }
+            _ => {
+                self.deposits = self.deposits.saturating_add(sticks as u8);
+                self.round.tenpai().contains(&dealer.into())
+            }
+        };
+        let won = matches!(self.round.end(), Some(RoundEnd::Won(_)));
+        self.honba = if won && !renchan {
+            0
+        } else {
+            self.honba.saturating_add(1)
+        };
+        if !renchan {
+            dealer = dealer.next();
+            if dealer == Wind::East {
+                self.round_wind = self.round_wind.next();
+            }
+        }
+        self.round = Round::new(dealer).with_counters(self.round_wind, self.honba, self.deposits);
+        let round_wind = self.round_wind;
+        self.bots = WindSet::from_fn(|_| (self.new_bot)(round_wind));
+        self.deal();
+    }
+    fn deal(&mut self) {
+        self.result = None;
+        self.round.start().expect("a new round starts");
+        self.turn_started();
+    }
+    fn apply(&mut self, action: Action, player: Player) -> Result<(), Reason> {
+        self.round.apply(&action, player)?;
+        if let Some(result) = RoundResult::of_round(&self.round) {
+            for (seat, paid) in result.payments.iter() {
+                *self.scores.get_mut(seat) += paid;
+            }
+            self.result = Some(result);
+        } else if self.is_my_turn() {
+            self.turn_started();
+        }
+        Ok(())
+    }
+    fn has_choice(&self, player: Player) -> bool {
+        self.round
+            .legal_actions(player)
+            .iter()
+            .any(|a| !matches!(a, Action::Pass))
+    }
+    /// Put the cursor on the drawn tile.
+    fn turn_started(&mut self) {
+        self.cursor = self.tiles().len().saturating_sub(1);
+    }
+    fn tiles(&self) -> Vec<TileId> {
+        let sight = self.sight();
+        let draw = sight.draw.filter(|_| sight.to_discard == Some(self.seat));
+        hand_order(&sight.self_deck.hand, draw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rahjong::bot::Tsumogiri;
+
+    #[test]
+    fn test_play_round() {
+        let mut game = LocalGame::new(Player::SOUTH);
+        let mut discards = 0;
+        while game.result().is_none() {
+            while game.step() {}
+            if game.is_my_turn() {
+                game.move_cursor(-1);
+                game.discard().unwrap();
+                discards += 1;
+            } else if !game.choices().is_empty() {
+                game.act(Action::Pass).unwrap();
+            }
+        }
+        // the bots never discard for the human, whoever wins
+        let south = game.round().history().iter().filter(|(player, action)| {
+            *player == Player::SOUTH && matches!(action, Action::Discard(_))
+        });
+        assert_eq!(south.count(), discards);
+        assert!(game.discard().is_err());
+        let result = game.result().unwrap().clone();
+        let dealer_won = result.wins.iter().any(|w| w.player == Player::EAST);
+        let renchan = match game.round().end() {
+            Some(RoundEnd::Aborted(_)) => true,
+            _ => dealer_won || result.tenpai.contains(&Player::EAST),
+        };
+        game.next_round();
+        // the riichi sticks of a draw are left on the table
+        let total: i32 = game.scores().iter().map(|(_, s)| s).sum();
+        assert_eq!(total + 1000 * game.deposits() as i32, 4 * STARTING_POINTS);
+        let dealer = if renchan { Wind::East } else { Wind::South };
+        assert_eq!(game.round().dealer(), dealer);
+        let honba = if result.wins.is_empty() || dealer_won {
+            1
+        } else {
+            0
+        };
+        assert_eq!(game.honba(), honba);
+        assert!(game.result().is_none());
+    }
+
+    /// A game where the human plays `seat` and the bots discard what they draw.
+    fn tsumogiri(seat: Player) -> LocalGame {
+        LocalGame::new(seat).with_bots(|_| Box::new(Tsumogiri))
+    }
+
+    /// Deal a seeded round with `dealer`, and let the human play it greedily.
+    fn play_greedy(game: &mut LocalGame, dealer: Wind, seed: u64) {
+        use rahjong::rules::jp::{round::RoundOptions, Jp};
+        game.round = Round::with_seed(Jp, dealer, RoundOptions::default(), seed);
+        game.deal();
+        let mut greedy = Greedy::default();
+        while game.result().is_none() {
+            while game.step() {}
+            let actions = game.round.legal_actions(game.seat);
+            if !actions.is_empty() {
+                let action = greedy.choose(game.seat, &game.sight(), &actions);
+                game.act(action).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_win_results() {
+        // the dealer draws the winning tile with this wall and keeps the deal
+        let mut game = tsumogiri(Player::EAST);
+        play_greedy(&mut game, Wind::East, 2);
+        let result = game.result().unwrap().clone();
+        let [agari] = result.wins.as_slice() else {
+            panic!("east should win alone");
+        };
+        assert_eq!((agari.player, agari.from), (Player::EAST, None));
+        assert!(agari.score.han > 0 && agari.score.fu > 0);
+        assert_eq!(
+            *game.scores().get(Player::EAST),
+            STARTING_POINTS + result.payments.get(Player::EAST)
+        );
+        game.next_round();
+        assert_eq!(game.round().dealer(), Wind::East);
+        assert_eq!(game.honba(), 1);
+
+        // another seat wins off the dealer, the deal passes on and the counters go
+        let mut game = tsumogiri(Player::EAST);
+        game.honba = 2;
+        play_greedy(&mut game, Wind::South, 1);
+        let wins = &game.result().unwrap().wins;
+        assert_eq!(wins[0].from, Some(Player::SOUTH));
+        game.next_round();
+        assert_eq!(game.round().dealer(), Wind::West);
+        assert_eq!(game.honba(), 0);
+        assert_eq!(game.deposits(), 0);
+    }
+
+    #[test]
+    fn test_hot_seat() {
+        let mut game = LocalGame::with_humans(vec![Player::EAST, Player::WEST])
+            .with_bots(|_| Box::new(Tsumogiri));
+        let mut discards: WindSet<usize> = WindSet::from_fn(|_| 0);
+        while game.result().is_none() {
+            while game.step() {}
+            if let Some(next) = game.handover() {
+                assert_ne!(next, game.seat());
+                game.take_over(next);
+                continue;
+            }
+            if game.is_my_turn() {
+                game.discard().unwrap();
+                *discards.get_mut(game.seat()) += 1;
+            } else if !game.choices().is_empty() {
+                game.act(Action::Pass).unwrap();
+            }
+        }
+        assert!(*discards.get(Player::EAST) > 5);
+        assert!(*discards.get(Player::WEST) > 5);
+        assert_eq!(*discards.get(Player::SOUTH), 0);
+    }
+}
@@ -1,56 +1,376 @@
+pub mod assist;
+pub mod game;
+pub mod net;
+pub mod prompt;
+pub mod remote;
+pub mod replay;
+pub mod theme;
+pub mod ui;
 pub mod widget;
 
-use std::io::{self, stdout};
+use std::{
+    io::{self, stdout},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, MouseEvent,
+    },
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
-use rahjong::{player::Player, tile::Wind};
-use ratatui::{prelude::*, widgets::*};
+use rahjong::{
+    player::Player,
+    tile::{TileId, Wind},
+};
+use rahjong_server::ClientMessage;
+use ratatui::prelude::*;
+
+use crate::{
+    game::LocalGame,
+    net::Client,
+    prompt::CallPrompt,
+    remote::RemoteGame,
+    replay::{Replay, Viewer},
+    theme::Theme,
+    widget::HandState,
+};
 
-use crate::widget::JpHandWidget;
+/// How long a bot takes to act, so their discards can be followed.
+const BOT_DELAY: Duration = Duration::from_millis(300);
 
+/// `rahjong-tui-client [--connect host:port] [--name name] [--replay file] [--config file]
+/// [--humans eswn]`, plays against bots unless connected to a server or replaying a saved game.
+/// `--humans` takes the winds of the seats humans play on this terminal, east alone by default.
+/// The tiles are drawn as the config file says, see [`theme`].
 fn main() -> io::Result<()> {
+    let mut connect = None;
+    let mut replay = None;
+    let mut config = None;
+    let mut humans = vec![Player::EAST];
+    let mut name = std::env::var("USER").unwrap_or_else(|_| "player".to_owned());
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--connect", Some(addr)) => connect = Some(addr),
+            ("--name", Some(n)) => name = n,
+            ("--replay", Some(path)) => replay = Some(Replay::load(path)?),
+            ("--config", Some(path)) => config = Some(PathBuf::from(path)),
+            ("--humans", Some(winds)) => match parse_humans(&winds) {
+                Some(seats) => humans = seats,
+                None => {
+                    eprintln!("--humans takes the winds of the seats, like ew");
+                    return Ok(());
+                }
+            },
+            _ => {
+                eprintln!(
+                    "usage: rahjong-tui-client [--connect host:port] [--name name] [--replay file] \
+                     [--config file] [--humans eswn]"
+                );
+                return Ok(());
+            }
+        }
+    }
+    // a missing config file is only an error if it was asked for
+    let config = config.or(Theme::default_path().filter(|path| path.exists()));
+    if let Some(path) = config {
+        theme::set_theme(Theme::load(&path)?);
+    }
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(EnableMouseCapture)?;
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
-    let mut should_quit = false;
-    use rahjong::rules::jp::round::Round;
-    let mut round = Round::new(Wind::East);
-    round.start();
-    let sight = round.player_sight(Player::EAST);
-    let hand = sight.self_deck.hand;
-    let draw = sight.draw;
-    while !should_quit {
+    let result = match (connect, replay) {
+        (_, Some(replay)) => run_replay(&mut terminal, Viewer::new(replay)),
+        (Some(addr), None) => run_remote(&mut terminal, Client::connect(addr, name)),
+        (None, None) => run(&mut terminal, humans),
+    };
+    disable_raw_mode()?;
+    stdout().execute(DisableMouseCapture)?;
+    stdout().execute(LeaveAlternateScreen)?;
+    result
+}
+
+/// Play with humans at `humans` and bots at the other seats.
+fn run(terminal: &mut Terminal<impl Backend>, humans: Vec<Player>) -> io::Result<()> {
+    let mut game = LocalGame::with_humans(humans);
+    let mut prompt = CallPrompt::new(game.choices());
+    let mut last_step = Instant::now();
+    let mut hints = false;
+    let mut hand = HandState::default();
+    loop {
+        // the table stays covered until the next human is at the keyboard
+        let handover = game.handover();
+        terminal.draw(|frame| match handover {
+            Some(next) => ui::draw_handover(frame, next),
+            None => ui::draw(frame, &game, prompt.as_ref(), hints, &mut hand),
+        })?;
+        let mut acted = false;
+        if event::poll(Duration::from_millis(50))? {
+            let event = event::read()?;
+            if let (Some(next), Event::Key(key)) = (handover, &event) {
+                match key.code {
+                    _ if key.kind != KeyEventKind::Press => {}
+                    KeyCode::Char('q') => return Ok(()),
+                    KeyCode::Enter => {
+                        game.take_over(next);
+                        acted = true;
+                    }
+                    _ => {}
+                }
+            } else if let Event::Mouse(mouse) = event {
+                match clicked(&mut hand, mouse, game.selected()) {
+                    Some((_, true)) => acted = game.discard().is_ok(),
+                    Some((tile, false)) => {
+                        game.select(tile);
+                    }
+                    None => {}
+                }
+            } else if let Event::Key(key) = event {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                // a call to make holds the table, on the own turn the hand keeps the keys
+                let reacting = prompt.as_ref().filter(|p| p.can_pass());
+                match (key.code, reacting) {
+                    (KeyCode::Char('q'), _) => return Ok(()),
+                    (KeyCode::Char('h'), _) => hints = !hints,
+                    (KeyCode::Up, Some(_)) => prompt.iter_mut().for_each(|p| p.move_cursor(-1)),
+                    (KeyCode::Down, Some(_)) => prompt.iter_mut().for_each(|p| p.move_cursor(1)),
+                    (KeyCode::Left, None) => game.move_cursor(-1),
+                    (KeyCode::Right, None) => game.move_cursor(1),
+                    (KeyCode::Enter, None) => acted = game.discard().is_ok(),
+                    (KeyCode::Char('n'), _) if game.result().is_some() => {
+                        game.next_round();
+                        acted = true;
+                    }
+                    (code, _) => {
+                        let action = prompt.as_ref().and_then(|p| p.key(code, game.selected()));
+                        if let Some(action) = action {
+                            acted = game.act(action).is_ok();
+                        }
+                    }
+                }
+            }
+        }
+        if !acted && handover.is_none() && last_step.elapsed() >= BOT_DELAY {
+            acted = game.step();
+        }
+        if acted {
+            last_step = Instant::now();
+            prompt = CallPrompt::new(game.choices());
+        }
+    }
+}
+
+fn run_remote(terminal: &mut Terminal<impl Backend>, client: Client) -> io::Result<()> {
+    let mut game = RemoteGame::default();
+    let mut prompt: Option<CallPrompt> = None;
+    // the room code being typed
+    let mut code: Option<String> = None;
+    let mut hints = false;
+    let mut hand = HandState::default();
+    loop {
+        let mut changed = false;
+        while let Some(event) = client.try_recv() {
+            if let Some(reply) = game.handle(event, Instant::now()) {
+                client.send(reply);
+            }
+            changed = true;
+        }
+        if changed {
+            prompt = CallPrompt::new(game.choices());
+            if let (Some(p), Some(deadline)) = (prompt.take(), game.deadline()) {
+                prompt = Some(p.with_deadline(deadline));
+            }
+        }
         terminal.draw(|frame| {
-            let hand_widget = JpHandWidget::new(&hand, vec![], hand.tiles.iter().nth(1), draw);
-            frame.render_widget(hand_widget, frame.size());
+            let code = code.as_deref();
+            ui::draw_remote(frame, &game, prompt.as_ref(), code, hints, &mut hand)
         })?;
-        should_quit = handle_events()?;
+        if !event::poll(Duration::from_millis(50))? {
+            continue;
+        }
+        let key = match event::read()? {
+            Event::Key(key) => key,
+            Event::Mouse(mouse) if game.sight().is_some() => {
+                match clicked(&mut hand, mouse, game.selected()) {
+                    Some((_, true)) => {
+                        if let Some(discard) = game.discard() {
+                            client.send(discard);
+                            prompt = None;
+                        }
+                    }
+                    Some((tile, false)) => {
+                        game.select(tile);
+                    }
+                    None => {}
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if let Some(typed) = &mut code {
+            match key.code {
+                KeyCode::Enter => client.send(game.join_room(code.take()).expect("a code")),
+                KeyCode::Esc => code = None,
+                KeyCode::Backspace => {
+                    typed.pop();
+                }
+                KeyCode::Char(c) => typed.push(c),
+                _ => {}
+            }
+            continue;
+        }
+        if key.code == KeyCode::Char('q') {
+            return Ok(());
+        }
+        let message = if game.sight().is_some() {
+            let reacting = prompt.as_ref().filter(|p| p.can_pass());
+            match (key.code, reacting) {
+                (KeyCode::Char('h'), _) => {
+                    hints = !hints;
+                    None
+                }
+                (KeyCode::Up, Some(_)) => {
+                    prompt.iter_mut().for_each(|p| p.move_cursor(-1));
+                    None
+                }
+                (KeyCode::Down, Some(_)) => {
+                    prompt.iter_mut().for_each(|p| p.move_cursor(1));
+                    None
+                }
+                (KeyCode::Left, None) => {
+                    game.move_cursor(-1);
+                    None
+                }
+                (KeyCode::Right, None) => {
+                    game.move_cursor(1);
+                    None
+                }
+                (KeyCode::Enter, None) => game.discard(),
+                (KeyCode::Char('n'), _) if game.result().is_some() => {
+                    game.dismiss_result();
+                    None
+                }
+                (code, _) => {
+                    let action = prompt.as_ref().and_then(|p| p.key(code, game.selected()));
+                    action.map(|action| game.act(action))
+                }
+            }
+        } else if game.room().is_some() {
+            match key.code {
+                KeyCode::Char(c @ '1'..='4') => {
+                    let seat = Wind::enumerate().nth(c as usize - '1' as usize);
+                    seat.map(|wind| ClientMessage::TakeSeat(wind.into()))
+                }
+                KeyCode::Char(' ') => Some(ClientMessage::Ready),
+                KeyCode::Char('l') => Some(game.leave_room()),
+                _ => None,
+            }
+        } else {
+            match key.code {
+                KeyCode::Up => {
+                    game.move_room_cursor(-1);
+                    None
+                }
+                KeyCode::Down => {
+                    game.move_room_cursor(1);
+                    None
+                }
+                KeyCode::Enter => game.join_room(None),
+                KeyCode::Char('j') => {
+                    code = Some(String::new());
+                    None
+                }
+                KeyCode::Char('c') => Some(game.create_room()),
+                KeyCode::Char('u') => Some(ClientMessage::Queue),
+                KeyCode::Char('r') => Some(ClientMessage::ListRooms),
+                _ => None,
+            }
+        };
+        if let Some(message) = message {
+            let acted = matches!(message, ClientMessage::Action(_));
+            client.send(message);
+            if acted {
+                prompt = None;
+            }
+        }
     }
+}
 
-    disable_raw_mode()?;
-    stdout().execute(LeaveAlternateScreen)?;
-    Ok(())
+/// The seats of `winds` like `"ew"`, each at most once.
+fn parse_humans(winds: &str) -> Option<Vec<Player>> {
+    let mut humans = Vec::new();
+    for c in winds.chars() {
+        let wind = match c.to_ascii_lowercase() {
+            'e' => Wind::East,
+            's' => Wind::South,
+            'w' => Wind::West,
+            'n' => Wind::North,
+            _ => return None,
+        };
+        if humans.contains(&Player::from(wind)) {
+            return None;
+        }
+        humans.push(wind.into());
+    }
+    (!humans.is_empty()).then_some(humans)
 }
 
-fn handle_events() -> io::Result<bool> {
-    if event::poll(std::time::Duration::from_millis(50))? {
-        let event = event::read()?;
-        match event {
-            Event::Key(key) => {
-                if key.code == KeyCode::Char('q')
-                    && key.modifiers.is_empty()
-                    && key.kind == event::KeyEventKind::Press
-                {
-                    return Ok(true);
+/// The tile clicked in the hand, and whether it was selected already, a second click discards it.
+fn clicked(
+    hand: &mut HandState,
+    mouse: MouseEvent,
+    selected: Option<TileId>,
+) -> Option<(TileId, bool)> {
+    let tile = hand.click(mouse)?;
+    Some((tile, selected == Some(tile)))
+}
+
+fn run_replay(terminal: &mut Terminal<impl Backend>, mut viewer: Viewer) -> io::Result<()> {
+    // the round under the cursor while picking one
+    let mut picker: Option<usize> = None;
+    loop {
+        terminal.draw(|frame| ui::draw_replay(frame, &viewer, picker))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if let Some(cursor) = &mut picker {
+            let rounds = viewer.rounds().len();
+            match key.code {
+                KeyCode::Up => *cursor = (*cursor + rounds - 1) % rounds,
+                KeyCode::Down => *cursor = (*cursor + 1) % rounds,
+                KeyCode::Enter => {
+                    viewer.jump(*cursor);
+                    picker = None;
                 }
+                KeyCode::Esc => picker = None,
+                _ => {}
             }
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') => return Ok(()),
+            KeyCode::Left => viewer.step_by(-1),
+            KeyCode::Right => viewer.step_by(1),
+            KeyCode::Home => viewer.first(),
+            KeyCode::End => viewer.last(),
+            KeyCode::PageUp => viewer.jump(viewer.round_index().saturating_sub(1)),
+            KeyCode::PageDown => viewer.jump(viewer.round_index() + 1),
+            KeyCode::Char('g') => picker = Some(viewer.round_index()),
+            KeyCode::Char('s') => viewer.next_seat(),
+            KeyCode::Char('v') => viewer.toggle_reveal(),
             _ => {}
         }
-        
     }
-    Ok(false)
 }
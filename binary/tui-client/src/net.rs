@@ -0,0 +1,290 @@
+//! The connection to a rahjong server, kept up on a thread of its own.
+//!
+//! The session token from the welcome is kept, so a dropped connection is resumed where it left
+//! off and the server sends on what was missed. Messages sent while offline wait for the next
+//! connection.
+use std::{sync::mpsc as std_mpsc, thread, time::Duration};
+
+use rahjong::rules::jp::round::ReasonKind;
+use rahjong_server::{
+    protocol::{SessionToken, PROTOCOL_VERSION},
+    ClientMessage, ConnectionId, Encoding, Payload, SerialNumber, ServerMessage, TcpConnection,
+};
+use tokio::{net::TcpStream, sync::mpsc};
+
+/// How long to wait before the first attempt to reconnect, doubled after every failed one.
+const RETRY: Duration = Duration::from_millis(500);
+const MAX_RETRY: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum NetEvent {
+    /// Welcomed by the server, `resumed` if the session of the last connection carried on.
+    Connected {
+        resumed: bool,
+    },
+    Message(ServerMessage),
+    /// The connection dropped, another attempt is made after `retry`.
+    Disconnected {
+        reason: String,
+        retry: Duration,
+    },
+}
+
+pub struct Client {
+    outbox: mpsc::UnboundedSender<ClientMessage>,
+    inbox: std_mpsc::Receiver<NetEvent>,
+}
+
+impl Client {
+    /// Connect to `addr` as `name`, in the background.
+    pub fn connect(addr: String, name: String) -> Self {
+        let (outbox, outgoing) = mpsc::unbounded_channel();
+        let (incoming, inbox) = std_mpsc::channel();
+        thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("a runtime for the connection");
+            runtime.block_on(run(addr, name, outgoing, incoming));
+        });
+        Client { outbox, inbox }
+    }
+    pub fn send(&self, message: ClientMessage) {
+        let _ = self.outbox.send(message);
+    }
+    /// The next event if there is one, without waiting.
+    pub fn try_recv(&self) -> Option<NetEvent> {
+        self.inbox.try_recv().ok()
+    }
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<NetEvent> {
+        self.inbox.recv_timeout(timeout).ok()
+    }
+}
+
+/// What the client knows of its session across connections.
+struct Session {
+    token: Option<SessionToken>,
+    acked: SerialNumber,
+}
+
+impl Session {
+    fn greeting(&self, name: &str) -> ClientMessage {
+        match &self.token {
+            Some(session) => ClientMessage::Resume {
+                version: PROTOCOL_VERSION,
+                session: session.clone(),
+                acked: self.acked,
+            },
+            None => ClientMessage::Hello {
+                version: PROTOCOL_VERSION,
+                name: name.to_owned(),
+            },
+        }
+    }
+}
+
+/// Why a connection ended.
+enum Closed {
+    /// The client was dropped, nobody is listening anymore.
+    Quit,
+    /// The server did not know the session, the next connection starts a new one.
+    Expired,
+    Dropped(String),
+}
+
+async fn run(
+    addr: String,
+    name: String,
+    mut outgoing: mpsc::UnboundedReceiver<ClientMessage>,
+    incoming: std_mpsc::Sender<NetEvent>,
+) {
+    let mut session = Session {
+        token: None,
+        acked: SerialNumber(0),
+    };
+    let mut retry = RETRY;
+    // a message that could not be sent before the connection dropped
+    let mut pending = None;
+    loop {
+        let closed = match TcpStream::connect(&addr).await {
+            Ok(stream) => {
+                let connection = TcpConnection::tcp(stream, ConnectionId(0), Encoding::Bincode);
+                let link = Link {
+                    name: &name,
+                    session: &mut session,
+                    retry: &mut retry,
+                    pending: &mut pending,
+                    outgoing: &mut outgoing,
+                    incoming: &incoming,
+                };
+                link.serve(connection).await
+            }
+            Err(e) => Closed::Dropped(e.to_string()),
+        };
+        let reason = match closed {
+            Closed::Quit => return,
+            Closed::Expired => {
+                session.token = None;
+                session.acked = SerialNumber(0);
+                retry = RETRY;
+                continue;
+            }
+            Closed::Dropped(reason) => reason,
+        };
+        let event = NetEvent::Disconnected { reason, retry };
+        if incoming.send(event).is_err() {
+            return;
+        }
+        tokio::time::sleep(retry).await;
+        retry = (retry * 2).min(MAX_RETRY);
+    }
+}
+
+/// The state a connection works on, it outlives the connection.
+struct Link<'a> {
+    name: &'a str,
+    session: &'a mut Session,
+    retry: &'a mut Duration,
+    pending: &'a mut Option<ClientMessage>,
+    outgoing: &'a mut mpsc::UnboundedReceiver<ClientMessage>,
+    incoming: &'a std_mpsc::Sender<NetEvent>,
+}
+
+impl Link<'_> {
+    /// Greet the server and pass messages both ways until the connection closes.
+    async fn serve(self, mut connection: TcpConnection) -> Closed {
+        let greeting = self.session.greeting(self.name);
+        if let Err(e) = connection.send(greeting.into()).await {
+            return Closed::Dropped(e.to_string());
+        }
+        let mut welcomed = false;
+        loop {
+            // before the welcome the message waits, the server reads the greeting first
+            let pending = if welcomed { self.pending.take() } else { None };
+            if let Some(message) = pending {
+                if let Err(e) = connection.send(message.clone().into()).await {
+                    *self.pending = Some(message);
+                    return Closed::Dropped(e.to_string());
+                }
+            }
+            tokio::select! {
+                message = self.outgoing.recv(), if welcomed => {
+                    let Some(message) = message else { return Closed::Quit };
+                    *self.pending = Some(message);
+                }
+                packet = connection.recv_packet() => {
+                    let packet = match packet {
+                        Some(Ok(packet)) => packet,
+                        Some(Err(e)) => return Closed::Dropped(e.to_string()),
+                        None => return Closed::Dropped("closed by the server".to_owned()),
+                    };
+                    let Payload::Server(message) = packet.pld else {
+                        continue;
+                    };
+                    let event = match message {
+                        ServerMessage::Welcome { session, .. } => {
+                            let resumed = self.session.token.as_ref() == Some(&session);
+                            if !resumed {
+                                self.session.acked = SerialNumber(0);
+                            }
+                            self.session.token = Some(session);
+                            *self.retry = RETRY;
+                            welcomed = true;
+                            NetEvent::Connected { resumed }
+                        }
+                        ServerMessage::Error(e)
+                            if !welcomed && e.kind == ReasonKind::UnknownSession =>
+                        {
+                            return Closed::Expired;
+                        }
+                        message => {
+                            self.session.acked = packet.ser;
+                            NetEvent::Message(message)
+                        }
+                    };
+                    if self.incoming.send(event).is_err() {
+                        return Closed::Quit;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rahjong_server::{lobby::Lobby, serve_tcp};
+    use tokio::net::TcpListener;
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    fn next_message(client: &Client) -> ServerMessage {
+        loop {
+            match client.recv_timeout(WAIT) {
+                Some(NetEvent::Message(message)) => return message,
+                Some(_) => {}
+                None => panic!("no message from the server"),
+            }
+        }
+    }
+
+    async fn accept(listener: &TcpListener) -> (TcpConnection, Payload) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut connection = TcpConnection::tcp(stream, ConnectionId(1), Encoding::Bincode);
+        let greeting = connection.recv().await.unwrap();
+        (connection, greeting)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_join_a_room() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let lobby = Lobby::new();
+        tokio::spawn(serve_tcp(listener, lobby.sender()));
+        tokio::spawn(lobby.run());
+
+        let client = Client::connect(addr, "tester".to_owned());
+        client.send(ClientMessage::CreateRoom(Default::default()));
+        let ServerMessage::Room(room) = next_message(&client) else {
+            panic!("expected the room");
+        };
+        assert_eq!(room.members, 1);
+        client.send(ClientMessage::ListRooms);
+        let ServerMessage::Rooms(rooms) = next_message(&client) else {
+            panic!("expected the rooms");
+        };
+        assert_eq!(rooms.len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resume_after_a_drop() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let client = Client::connect(addr, "tester".to_owned());
+
+        let (mut connection, greeting) = accept(&listener).await;
+        assert!(matches!(
+            greeting,
+            Payload::Client(ClientMessage::Hello { .. })
+        ));
+        let token = SessionToken("token".to_owned());
+        connection.welcome(token.clone()).await.unwrap();
+        connection
+            .send(ServerMessage::Queued { waiting: 1 }.into())
+            .await
+            .unwrap();
+        assert!(matches!(
+            next_message(&client),
+            ServerMessage::Queued { waiting: 1 }
+        ));
+        drop(connection);
+
+        let (_connection, greeting) = accept(&listener).await;
+        let Payload::Client(ClientMessage::Resume { session, acked, .. }) = greeting else {
+            panic!("expected a resume, got {greeting:?}");
+        };
+        assert_eq!(session, token);
+        assert_eq!(acked, SerialNumber(1));
+    }
+}
@@ -0,0 +1,359 @@
+//! The prompt for the decisions other than a discard: calls on the discard of another seat, and
+//! riichi, tsumo, kans, kyuushu or nukidora on the own turn.
+use std::time::Instant;
+
+use crossterm::event::KeyCode;
+use rahjong::{
+    hand::Gang,
+    rules::jp::round::Action,
+    tile::{TileFace, TileId},
+};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::widget::{sideways, tile_span};
+
+/// One line of the prompt, the actions of a choice differ only in the tile they discard.
+pub struct Choice {
+    /// Picks the first choice of its kind, the digits pick any choice.
+    pub key: char,
+    pub label: Line<'static>,
+    actions: Vec<Action>,
+}
+
+impl Choice {
+    /// The action to play, for a riichi or nukidora the one with the `selected` tile.
+    pub fn action(&self, selected: Option<TileId>) -> Option<Action> {
+        if let [action] = self.actions.as_slice() {
+            return Some(action.clone());
+        }
+        self.actions
+            .iter()
+            .find(|action| match action {
+                Action::Richii(riichi) => Some(riichi.discard.tile()) == selected,
+                Action::Nukidora(tile) => Some(*tile) == selected,
+                _ => false,
+            })
+            .or(self
+                .actions
+                .first()
+                .filter(|a| matches!(a, Action::Nukidora(_))))
+            .cloned()
+    }
+}
+
+pub struct CallPrompt {
+    choices: Vec<Choice>,
+    cursor: usize,
+    deadline: Option<Instant>,
+}
+
+impl CallPrompt {
+    /// A prompt for the actions that aren't discards, `None` if passing is all there is.
+    pub fn new(actions: Vec<Action>) -> Option<Self> {
+        let mut choices: Vec<Choice> = Vec::new();
+        for action in actions {
+            if matches!(action, Action::Discard(_)) {
+                continue;
+            }
+            let grouped = choices.iter_mut().find(|c| {
+                matches!(
+                    (&c.actions[0], &action),
+                    (Action::Richii(_), Action::Richii(_))
+                        | (Action::Nukidora(_), Action::Nukidora(_))
+                )
+            });
+            match grouped {
+                Some(choice) => choice.actions.push(action),
+                None => choices.push(Choice {
+                    key: key(&action),
+                    label: label(&action),
+                    actions: vec![action],
+                }),
+            }
+        }
+        if choices.iter().all(|c| matches!(c.actions[0], Action::Pass)) {
+            return None;
+        }
+        // passing comes last, the calls are what the prompt is for
+        choices.sort_by_key(|c| matches!(c.actions[0], Action::Pass));
+        Some(CallPrompt {
+            choices,
+            cursor: 0,
+            deadline: None,
+        })
+    }
+    /// Count down to `deadline`, when the default action is played.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+    pub fn choices(&self) -> &[Choice] {
+        &self.choices
+    }
+    /// Whether passing is one of the choices, while reacting to a discard.
+    pub fn can_pass(&self) -> bool {
+        self.choices
+            .iter()
+            .any(|c| matches!(c.actions[0], Action::Pass))
+    }
+    pub fn move_cursor(&mut self, by: isize) {
+        let len = self.choices.len() as isize;
+        self.cursor = (self.cursor as isize + by).rem_euclid(len) as usize;
+    }
+    /// The action a key stands for. Enter takes the choice under the cursor and Esc passes.
+    pub fn key(&self, key: KeyCode, selected: Option<TileId>) -> Option<Action> {
+        let choice = match key {
+            KeyCode::Enter => self.choices.get(self.cursor),
+            KeyCode::Esc => self
+                .choices
+                .iter()
+                .find(|c| matches!(c.actions[0], Action::Pass)),
+            KeyCode::Char(c @ '1'..='9') => self.choices.get(c as usize - '1' as usize),
+            KeyCode::Char(c) => self.choices.iter().find(|choice| choice.key == c),
+            _ => None,
+        }?;
+        choice.action(selected)
+    }
+}
+
+impl Widget for &CallPrompt {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let lines: Vec<Line> = self
+            .choices
+            .iter()
+            .enumerate()
+            .map(|(i, choice)| {
+                let key = match choice.actions[0] {
+                    Action::Pass => "Esc".to_owned(),
+                    _ => format!("{} {}", i + 1, choice.key),
+                };
+                let mut line = Line::from(format!("{key:<4} "));
+                line.spans.extend(choice.label.spans.iter().cloned());
+                if i == self.cursor {
+                    line = line.patch_style(Style::default().add_modifier(Modifier::BOLD));
+                    line.spans.insert(0, Span::raw("> "));
+                } else {
+                    line.spans.insert(0, Span::raw("  "));
+                }
+                line
+            })
+            .collect();
+        let mut block = Block::default().borders(Borders::ALL).title("Call");
+        if let Some(deadline) = self.deadline {
+            let left = deadline.saturating_duration_since(Instant::now());
+            block = block.title(format!("{}s", left.as_secs_f32().ceil()));
+        }
+        Clear.render(area, buf);
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+}
+
+fn key(action: &Action) -> char {
+    match action {
+        Action::Pass => ' ',
+        Action::Discard(_) => 'd',
+        Action::Tsumo(_) => 't',
+        Action::Ron(_) | Action::Chankan(_) | Action::Richii(_) => 'r',
+        Action::Kan(_) => 'k',
+        Action::Chi(_) => 'c',
+        Action::Pon(_) => 'p',
+        Action::Nukidora(_) => 'n',
+        Action::Ryukyoku => 'y',
+        _ => '?',
+    }
+}
+
+fn label(action: &Action) -> Line<'static> {
+    let tiles = |faces: &[TileFace]| -> Vec<Span<'static>> {
+        faces.iter().map(|face| tile_span(*face)).collect()
+    };
+    let mut spans = Vec::new();
+    match action {
+        Action::Pass => spans.push(Span::raw("Pass")),
+        Action::Discard(discard) => {
+            spans.push(Span::raw("Discard "));
+            spans.push(tile_span(discard.tile().face()));
+        }
+        Action::Tsumo(tsumo) => {
+            spans.push(Span::raw("Tsumo "));
+            spans.push(tile_span(tsumo.tile.tile().face()));
+        }
+        Action::Ron(ron) => {
+            spans.push(Span::raw("Ron "));
+            spans.push(tile_span(ron.discard.face()));
+        }
+        Action::Chankan(chankan) => {
+            spans.push(Span::raw("Ron on the kan "));
+            spans.push(tile_span(chankan.tile.face()));
+        }
+        Action::Kan(gang) => {
+            let kind = match gang {
+                Gang::AnGang { .. } => "Ankan ",
+                Gang::JiaGang { .. } => "Kakan ",
+                Gang::MingGang { .. } | Gang::Kong { .. } => "Kan ",
+            };
+            spans.push(Span::raw(kind));
+            spans.extend(tiles(&[gang.face(); 4]));
+        }
+        Action::Chi(chi) => {
+            spans.push(Span::raw("Chi "));
+            spans.push(sideways(tile_span(chi.claim.tile().face())));
+            spans.extend(tiles(&chi.tiles.map(|t| t.face())));
+        }
+        Action::Pon(pon) => {
+            spans.push(Span::raw("Pon "));
+            spans.push(sideways(tile_span(pon.claim.tile().face())));
+            spans.extend(tiles(&pon.tiles.map(|t| t.face())));
+        }
+        Action::Richii(_) => spans.push(Span::raw("Riichi, discarding the selected tile")),
+        Action::Nukidora(tile) => {
+            spans.push(Span::raw("Nukidora "));
+            spans.push(tile_span(tile.face()));
+        }
+        Action::Ryukyoku => spans.push(Span::raw("Kyuushu kyuuhai, abort the hand")),
+        _ => spans.push(Span::raw("?")),
+    }
+    Line::from(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rahjong::{
+        bot::{Greedy, Strategy},
+        discard::Discard,
+        hand::{Chi, Hand},
+        player::Player,
+        rules::jp::{
+            riichi::Richii,
+            round::{Round, RoundEnd, RoundOptions},
+            Jp,
+        },
+        tile::{TileIndex, Wind, B3, B4, B5, B6, B7},
+    };
+
+    #[test]
+    fn test_chi_shapes() {
+        let hand = Hand::from_faces([B3, B4, B6, B7]);
+        let claim = Discard::new(Player::EAST, TileId::from_face_idx(B5, TileIndex::T0));
+        let chis: Vec<Action> = hand
+            .chi_options(claim.tile())
+            .iter()
+            .filter_map(|option| option.chi(claim, &hand))
+            .map(Action::Chi)
+            .collect();
+        assert_eq!(chis.len(), 3);
+        let mut actions = vec![Action::Pass];
+        actions.extend(chis);
+        let mut prompt = CallPrompt::new(actions).unwrap();
+        // every shape is its own choice, passing comes last
+        assert_eq!(prompt.choices().len(), 4);
+        assert!(prompt.can_pass());
+        let shape = |action: Option<Action>| match action {
+            Some(Action::Chi(Chi { tiles, .. })) => tiles.map(|t| t.face()),
+            other => panic!("expected a chi, got {other:?}"),
+        };
+        let first = shape(prompt.key(KeyCode::Char('c'), None));
+        assert_eq!(shape(prompt.key(KeyCode::Enter, None)), first);
+        prompt.move_cursor(1);
+        let second = shape(prompt.key(KeyCode::Enter, None));
+        assert_ne!(first, second);
+        assert_eq!(shape(prompt.key(KeyCode::Char('2'), None)), second);
+        assert!(matches!(prompt.key(KeyCode::Esc, None), Some(Action::Pass)));
+        assert!(CallPrompt::new(vec![Action::Pass]).is_none());
+    }
+
+    /// Play a seeded round, east greedy and the others discarding what they draw, until east
+    /// is offered an action `wanted` picks.
+    fn play_until(seed: u64, wanted: fn(&Action) -> bool) -> (Round, Vec<Action>) {
+        let mut round: Round = Round::with_seed(Jp, Wind::East, RoundOptions::default(), seed);
+        round.start().unwrap();
+        let mut greedy = Greedy::default();
+        loop {
+            assert!(!round.is_end(), "east was never offered the action");
+            let actions = round.legal_actions(Player::EAST);
+            if actions.iter().any(wanted) {
+                return (round, actions);
+            }
+            let (seat, action) = if actions.is_empty() {
+                Wind::enumerate()
+                    .map(Player::from)
+                    .find_map(|seat| Some((seat, round.default_action(seat)?)))
+                    .unwrap()
+            } else {
+                let sight = round.player_sight(Player::EAST);
+                (Player::EAST, greedy.choose(Player::EAST, &sight, &actions))
+            };
+            round.apply(&action, seat).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_round_choices() {
+        // east gets ready and draws the winning tile with this wall
+        let (mut round, actions) = play_until(2, |a| matches!(a, Action::Richii(_)));
+        let riichis = actions
+            .iter()
+            .filter(|a| matches!(a, Action::Richii(_)))
+            .count();
+        let prompt = CallPrompt::new(actions.clone()).unwrap();
+        // one line for every tile riichi can be declared with
+        assert_eq!(prompt.choices().len(), 1);
+        assert_eq!(prompt.choices()[0].actions.len(), riichis);
+        assert!(!prompt.can_pass());
+        let Some(Action::Richii(offered)) = actions.iter().find(|a| matches!(a, Action::Richii(_)))
+        else {
+            unreachable!()
+        };
+        let selected = offered.discard.tile();
+        let riichi = prompt.key(KeyCode::Char('r'), Some(selected)).unwrap();
+        round.apply(&riichi, Player::EAST).unwrap();
+        assert_eq!(*round.riichi_payments().get(Player::EAST), -1000);
+
+        let (mut round, actions) = play_until(2, |a| matches!(a, Action::Tsumo(_)));
+        let prompt = CallPrompt::new(actions).unwrap();
+        let tsumo = prompt.key(KeyCode::Char('t'), None).unwrap();
+        round.apply(&tsumo, Player::EAST).unwrap();
+        assert!(matches!(round.end(), Some(RoundEnd::Won(_))));
+    }
+
+    #[test]
+    fn test_ron_choice() {
+        // east can win on a tile north lets go with this wall
+        let (mut round, actions) = play_until(0, |a| matches!(a, Action::Ron(_)));
+        let prompt = CallPrompt::new(actions).unwrap();
+        assert!(prompt.can_pass());
+        assert!(matches!(prompt.key(KeyCode::Esc, None), Some(Action::Pass)));
+        let ron = prompt.key(KeyCode::Char('r'), None).unwrap();
+        round.apply(&ron, Player::EAST).unwrap();
+        for seat in [Player::SOUTH, Player::WEST] {
+            round.apply(&Action::Pass, seat).unwrap();
+        }
+        let Some(RoundEnd::Won(wins)) = round.end() else {
+            panic!("east should win");
+        };
+        assert_eq!(wins[0].from, Some(Player::NORTH));
+    }
+
+    #[test]
+    fn test_riichi_discards_the_selected_tile() {
+        let tiles = [B3, B4].map(|face| TileId::from_face_idx(face, TileIndex::T0));
+        let mut actions: Vec<Action> = tiles
+            .iter()
+            .map(|t| Action::Discard(Player::EAST.discard(*t)))
+            .collect();
+        actions.extend(tiles.iter().map(|t| {
+            Action::Richii(Richii {
+                discard: Player::EAST.discard(*t),
+            })
+        }));
+        let prompt = CallPrompt::new(actions).unwrap();
+        assert_eq!(prompt.choices().len(), 1);
+        assert!(!prompt.can_pass());
+        let Some(Action::Richii(riichi)) = prompt.key(KeyCode::Char('r'), Some(tiles[1])) else {
+            panic!("expected a riichi");
+        };
+        assert_eq!(riichi.discard.tile(), tiles[1]);
+        assert!(prompt.key(KeyCode::Char('r'), None).is_none());
+    }
+}
@@ -0,0 +1,417 @@
+//! A game at a rahjong server, as the messages of the server tell it.
+//!
+//! The server sends the sight and the legal actions of the seat after every action, so the round
+//! is shown as sent rather than replayed here. The events only feed the log and the scores.
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use rahjong::{
+    player::{Player, WindSet},
+    rules::jp::{
+        round::{Action, PlayerSight},
+        win::Agari,
+    },
+    tile::TileId,
+};
+use rahjong_server::{
+    protocol::{Event, RoomCode, RoomInfo, RoomSettings},
+    ClientMessage, ServerMessage,
+};
+
+use crate::{
+    game::{RoundResult, STARTING_POINTS},
+    net::NetEvent,
+    widget::hand_order,
+};
+
+/// How many events are kept for the log.
+const LOG_LEN: usize = 50;
+
+#[derive(Debug, Clone)]
+pub enum Status {
+    Connecting,
+    Online,
+    /// The connection dropped, the next attempt is made at `retry_at`.
+    Offline {
+        reason: String,
+        retry_at: Instant,
+    },
+}
+
+pub struct RemoteGame {
+    status: Status,
+    rooms: Vec<RoomInfo>,
+    /// The room under the cursor in the lobby.
+    room_cursor: usize,
+    room: Option<RoomInfo>,
+    /// How many are waiting to be matched, while queued.
+    queued: Option<usize>,
+    seat: Option<Player>,
+    sight: Option<PlayerSight>,
+    actions: Vec<Action>,
+    /// The selected tile, counting the hand in the order it is shown and the drawn tile last.
+    cursor: usize,
+    /// When the default action is played for the seat.
+    deadline: Option<Instant>,
+    scores: WindSet<i32>,
+    result: Option<RoundResult>,
+    log: VecDeque<Event>,
+    error: Option<String>,
+}
+
+impl Default for RemoteGame {
+    fn default() -> Self {
+        RemoteGame {
+            status: Status::Connecting,
+            rooms: Vec::new(),
+            room_cursor: 0,
+            room: None,
+            queued: None,
+            seat: None,
+            sight: None,
+            actions: Vec::new(),
+            cursor: 0,
+            deadline: None,
+            scores: WindSet::from_fn(|_| STARTING_POINTS),
+            result: None,
+            log: VecDeque::new(),
+            error: None,
+        }
+    }
+}
+
+impl RemoteGame {
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+    pub fn rooms(&self) -> &[RoomInfo] {
+        &self.rooms
+    }
+    pub fn room_cursor(&self) -> usize {
+        self.room_cursor
+    }
+    pub fn move_room_cursor(&mut self, by: isize) {
+        let len = self.rooms.len() as isize;
+        if len > 0 {
+            self.room_cursor = (self.room_cursor as isize + by).rem_euclid(len) as usize;
+        }
+    }
+    /// The room the client is in.
+    pub fn room(&self) -> Option<&RoomInfo> {
+        self.room.as_ref()
+    }
+    pub fn queued(&self) -> Option<usize> {
+        self.queued
+    }
+    /// The seat played at the table, once the round started.
+    pub fn seat(&self) -> Option<Player> {
+        self.seat
+    }
+    pub fn sight(&self) -> Option<&PlayerSight> {
+        self.sight.as_ref()
+    }
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+    pub fn scores(&self) -> &WindSet<i32> {
+        &self.scores
+    }
+    pub fn result(&self) -> Option<&RoundResult> {
+        self.result.as_ref()
+    }
+    /// The events of the table, the latest last.
+    pub fn log(&self) -> impl Iterator<Item = &Event> {
+        self.log.iter()
+    }
+    /// The last error the server answered with.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+    pub fn is_my_turn(&self) -> bool {
+        self.actions.iter().any(|a| matches!(a, Action::Discard(_)))
+    }
+    /// The tile under the cursor.
+    pub fn selected(&self) -> Option<TileId> {
+        self.tiles().get(self.cursor).copied()
+    }
+    /// Put the cursor on `tile`, `false` if the seat doesn't hold it.
+    pub fn select(&mut self, tile: TileId) -> bool {
+        let Some(cursor) = self.tiles().iter().position(|t| *t == tile) else {
+            return false;
+        };
+        self.cursor = cursor;
+        true
+    }
+    /// Move the cursor `by` tiles, wrapping around the ends.
+    pub fn move_cursor(&mut self, by: isize) {
+        let len = self.tiles().len() as isize;
+        if len > 0 {
+            self.cursor = (self.cursor as isize + by).rem_euclid(len) as usize;
+        }
+    }
+    /// What the seat may do now besides discarding.
+    pub fn choices(&self) -> Vec<Action> {
+        self.actions
+            .iter()
+            .filter(|a| !matches!(a, Action::Discard(_)))
+            .cloned()
+            .collect()
+    }
+    /// Play one of the choices, the returned message is to be sent.
+    pub fn act(&mut self, action: Action) -> ClientMessage {
+        // the server answers with the next choices, these are spent
+        self.actions.clear();
+        self.deadline = None;
+        self.error = None;
+        ClientMessage::Action(action)
+    }
+    /// Discard the selected tile, `None` if it isn't the seat's turn.
+    pub fn discard(&mut self) -> Option<ClientMessage> {
+        let seat = self.seat?;
+        let tile = self.selected().filter(|_| self.is_my_turn())?;
+        Some(self.act(Action::Discard(seat.discard(tile))))
+    }
+    /// Put the result of the round away and go back to the room.
+    pub fn dismiss_result(&mut self) {
+        self.result = None;
+        self.sight = None;
+        self.seat = None;
+        self.actions.clear();
+        self.deadline = None;
+    }
+    pub fn create_room(&mut self) -> ClientMessage {
+        self.error = None;
+        ClientMessage::CreateRoom(RoomSettings::default())
+    }
+    /// Join the room under the cursor, or the one with `code` if given.
+    pub fn join_room(&mut self, code: Option<String>) -> Option<ClientMessage> {
+        let code = match code {
+            Some(code) => RoomCode(code.trim().to_owned()),
+            None => self.rooms.get(self.room_cursor)?.code.clone(),
+        };
+        self.error = None;
+        Some(ClientMessage::JoinRoom(code))
+    }
+    pub fn leave_room(&mut self) -> ClientMessage {
+        self.room = None;
+        self.dismiss_result();
+        ClientMessage::LeaveRoom
+    }
+    /// Take in what came from the connection at `now`, returns what to answer with.
+    pub fn handle(&mut self, event: NetEvent, now: Instant) -> Option<ClientMessage> {
+        match event {
+            NetEvent::Connected { resumed } => {
+                self.status = Status::Online;
+                if !resumed {
+                    // a new session, whatever the last one was in is gone
+                    *self = RemoteGame {
+                        status: Status::Online,
+                        scores: self.scores,
+                        log: std::mem::take(&mut self.log),
+                        ..Default::default()
+                    };
+                    return Some(ClientMessage::ListRooms);
+                }
+                None
+            }
+            NetEvent::Disconnected { reason, retry } => {
+                self.status = Status::Offline {
+                    reason,
+                    retry_at: now + retry,
+                };
+                None
+            }
+            NetEvent::Message(message) => {
+                self.receive(message, now);
+                None
+            }
+        }
+    }
+    fn receive(&mut self, message: ServerMessage, now: Instant) {
+        match message {
+            ServerMessage::Rooms(rooms) => {
+                self.rooms = rooms;
+                self.room_cursor = self.room_cursor.min(self.rooms.len().saturating_sub(1));
+            }
+            ServerMessage::Room(room) => {
+                self.queued = None;
+                if !room.started && self.result.is_none() {
+                    self.dismiss_result();
+                }
+                self.room = Some(room);
+            }
+            ServerMessage::Queued { waiting } => self.queued = Some(waiting),
+            ServerMessage::Seated { seat, .. } => {
+                self.seat = Some(seat);
+                self.result = None;
+            }
+            ServerMessage::Sight(sight) => {
+                let drawn = self.sight.as_ref().and_then(|s| s.draw);
+                let new_draw = sight.draw.is_some() && sight.draw != drawn;
+                self.sight = Some(*sight);
+                let len = self.tiles().len();
+                self.cursor = if new_draw {
+                    len.saturating_sub(1)
+                } else {
+                    self.cursor.min(len.saturating_sub(1))
+                };
+            }
+            ServerMessage::LegalActions(actions) => {
+                if actions.is_empty() {
+                    self.deadline = None;
+                }
+                self.actions = actions;
+            }
+            ServerMessage::Timer {
+                player,
+                millis,
+                bank,
+            } if Some(player) == self.seat => {
+                self.deadline = Some(now + Duration::from_millis(millis + bank));
+            }
+            ServerMessage::Event(event) => self.event(event),
+            ServerMessage::Error(error) => self.error = Some(error.message),
+            _ => {}
+        }
+    }
+    fn event(&mut self, event: Event) {
+        match &event {
+            Event::Started { .. } => self.result = None,
+            Event::DrawGame { tenpai, payments } => {
+                self.pay(payments);
+                self.result = Some(RoundResult {
+                    wins: Vec::new(),
+                    tenpai: tenpai.clone(),
+                    payments: *payments,
+                });
+                self.actions.clear();
+                self.deadline = None;
+            }
+            Event::Win {
+                player,
+                win,
+                from,
+                score,
+                payments,
+            } => {
+                self.pay(payments);
+                // every winner of a double ron has an event of their own
+                let result = self.result.get_or_insert_with(|| RoundResult {
+                    wins: Vec::new(),
+                    tenpai: Vec::new(),
+                    payments: WindSet::default(),
+                });
+                result.wins.push(Agari {
+                    player: *player,
+                    win: win.clone(),
+                    from: *from,
+                    score: score.clone(),
+                    payments: *payments,
+                });
+                for (seat, paid) in payments.iter() {
+                    *result.payments.get_mut(seat) += paid;
+                }
+                self.actions.clear();
+                self.deadline = None;
+            }
+            _ => {}
+        }
+        if self.log.len() == LOG_LEN {
+            self.log.pop_front();
+        }
+        self.log.push_back(event);
+    }
+    fn pay(&mut self, payments: &WindSet<i32>) {
+        for (seat, paid) in payments.iter() {
+            *self.scores.get_mut(seat) += paid;
+        }
+    }
+    fn tiles(&self) -> Vec<TileId> {
+        let Some(sight) = &self.sight else {
+            return Vec::new();
+        };
+        hand_order(&sight.self_deck.hand, sight.draw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rahjong::{
+        rules::jp::round::{Round, RoundState},
+        tile::Wind,
+    };
+    use rahjong_server::protocol::TableId;
+
+    #[test]
+    fn test_follow_the_server() {
+        let mut game = RemoteGame::default();
+        let now = Instant::now();
+        let reply = game.handle(NetEvent::Connected { resumed: false }, now);
+        assert!(matches!(reply, Some(ClientMessage::ListRooms)));
+
+        let mut round = Round::new(Wind::East);
+        round.start().unwrap();
+        let RoundState::WaitDiscard(dealer) = *round.state() else {
+            panic!("the dealer discards first");
+        };
+        let message = |m| NetEvent::Message(m);
+        game.handle(
+            message(ServerMessage::Seated {
+                table: TableId(1),
+                seat: dealer,
+            }),
+            now,
+        );
+        let sight = Box::new(round.player_sight(dealer));
+        game.handle(message(ServerMessage::Sight(sight)), now);
+        let actions = round.legal_actions(dealer);
+        game.handle(message(ServerMessage::LegalActions(actions)), now);
+        game.handle(
+            message(ServerMessage::Timer {
+                player: dealer,
+                millis: 1000,
+                bank: 500,
+            }),
+            now,
+        );
+        assert!(game.is_my_turn());
+        assert_eq!(game.deadline(), Some(now + Duration::from_millis(1500)));
+        // the cursor starts on the drawn tile
+        assert_eq!(game.selected(), round.draw().map(|d| d.tile()));
+
+        let Some(ClientMessage::Action(discard)) = game.discard() else {
+            panic!("expected a discard");
+        };
+        round.apply(&discard, dealer).unwrap();
+        assert!(!game.is_my_turn());
+        assert!(game.discard().is_none());
+
+        let mut payments = WindSet::from_fn(|_| -1000);
+        payments.insert(dealer, 3000);
+        let ended = Event::DrawGame {
+            tenpai: vec![dealer],
+            payments,
+        };
+        game.handle(message(ServerMessage::Event(ended)), now);
+        assert_eq!(*game.scores().get(dealer), STARTING_POINTS + 3000);
+        assert!(game.result().is_some());
+        assert_eq!(game.log().count(), 1);
+
+        game.handle(
+            NetEvent::Disconnected {
+                reason: "gone".to_owned(),
+                retry: Duration::from_secs(1),
+            },
+            now,
+        );
+        assert!(matches!(game.status(), Status::Offline { .. }));
+        // a resumed session keeps the table
+        assert!(game
+            .handle(NetEvent::Connected { resumed: true }, now)
+            .is_none());
+        assert!(game.result().is_some());
+    }
+}
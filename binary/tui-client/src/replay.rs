@@ -0,0 +1,654 @@
+//! Finished games to step through: the records saved by the server, or logs imported from mjai
+//! or Tenhou.
+//!
+//! A replay is a list of rounds and every round a list of steps, each step keeps what every seat
+//! saw right after it so the viewer can go both ways and show any seat.
+pub mod mjai;
+pub mod tenhou;
+
+use std::{collections::HashMap, fs, io, path::Path};
+
+use rahjong::{
+    discard::{Discard, DiscardSet},
+    draw::Draw,
+    hand::{decompose::FaceCounts, Deck, Gang},
+    player::{Player, WindSet},
+    rules::jp::{
+        riichi::Richii,
+        round::{Action, PlayerSight, Reason, RoundState},
+    },
+    tile::{TileFace, TileId, TileIndex, Wind, B5, C5, D5},
+};
+use rahjong_server::{records::GameRecord, table::Outcome};
+
+use crate::game::STARTING_POINTS;
+
+/// Tiles in the wall once the hands are dealt and the dead wall is set aside.
+const LIVE_WALL: usize = 136 - 14 - 4 * 13;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Call {
+    Chi,
+    Pon,
+    Kan,
+    Nukidora,
+}
+
+/// What happened at a step.
+#[derive(Debug, Clone)]
+pub enum Move {
+    Deal,
+    Draw(Player, TileId),
+    Discard {
+        discard: Discard,
+        riichi: bool,
+    },
+    Call {
+        player: Player,
+        call: Call,
+    },
+    /// A new dora indicator was flipped.
+    Dora(TileId),
+    /// `from` dealt in, `None` for a tsumo.
+    Win {
+        player: Player,
+        from: Option<Player>,
+        payments: WindSet<i32>,
+    },
+    DrawGame {
+        tenpai: Vec<Player>,
+        payments: WindSet<i32>,
+    },
+    Resigned(Player),
+    Abandoned,
+}
+
+pub struct Step {
+    pub play: Move,
+    pub sights: Box<WindSet<PlayerSight>>,
+    pub scores: WindSet<i32>,
+}
+
+pub struct ReplayRound {
+    pub round_wind: Wind,
+    /// The hand of the round wind, from 1.
+    pub hand: u8,
+    pub honba: u8,
+    pub names: WindSet<String>,
+    pub steps: Vec<Step>,
+}
+
+impl ReplayRound {
+    pub fn title(&self) -> String {
+        format!("{:?} {}, {} honba", self.round_wind, self.hand, self.honba)
+    }
+}
+
+pub struct Replay {
+    pub rounds: Vec<ReplayRound>,
+}
+
+impl Replay {
+    /// Read a saved game: a record or a list of records from the server, a Tenhou log or mjai
+    /// events one per line.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+    pub fn parse(text: &str) -> io::Result<Self> {
+        if let Ok(record) = serde_json::from_str::<GameRecord>(text) {
+            return Self::from_records(&[record]);
+        }
+        if let Ok(records) = serde_json::from_str::<Vec<GameRecord>>(text) {
+            return Self::from_records(&records);
+        }
+        if let Ok(log) = serde_json::from_str::<tenhou::Log>(text) {
+            return mjai::replay(&log.events()?);
+        }
+        let events = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<mjai::Event>, _>>()
+            .map_err(|e| invalid(format!("not a game record, Tenhou or mjai log: {e}")))?;
+        mjai::replay(&events)
+    }
+    /// Deal every recorded round again and play its actions.
+    pub fn from_records(records: &[GameRecord]) -> io::Result<Self> {
+        let mut scores = WindSet::from_fn(|_| STARTING_POINTS);
+        let mut rounds = Vec::new();
+        for (i, record) in records.iter().enumerate() {
+            let round = record_round(record, i, scores)?;
+            scores = round.steps.last().expect("a round has a deal").scores;
+            rounds.push(round);
+        }
+        if rounds.is_empty() {
+            return Err(invalid("no rounds in the log"));
+        }
+        Ok(Replay { rounds })
+    }
+}
+
+fn record_round(
+    record: &GameRecord,
+    index: usize,
+    scores: WindSet<i32>,
+) -> io::Result<ReplayRound> {
+    let mut round = record.ruleset.round_with_seed(record.dealer, record.seed);
+    round.start().map_err(refused)?;
+    let sights = |round: &rahjong::rules::jp::round::Round| {
+        Box::new(WindSet::from_fn(|seat| round.player_sight(seat)))
+    };
+    let mut steps = vec![Step {
+        play: Move::Deal,
+        sights: sights(&round),
+        scores,
+    }];
+    for (player, action) in &record.actions {
+        let draw = round.draw();
+        round.apply(action, *player).map_err(refused)?;
+        let play = match action {
+            Action::Discard(discard) => Some(Move::Discard {
+                discard: *discard,
+                riichi: false,
+            }),
+            Action::Richii(riichi) => Some(Move::Discard {
+                discard: riichi.discard,
+                riichi: true,
+            }),
+            Action::Chi(_) => Some(Move::Call {
+                player: *player,
+                call: Call::Chi,
+            }),
+            Action::Pon(_) => Some(Move::Call {
+                player: *player,
+                call: Call::Pon,
+            }),
+            Action::Kan(_) => Some(Move::Call {
+                player: *player,
+                call: Call::Kan,
+            }),
+            Action::Nukidora(_) => Some(Move::Call {
+                player: *player,
+                call: Call::Nukidora,
+            }),
+            _ => None,
+        };
+        if let Some(play) = play {
+            steps.push(Step {
+                play,
+                sights: sights(&round),
+                scores,
+            });
+        }
+        if let (RoundState::WaitDiscard(to), Some(drawn)) = (*round.state(), round.draw()) {
+            if draw != Some(drawn) {
+                steps.push(Step {
+                    play: Move::Draw(to, drawn.tile()),
+                    sights: sights(&round),
+                    scores,
+                });
+            }
+        }
+    }
+    let mut scores = scores;
+    // every winner of a double ron gets a step of their own
+    let plays = match &record.outcome {
+        Outcome::Win { wins, .. } => wins
+            .iter()
+            .map(|agari| Move::Win {
+                player: agari.player,
+                from: agari.from,
+                payments: agari.payments,
+            })
+            .collect(),
+        Outcome::DrawGame { tenpai, payments } => vec![Move::DrawGame {
+            tenpai: tenpai.clone(),
+            payments: *payments,
+        }],
+        Outcome::Resigned(player) => vec![Move::Resigned(*player)],
+        Outcome::Abandoned => vec![Move::Abandoned],
+    };
+    for play in plays {
+        if let Move::Win { payments, .. } | Move::DrawGame { payments, .. } = &play {
+            for (seat, paid) in payments.iter() {
+                *scores.get_mut(seat) += paid;
+            }
+        }
+        steps.push(Step {
+            play,
+            sights: sights(&round),
+            scores,
+        });
+    }
+    let names = WindSet::from_fn(|seat| {
+        (record.players.get(seat).clone()).unwrap_or_else(|| "bot".to_owned())
+    });
+    Ok(ReplayRound {
+        round_wind: Wind::East,
+        hand: index as u8 + 1,
+        honba: 0,
+        names,
+        steps,
+    })
+}
+
+/// A table built up from the events of an imported log, the wall itself is unknown.
+///
+/// Red fives are told apart by their index, they are always [`TileIndex::T0`].
+struct Table {
+    dealer: Wind,
+    wall_rest: usize,
+    decks: WindSet<Deck>,
+    discards: DiscardSet,
+    dora_indicators: Vec<TileId>,
+    riichi: WindSet<Option<Richii>>,
+    /// The seat that declared riichi and has yet to discard.
+    declared: Option<Player>,
+    draw: Option<Draw>,
+    to_discard: Option<Player>,
+    /// The copies of every face handed out so far, as a mask of their indices.
+    handed: HashMap<TileFace, u8>,
+}
+
+impl Table {
+    fn new(dealer: Wind) -> Self {
+        Table {
+            dealer,
+            wall_rest: LIVE_WALL,
+            decks: Default::default(),
+            discards: DiscardSet::new(),
+            dora_indicators: Vec::new(),
+            riichi: Default::default(),
+            declared: None,
+            draw: None,
+            to_discard: None,
+            handed: HashMap::new(),
+        }
+    }
+    /// Hand out a copy of `face` that is still unseen.
+    fn tile(&mut self, face: TileFace, red: bool) -> io::Result<TileId> {
+        let handed = self.handed.entry(face).or_default();
+        let is_free = |index: TileIndex| *handed & index.const_into_mask() == 0;
+        let index = if red {
+            Some(TileIndex::T0).filter(|i| is_free(*i))
+        } else {
+            // the red copy is the last one handed out for a five that isn't called red
+            let mut order: Vec<TileIndex> = TileIndex::enumerate().collect();
+            if is_five(face) {
+                order.rotate_left(1);
+            }
+            order.into_iter().find(|i| is_free(*i))
+        };
+        let Some(index) = index else {
+            return Err(invalid(format!("too many copies of {face:?}")));
+        };
+        *handed |= index.const_into_mask();
+        Ok(TileId::from_face_idx(face, index))
+    }
+    fn deal(&mut self, seat: Player, tiles: &[(TileFace, bool)]) -> io::Result<()> {
+        for (face, red) in tiles {
+            let tile = self.tile(*face, *red)?;
+            self.decks.get_mut(seat).hand.tiles.insert(tile);
+        }
+        Ok(())
+    }
+    fn dora(&mut self, face: TileFace, red: bool) -> io::Result<TileId> {
+        let tile = self.tile(face, red)?;
+        self.dora_indicators.push(tile);
+        Ok(tile)
+    }
+    fn draw(&mut self, seat: Player, face: TileFace, red: bool) -> io::Result<TileId> {
+        let tile = self.tile(face, red)?;
+        self.wall_rest = self.wall_rest.saturating_sub(1);
+        self.draw = Some(Draw::new(tile));
+        self.to_discard = Some(seat);
+        Ok(tile)
+    }
+    fn declare_riichi(&mut self, seat: Player) {
+        self.declared = Some(seat);
+    }
+    fn discard(
+        &mut self,
+        seat: Player,
+        face: TileFace,
+        red: bool,
+        tsumogiri: bool,
+    ) -> io::Result<Discard> {
+        let drawn = self.draw.take().map(|d| d.tile());
+        let tile = match drawn {
+            Some(drawn) if tsumogiri || (drawn.face() == face && !self.holds(seat, face)) => drawn,
+            _ => {
+                let tile = self.find(seat, face, red)?;
+                let hand = &mut self.decks.get_mut(seat).hand;
+                hand.remove(tile);
+                if let Some(drawn) = drawn {
+                    hand.tiles.insert(drawn);
+                }
+                tile
+            }
+        };
+        let discard = seat.discard(tile);
+        if tsumogiri {
+            self.discards.add_tsumogiri(discard);
+        } else {
+            self.discards.add(discard);
+        }
+        if self.declared == Some(seat) {
+            self.declared = None;
+            self.riichi.insert(seat, Some(Richii { discard }));
+        }
+        self.to_discard = None;
+        Ok(discard)
+    }
+    /// The last discard, which `face` claims.
+    fn claim(&self, face: TileFace) -> io::Result<Discard> {
+        self.discards
+            .iter()
+            .last()
+            .filter(|d| d.tile().face() == face)
+            .copied()
+            .ok_or_else(|| invalid(format!("no discard of {face:?} to call")))
+    }
+    fn chi(
+        &mut self,
+        seat: Player,
+        claim: TileFace,
+        consumed: &[(TileFace, bool)],
+    ) -> io::Result<()> {
+        let claim = self.claim(claim)?;
+        let tiles = self.consume(seat, consumed)?;
+        let chi = rahjong::hand::Chi { claim, tiles };
+        self.decks
+            .get_mut(seat)
+            .chi(chi)
+            .map_err(|_| invalid("illegal chi"))?;
+        self.to_discard = Some(seat);
+        Ok(())
+    }
+    fn pon(
+        &mut self,
+        seat: Player,
+        claim: TileFace,
+        consumed: &[(TileFace, bool)],
+    ) -> io::Result<()> {
+        let claim = self.claim(claim)?;
+        let tiles = self.consume(seat, consumed)?;
+        let peng = rahjong::hand::Peng { claim, tiles };
+        self.decks
+            .get_mut(seat)
+            .peng(peng)
+            .map_err(|_| invalid("illegal pon"))?;
+        self.to_discard = Some(seat);
+        Ok(())
+    }
+    fn open_kan(&mut self, seat: Player, claim: TileFace) -> io::Result<()> {
+        let claim = self.claim(claim)?.tile();
+        self.kan(seat, Gang::MingGang { claim })
+    }
+    fn closed_kan(&mut self, seat: Player, face: TileFace) -> io::Result<()> {
+        self.keep_draw(seat);
+        let displayed = self.find(seat, face, false)?;
+        self.kan(seat, Gang::AnGang { displayed })
+    }
+    fn added_kan(&mut self, seat: Player, face: TileFace, red: bool) -> io::Result<()> {
+        self.keep_draw(seat);
+        let claim = self.find(seat, face, red)?;
+        let peng = self
+            .decks
+            .get(seat)
+            .melded
+            .peng
+            .iter()
+            .find(|p| p.claim.tile().face() == face)
+            .copied()
+            .ok_or_else(|| invalid(format!("no pon of {face:?} to add to")))?;
+        self.kan(seat, Gang::JiaGang { peng, claim })
+    }
+    fn kan(&mut self, seat: Player, gang: Gang) -> io::Result<()> {
+        let deck = self.decks.get_mut(seat);
+        deck.gang(gang).map_err(|_| invalid("illegal kan"))?;
+        self.to_discard = None;
+        Ok(())
+    }
+    /// Put the drawn tile in the hand, before a kan out of it.
+    fn keep_draw(&mut self, seat: Player) {
+        if let Some(drawn) = self.draw.take() {
+            self.decks.get_mut(seat).hand.tiles.insert(drawn.tile());
+        }
+    }
+    fn holds(&self, seat: Player, face: TileFace) -> bool {
+        self.decks
+            .get(seat)
+            .hand
+            .tiles
+            .iter()
+            .any(|t| t.face() == face)
+    }
+    /// A tile of `face` in the hand, the red one if `red`.
+    fn find(&self, seat: Player, face: TileFace, red: bool) -> io::Result<TileId> {
+        let tiles: Vec<TileId> = (self.decks.get(seat).hand.tiles.iter())
+            .filter(|t| t.face() == face)
+            .collect();
+        tiles
+            .iter()
+            .find(|t| is_red(**t) == red)
+            .or(tiles.first())
+            .copied()
+            .ok_or_else(|| invalid(format!("{:?} holds no {face:?}", seat.wind())))
+    }
+    fn consume(&self, seat: Player, consumed: &[(TileFace, bool)]) -> io::Result<[TileId; 2]> {
+        let [(a, a_red), (b, b_red)] = consumed else {
+            return Err(invalid("a call takes two tiles from the hand"));
+        };
+        let first = self.find(seat, *a, *a_red)?;
+        let second = if a == b {
+            let tiles: Vec<TileId> = (self.decks.get(seat).hand.tiles.iter())
+                .filter(|t| t.face() == *b && *t != first)
+                .collect();
+            *tiles
+                .iter()
+                .find(|t| is_red(**t) == *b_red)
+                .or(tiles.first())
+                .ok_or_else(|| invalid(format!("{:?} holds no {b:?}", seat.wind())))?
+        } else {
+            self.find(seat, *b, *b_red)?
+        };
+        Ok([first, second])
+    }
+    fn sights(&self) -> Box<WindSet<PlayerSight>> {
+        Box::new(WindSet::from_fn(|seat| PlayerSight {
+            dealer: self.dealer,
+            wall_rest: self.wall_rest,
+            self_deck: self.decks.get(seat).clone(),
+            other_hand_size: (self.decks.iter())
+                .filter(|(p, _)| *p != seat)
+                .map(|(p, d)| (p, d.hand.len() as u8))
+                .collect(),
+            other_hand_melded: (self.decks.iter())
+                .filter(|(p, _)| *p != seat)
+                .map(|(p, d)| (p, d.melded.clone()))
+                .collect(),
+            bonus: Default::default(),
+            discards: self.discards.clone(),
+            dora_indicators: self.dora_indicators.clone(),
+            riichi: self.riichi.clone(),
+            draw: self.draw,
+            to_discard: self.to_discard,
+        }))
+    }
+}
+
+fn is_five(face: TileFace) -> bool {
+    [B5, C5, D5].contains(&face)
+}
+
+/// Whether `tile` is a red five, in an imported log.
+pub fn is_red(tile: TileId) -> bool {
+    let (face, index) = tile.into_face_idx();
+    is_five(face) && index == TileIndex::T0
+}
+
+/// How far a seat is from a ready hand, and what it waits on once it is.
+pub struct Analysis {
+    pub shanten: i8,
+    pub waits: Vec<TileFace>,
+}
+
+impl Analysis {
+    /// The hand of `seat` as it sees it, with the drawn tile while it is theirs.
+    pub fn of(sight: &PlayerSight, seat: Player) -> Self {
+        let draw = sight.draw.filter(|_| sight.to_discard == Some(seat));
+        let counts = FaceCounts::new(
+            sight
+                .self_deck
+                .hand
+                .tiles
+                .iter()
+                .chain(draw.map(|d| d.tile())),
+        );
+        Analysis {
+            shanten: counts.shanten(),
+            waits: counts.waits(),
+        }
+    }
+}
+
+/// Where the viewer is in a replay.
+pub struct Viewer {
+    replay: Replay,
+    round: usize,
+    step: usize,
+    seat: Player,
+    reveal: bool,
+}
+
+impl Viewer {
+    pub fn new(replay: Replay) -> Self {
+        Viewer {
+            replay,
+            round: 0,
+            step: 0,
+            seat: Player::EAST,
+            reveal: false,
+        }
+    }
+    pub fn rounds(&self) -> &[ReplayRound] {
+        &self.replay.rounds
+    }
+    pub fn round_index(&self) -> usize {
+        self.round
+    }
+    pub fn round(&self) -> &ReplayRound {
+        &self.replay.rounds[self.round]
+    }
+    pub fn step_index(&self) -> usize {
+        self.step
+    }
+    pub fn step(&self) -> &Step {
+        &self.round().steps[self.step]
+    }
+    /// The seat whose view is shown.
+    pub fn seat(&self) -> Player {
+        self.seat
+    }
+    /// Whether every hand is shown.
+    pub fn reveal(&self) -> bool {
+        self.reveal
+    }
+    /// What the shown seat sees at this step.
+    pub fn sight(&self) -> &PlayerSight {
+        self.step().sights.get(self.seat)
+    }
+    pub fn analysis(&self, seat: Player) -> Analysis {
+        Analysis::of(self.step().sights.get(seat), seat)
+    }
+    /// Step `by` steps, stopping at either end of the round.
+    pub fn step_by(&mut self, by: isize) {
+        let last = self.round().steps.len() as isize - 1;
+        self.step = (self.step as isize + by).clamp(0, last) as usize;
+    }
+    pub fn first(&mut self) {
+        self.step = 0;
+    }
+    pub fn last(&mut self) {
+        self.step = self.round().steps.len() - 1;
+    }
+    /// Go to the start of round `round`, counting from 0.
+    pub fn jump(&mut self, round: usize) {
+        if round < self.replay.rounds.len() {
+            self.round = round;
+            self.step = 0;
+        }
+    }
+    pub fn next_seat(&mut self) {
+        self.seat = self.seat.next();
+    }
+    pub fn toggle_reveal(&mut self) {
+        self.reveal = !self.reveal;
+    }
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn refused(reason: Reason) -> io::Error {
+    invalid(format!(
+        "the recorded actions don't replay: {}",
+        reason.expr()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::LocalGame;
+    use rahjong::bot::Tsumogiri;
+    use rahjong_server::{protocol::Ruleset, records::RecordId};
+
+    #[test]
+    fn test_replay_record() {
+        let mut game = LocalGame::new(Player::EAST).with_bots(|_| Box::new(Tsumogiri));
+        while game.result().is_none() {
+            while game.step() {}
+            if game.discard().is_err() {
+                let _ = game.act(Action::Pass);
+            }
+        }
+        let round = game.round();
+        let result = game.result().unwrap();
+        let record = GameRecord {
+            id: RecordId(1),
+            finished_at: 0,
+            ruleset: Ruleset::Riichi,
+            seed: round.seed(),
+            dealer: round.dealer(),
+            players: WindSet::from_fn(|seat| (seat == Player::EAST).then(|| "me".to_owned())),
+            actions: round.history().to_vec(),
+            outcome: Outcome::DrawGame {
+                tenpai: result.tenpai.clone(),
+                payments: result.payments,
+            },
+            scores: result.payments,
+        };
+        let text = serde_json::to_string(&record).unwrap();
+        let mut viewer = Viewer::new(Replay::parse(&text).unwrap());
+        assert_eq!(viewer.rounds().len(), 1);
+        assert_eq!(viewer.round().names.get(Player::SOUTH), "bot");
+        assert!(matches!(viewer.step().play, Move::Deal));
+        viewer.last();
+        assert!(matches!(viewer.step().play, Move::DrawGame { .. }));
+        assert_eq!(viewer.step().scores, *game.scores());
+        assert_eq!(viewer.sight().wall_rest, 0);
+        // stepping back restores the discards as they were
+        viewer.step_by(-2);
+        let discards = viewer.sight().discards.iter().count();
+        viewer.first();
+        assert_eq!(viewer.sight().discards.iter().count(), 0);
+        assert!(discards > 0);
+        for (seat, _) in viewer.step().sights.iter() {
+            let analysis = viewer.analysis(seat);
+            assert!((0..=6).contains(&analysis.shanten));
+        }
+    }
+}
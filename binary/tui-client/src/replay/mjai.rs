@@ -0,0 +1,468 @@
+//! Logs in the mjai format: one JSON event per line, seats are numbered from the first dealer
+//! and tiles are written `1m`, `5pr` (a red five), `E` or `P`.
+use std::io;
+
+use rahjong::{
+    player::{Player, WindSet},
+    tile::{
+        TileFace, Wind, B1, B2, B3, B4, B5, B6, B7, B8, B9, C1, C2, C3, C4, C5, C6, C7, C8, C9, D1,
+        D2, D3, D4, D5, D6, D7, D8, D9, EAST, GREEN, NORTH, RED, SOUTH, WEST, WHITE,
+    },
+};
+use serde::Deserialize;
+
+use super::{invalid, Call, Move, Replay, ReplayRound, Step, Table};
+use crate::game::STARTING_POINTS;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    StartGame {
+        #[serde(default)]
+        names: Vec<String>,
+    },
+    StartKyoku {
+        bakaze: String,
+        kyoku: u8,
+        honba: u8,
+        oya: usize,
+        dora_marker: String,
+        #[serde(default)]
+        scores: Option<Vec<i32>>,
+        tehais: Vec<Vec<String>>,
+    },
+    Tsumo {
+        actor: usize,
+        pai: String,
+    },
+    Dahai {
+        actor: usize,
+        pai: String,
+        tsumogiri: bool,
+    },
+    Chi {
+        actor: usize,
+        target: usize,
+        pai: String,
+        consumed: Vec<String>,
+    },
+    Pon {
+        actor: usize,
+        target: usize,
+        pai: String,
+        consumed: Vec<String>,
+    },
+    Daiminkan {
+        actor: usize,
+        target: usize,
+        pai: String,
+        consumed: Vec<String>,
+    },
+    Ankan {
+        actor: usize,
+        consumed: Vec<String>,
+    },
+    Kakan {
+        actor: usize,
+        pai: String,
+        consumed: Vec<String>,
+    },
+    Reach {
+        actor: usize,
+    },
+    ReachAccepted {
+        actor: usize,
+        #[serde(default)]
+        scores: Option<Vec<i32>>,
+    },
+    Dora {
+        dora_marker: String,
+    },
+    Hora {
+        actor: usize,
+        target: usize,
+        #[serde(default)]
+        deltas: Option<Vec<i32>>,
+        #[serde(default)]
+        scores: Option<Vec<i32>>,
+    },
+    Ryukyoku {
+        #[serde(default)]
+        tenpais: Option<Vec<bool>>,
+        #[serde(default)]
+        deltas: Option<Vec<i32>>,
+        #[serde(default)]
+        scores: Option<Vec<i32>>,
+    },
+    EndKyoku,
+    EndGame,
+    #[serde(other)]
+    Other,
+}
+
+/// A tile in mjai notation and whether it is red.
+pub fn parse_tile(pai: &str) -> io::Result<(TileFace, bool)> {
+    const MAN: [TileFace; 9] = [C1, C2, C3, C4, C5, C6, C7, C8, C9];
+    const PIN: [TileFace; 9] = [D1, D2, D3, D4, D5, D6, D7, D8, D9];
+    const SOU: [TileFace; 9] = [B1, B2, B3, B4, B5, B6, B7, B8, B9];
+    let honor = match pai {
+        "E" => Some(EAST),
+        "S" => Some(SOUTH),
+        "W" => Some(WEST),
+        "N" => Some(NORTH),
+        "P" => Some(WHITE),
+        "F" => Some(GREEN),
+        "C" => Some(RED),
+        "?" => {
+            return Err(invalid(
+                "the log hides tiles, only full logs can be replayed",
+            ))
+        }
+        _ => None,
+    };
+    if let Some(face) = honor {
+        return Ok((face, false));
+    }
+    let (tile, red) = match pai.strip_suffix('r') {
+        Some(five) => (five, true),
+        None => (pai, false),
+    };
+    let mut chars = tile.chars();
+    let (Some(num @ '1'..='9'), Some(suit), None) = (chars.next(), chars.next(), chars.next())
+    else {
+        return Err(invalid(format!("no such tile {pai:?}")));
+    };
+    let num = num as usize - '1' as usize;
+    let face = match suit {
+        'm' => MAN[num],
+        'p' => PIN[num],
+        's' => SOU[num],
+        _ => return Err(invalid(format!("no such tile {pai:?}"))),
+    };
+    Ok((face, red))
+}
+
+fn tiles(pais: &[String]) -> io::Result<Vec<(TileFace, bool)>> {
+    pais.iter().map(|p| parse_tile(p)).collect()
+}
+
+/// Scores or deltas by seat number, turned to the winds of the round.
+fn by_wind(values: &[i32], oya: usize) -> WindSet<i32> {
+    WindSet::from_fn(|seat| {
+        values
+            .get((seat.wind() as usize + oya) % 4)
+            .copied()
+            .unwrap_or_default()
+    })
+}
+
+/// The round being read and where its seats sit.
+struct Kyoku {
+    round: ReplayRound,
+    table: Table,
+    oya: usize,
+    scores: WindSet<i32>,
+}
+
+impl Kyoku {
+    fn seat(&self, actor: usize) -> Player {
+        Wind::from_index((actor + 4 - self.oya) % 4).into()
+    }
+    fn push(&mut self, play: Move) {
+        self.round.steps.push(Step {
+            play,
+            sights: self.table.sights(),
+            scores: self.scores,
+        });
+    }
+}
+
+/// Play the events of a game onto tables, one round per kyoku.
+pub fn replay(events: &[Event]) -> io::Result<Replay> {
+    let mut names: Vec<String> = Vec::new();
+    let mut seat_scores = vec![STARTING_POINTS; 4];
+    let mut rounds = Vec::new();
+    let mut kyoku: Option<Kyoku> = None;
+    for event in events {
+        if let Event::StartGame { names: n } = event {
+            names.clone_from(n);
+            continue;
+        }
+        if let Event::StartKyoku {
+            bakaze,
+            kyoku: hand,
+            honba,
+            oya,
+            dora_marker,
+            scores,
+            tehais,
+        } = event
+        {
+            if let Some(scores) = scores {
+                seat_scores.clone_from(scores);
+            }
+            let round_wind = match bakaze.as_str() {
+                "E" => Wind::East,
+                "S" => Wind::South,
+                "W" => Wind::West,
+                "N" => Wind::North,
+                _ => return Err(invalid(format!("no such round wind {bakaze:?}"))),
+            };
+            let mut started = Kyoku {
+                round: ReplayRound {
+                    round_wind,
+                    hand: *hand,
+                    honba: *honba,
+                    names: WindSet::default(),
+                    steps: Vec::new(),
+                },
+                table: Table::new(Wind::East),
+                oya: *oya,
+                scores: by_wind(&seat_scores, *oya),
+            };
+            started.round.names = WindSet::from_fn(|seat| {
+                let actor = (seat.wind() as usize + oya) % 4;
+                names
+                    .get(actor)
+                    .cloned()
+                    .unwrap_or_else(|| format!("Player {}", actor + 1))
+            });
+            for (actor, tehai) in tehais.iter().enumerate() {
+                let seat = started.seat(actor);
+                started.table.deal(seat, &tiles(tehai)?)?;
+            }
+            let (face, red) = parse_tile(dora_marker)?;
+            started.table.dora(face, red)?;
+            started.push(Move::Deal);
+            if let Some(done) = kyoku.replace(started) {
+                rounds.push(done.round);
+            }
+            continue;
+        }
+        let Some(k) = kyoku.as_mut() else {
+            continue;
+        };
+        match event {
+            Event::Tsumo { actor, pai } => {
+                let seat = k.seat(*actor);
+                let (face, red) = parse_tile(pai)?;
+                let tile = k.table.draw(seat, face, red)?;
+                k.push(Move::Draw(seat, tile));
+            }
+            Event::Reach { actor } => {
+                let seat = k.seat(*actor);
+                k.table.declare_riichi(seat);
+            }
+            Event::Dahai {
+                actor,
+                pai,
+                tsumogiri,
+            } => {
+                let seat = k.seat(*actor);
+                let riichi = k.table.declared == Some(seat);
+                let (face, red) = parse_tile(pai)?;
+                let discard = k.table.discard(seat, face, red, *tsumogiri)?;
+                k.push(Move::Discard { discard, riichi });
+            }
+            Event::ReachAccepted {
+                scores: Some(scores),
+                ..
+            } => {
+                k.scores = by_wind(scores, k.oya);
+            }
+            Event::Chi {
+                actor,
+                pai,
+                consumed,
+                ..
+            }
+            | Event::Pon {
+                actor,
+                pai,
+                consumed,
+                ..
+            } => {
+                let seat = k.seat(*actor);
+                let (face, _) = parse_tile(pai)?;
+                let consumed = tiles(consumed)?;
+                let call = if matches!(event, Event::Chi { .. }) {
+                    k.table.chi(seat, face, &consumed)?;
+                    Call::Chi
+                } else {
+                    k.table.pon(seat, face, &consumed)?;
+                    Call::Pon
+                };
+                k.push(Move::Call { player: seat, call });
+            }
+            Event::Daiminkan { actor, pai, .. } => {
+                let seat = k.seat(*actor);
+                let (face, _) = parse_tile(pai)?;
+                k.table.open_kan(seat, face)?;
+                k.push(Move::Call {
+                    player: seat,
+                    call: Call::Kan,
+                });
+            }
+            Event::Ankan { actor, consumed } => {
+                let seat = k.seat(*actor);
+                let Some(pai) = consumed.first() else {
+                    return Err(invalid("a kan of no tiles"));
+                };
+                let (face, _) = parse_tile(pai)?;
+                k.table.closed_kan(seat, face)?;
+                k.push(Move::Call {
+                    player: seat,
+                    call: Call::Kan,
+                });
+            }
+            Event::Kakan { actor, pai, .. } => {
+                let seat = k.seat(*actor);
+                let (face, red) = parse_tile(pai)?;
+                k.table.added_kan(seat, face, red)?;
+                k.push(Move::Call {
+                    player: seat,
+                    call: Call::Kan,
+                });
+            }
+            Event::Dora { dora_marker } => {
+                let (face, red) = parse_tile(dora_marker)?;
+                let tile = k.table.dora(face, red)?;
+                k.push(Move::Dora(tile));
+            }
+            Event::Hora {
+                actor,
+                target,
+                deltas,
+                scores,
+            } => {
+                let player = k.seat(*actor);
+                let from = (actor != target).then(|| k.seat(*target));
+                let payments = by_wind(deltas.as_deref().unwrap_or_default(), k.oya);
+                k.scores = match scores {
+                    Some(scores) => by_wind(scores, k.oya),
+                    None => add(k.scores, payments),
+                };
+                k.table.to_discard = None;
+                k.push(Move::Win {
+                    player,
+                    from,
+                    payments,
+                });
+            }
+            Event::Ryukyoku {
+                tenpais,
+                deltas,
+                scores,
+            } => {
+                let payments = by_wind(deltas.as_deref().unwrap_or_default(), k.oya);
+                let tenpai = (0..4)
+                    .filter(|actor| {
+                        tenpais
+                            .as_ref()
+                            .is_some_and(|t| t.get(*actor) == Some(&true))
+                    })
+                    .map(|actor| k.seat(actor))
+                    .collect();
+                k.scores = match scores {
+                    Some(scores) => by_wind(scores, k.oya),
+                    None => add(k.scores, payments),
+                };
+                k.table.to_discard = None;
+                k.push(Move::DrawGame { tenpai, payments });
+            }
+            Event::EndKyoku => {
+                // the next kyoku starts from the scores this one ended with
+                let scores = k.scores;
+                seat_scores = (0..4).map(|actor| *scores.get(k.seat(actor))).collect();
+            }
+            _ => {}
+        }
+    }
+    rounds.extend(kyoku.map(|k| k.round));
+    if rounds.is_empty() {
+        return Err(invalid("no rounds in the log"));
+    }
+    Ok(Replay { rounds })
+}
+
+fn add(mut scores: WindSet<i32>, payments: WindSet<i32>) -> WindSet<i32> {
+    for (seat, paid) in payments.iter() {
+        *scores.get_mut(seat) += paid;
+    }
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::{is_red, Viewer};
+
+    const LOG: &str = r#"
+{"type":"start_game","names":["A","B","C","D"]}
+{"type":"start_kyoku","bakaze":"E","kyoku":2,"honba":1,"kyotaku":0,"oya":1,"dora_marker":"5sr","scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","W"],["1m","1m","2p","3p","5mr","6m","7m","8p","9p","N","N","P","P"],["2m","3m","4m","2p","3p","7p","2s","3s","4s","F","F","C","C"],["9m","9m","9p","9p","1s","1s","1s","5s","6s","7s","W","N","S"]]}
+{"type":"tsumo","actor":1,"pai":"4p"}
+{"type":"dahai","actor":1,"pai":"4p","tsumogiri":true}
+{"type":"pon","actor":3,"target":1,"pai":"4p","consumed":["4p","4p"]}
+{"type":"dahai","actor":3,"pai":"S","tsumogiri":false}
+{"type":"tsumo","actor":0,"pai":"E"}
+{"type":"reach","actor":0}
+{"type":"dahai","actor":0,"pai":"W","tsumogiri":false}
+{"type":"reach_accepted","actor":0,"scores":[24000,25000,25000,25000]}
+{"type":"ryukyoku","reason":"fanpai","tenpais":[true,false,false,false],"deltas":[3000,-1000,-1000,-1000]}
+{"type":"end_kyoku"}
+{"type":"end_game"}
+"#;
+
+    #[test]
+    fn test_import() {
+        // a pon of a tile nobody holds is refused
+        assert!(Replay::parse(LOG).is_err());
+        let log = LOG.replace(r#""9m","9m","9p","9p","1s""#, r#""9m","9m","4p","4p","1s""#);
+        let mut viewer = Viewer::new(Replay::parse(&log).unwrap());
+        let round = viewer.round();
+        assert_eq!(round.title(), "East 2, 1 honba");
+        // seat 1 deals, so seat 0 sits north
+        assert_eq!(round.names.get(Player::EAST), "B");
+        assert_eq!(round.names.get(Player::NORTH), "A");
+        assert!(is_red(
+            round.steps[0].sights.get(Player::NORTH).dora_indicators[0]
+        ));
+        let dealt = round.steps[0].sights.get(Player::EAST);
+        assert!(dealt.self_deck.hand.tiles.iter().any(is_red));
+
+        viewer.step_by(3);
+        assert!(matches!(
+            viewer.step().play,
+            Move::Call {
+                player: Player::WEST,
+                call: Call::Pon
+            }
+        ));
+        assert_eq!(
+            viewer
+                .step()
+                .sights
+                .get(Player::WEST)
+                .self_deck
+                .melded
+                .peng
+                .len(),
+            1
+        );
+
+        viewer.last();
+        let step = viewer.step();
+        assert!(matches!(&step.play, Move::DrawGame { tenpai, .. } if tenpai == &[Player::NORTH]));
+        assert_eq!(*step.scores.get(Player::NORTH), 27000);
+        assert!(step
+            .sights
+            .get(Player::NORTH)
+            .riichi
+            .get(Player::NORTH)
+            .is_some());
+        // north holds 123m 456p 789s EEE S and waits on S
+        let analysis = viewer.analysis(Player::NORTH);
+        assert_eq!(analysis.shanten, 0);
+        assert_eq!(analysis.waits, vec![SOUTH]);
+    }
+}
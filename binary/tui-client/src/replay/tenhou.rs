@@ -0,0 +1,388 @@
+//! Tenhou's JSON logs, read by turning them into mjai events.
+//!
+//! A Tenhou log lists the draws and the discards of every seat on their own, the turns are
+//! worked out again here: a seat whose next draw is a call on the discard just made takes it,
+//! otherwise the next seat draws. Tiles are numbers, `11` to `19` for the characters, `21` for
+//! the dots, `31` for the bamboos, `41` to `47` for the honors and `51` to `53` for the red fives.
+use std::io;
+
+use serde::{de::IgnoredAny, Deserialize};
+use serde_json::Value;
+
+use super::{invalid, mjai::Event};
+
+/// A whole game as Tenhou saves it.
+#[derive(Debug, Deserialize)]
+pub struct Log {
+    #[serde(default)]
+    pub name: Vec<String>,
+    pub log: Vec<Kyoku>,
+}
+
+/// A draw or a discard: a tile, or a call, riichi or kan written out.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Tile(u8),
+    Meld(String),
+}
+
+/// The discard that is the tile just drawn.
+const TSUMOGIRI: u8 = 60;
+
+#[derive(Debug, Deserialize)]
+pub struct Kyoku(
+    /// The round and hand counted from 0, the counters and the riichi sticks.
+    Vec<u8>,
+    Vec<i32>,
+    /// Dora indicators, in the order they are flipped.
+    Vec<u8>,
+    /// Ura dora indicators, only known to the winner and not shown.
+    IgnoredAny,
+    Vec<u8>,
+    Vec<Entry>,
+    Vec<Entry>,
+    Vec<u8>,
+    Vec<Entry>,
+    Vec<Entry>,
+    Vec<u8>,
+    Vec<Entry>,
+    Vec<Entry>,
+    Vec<u8>,
+    Vec<Entry>,
+    Vec<Entry>,
+    /// How the hand ended, `"和了"` followed by the payments and the winner of every win.
+    Vec<Value>,
+);
+
+/// A call as Tenhou writes it: the letter goes before the called tile.
+struct Meld {
+    kind: char,
+    called: u8,
+    /// Where the called tile is among all of them, which tells the seat it came from.
+    at: usize,
+    others: Vec<u8>,
+}
+
+impl Meld {
+    fn parse(text: &str) -> io::Result<Self> {
+        let mut kind = None;
+        let mut tiles = Vec::new();
+        let mut at = 0;
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c.is_ascii_alphabetic() {
+                kind = Some(c);
+                at = tiles.len();
+                continue;
+            }
+            let code = chars
+                .next()
+                .and_then(|d| format!("{c}{d}").parse().ok())
+                .ok_or_else(|| invalid(format!("no such call {text:?}")))?;
+            tiles.push(code);
+        }
+        let Some(kind) = kind.filter(|_| at < tiles.len()) else {
+            return Err(invalid(format!("no such call {text:?}")));
+        };
+        let called = tiles.remove(at);
+        Ok(Meld {
+            kind,
+            called,
+            at,
+            others: tiles,
+        })
+    }
+    /// The seat the called tile came from, for the seat `caller`.
+    fn source(&self, caller: usize) -> usize {
+        match (self.kind, self.at) {
+            ('c', _) | (_, 0) => (caller + 3) % 4,
+            (_, 1) => (caller + 2) % 4,
+            _ => (caller + 1) % 4,
+        }
+    }
+}
+
+/// A tile in mjai notation.
+fn pai(code: u8) -> io::Result<String> {
+    const HONORS: [&str; 7] = ["E", "S", "W", "N", "P", "F", "C"];
+    let (kind, num) = (code / 10, code % 10);
+    let pai = match (kind, num) {
+        (1, 1..=9) => format!("{num}m"),
+        (2, 1..=9) => format!("{num}p"),
+        (3, 1..=9) => format!("{num}s"),
+        (4, 1..=7) => HONORS[num as usize - 1].to_owned(),
+        (5, 1) => "5mr".to_owned(),
+        (5, 2) => "5pr".to_owned(),
+        (5, 3) => "5sr".to_owned(),
+        _ => return Err(invalid(format!("no such tile {code}"))),
+    };
+    Ok(pai)
+}
+
+fn pais(codes: &[u8]) -> io::Result<Vec<String>> {
+    codes.iter().map(|c| pai(*c)).collect()
+}
+
+/// The same tile whether red or not.
+fn plain(code: u8) -> u8 {
+    match code {
+        51..=53 => (code - 50) * 10 + 5,
+        code => code,
+    }
+}
+
+impl Log {
+    pub fn events(&self) -> io::Result<Vec<Event>> {
+        let mut events = vec![Event::StartGame {
+            names: self.name.clone(),
+        }];
+        for kyoku in &self.log {
+            kyoku.events(&mut events)?;
+        }
+        events.push(Event::EndGame);
+        Ok(events)
+    }
+}
+
+impl Kyoku {
+    fn events(&self, events: &mut Vec<Event>) -> io::Result<()> {
+        let [round, honba, ..] = self.0[..] else {
+            return Err(invalid("a hand without its number"));
+        };
+        let oya = round as usize % 4;
+        let Some(dora) = self.2.first() else {
+            return Err(invalid("a hand without a dora indicator"));
+        };
+        let haipai = [&self.4, &self.7, &self.10, &self.13];
+        let takes = [&self.5, &self.8, &self.11, &self.14];
+        let discards = [&self.6, &self.9, &self.12, &self.15];
+        events.push(Event::StartKyoku {
+            bakaze: ["E", "S", "W", "N"][round as usize / 4 % 4].to_owned(),
+            kyoku: round % 4 + 1,
+            honba,
+            oya,
+            dora_marker: pai(*dora)?,
+            scores: Some(self.1.clone()),
+            tehais: haipai.iter().map(|h| pais(h)).collect::<io::Result<_>>()?,
+        });
+        let mut doras = self.2.iter().skip(1);
+        let mut flip = |events: &mut Vec<Event>| -> io::Result<()> {
+            if let Some(dora) = doras.next() {
+                events.push(Event::Dora {
+                    dora_marker: pai(*dora)?,
+                });
+            }
+            Ok(())
+        };
+        let mut taken = [0; 4];
+        let mut discarded = [0; 4];
+        let mut drawn = [0; 4];
+        let mut actor = oya;
+        let mut draws = true;
+        loop {
+            if draws {
+                let Some(take) = takes[actor].get(taken[actor]) else {
+                    break;
+                };
+                taken[actor] += 1;
+                let Entry::Tile(tile) = take else {
+                    return Err(invalid("a call nobody discarded for"));
+                };
+                drawn[actor] = *tile;
+                events.push(Event::Tsumo {
+                    actor,
+                    pai: pai(*tile)?,
+                });
+            }
+            draws = true;
+            let Some(discard) = discards[actor].get(discarded[actor]) else {
+                break;
+            };
+            discarded[actor] += 1;
+            let (tile, riichi) = match discard {
+                Entry::Tile(0) => return Err(invalid("a kan nobody called")),
+                Entry::Tile(tile) => (*tile, false),
+                Entry::Meld(text) => {
+                    let meld = Meld::parse(text)?;
+                    match meld.kind {
+                        'r' => (meld.called, true),
+                        'a' => {
+                            let mut consumed = meld.others.clone();
+                            consumed.push(meld.called);
+                            events.push(Event::Ankan {
+                                actor,
+                                consumed: pais(&consumed)?,
+                            });
+                            flip(events)?;
+                            continue;
+                        }
+                        'k' => {
+                            events.push(Event::Kakan {
+                                actor,
+                                pai: pai(meld.called)?,
+                                consumed: pais(&meld.others)?,
+                            });
+                            flip(events)?;
+                            continue;
+                        }
+                        _ => return Err(invalid(format!("no such discard {text:?}"))),
+                    }
+                }
+            };
+            let tsumogiri = tile == TSUMOGIRI;
+            let tile = if tsumogiri { drawn[actor] } else { tile };
+            if riichi {
+                events.push(Event::Reach { actor });
+            }
+            events.push(Event::Dahai {
+                actor,
+                pai: pai(tile)?,
+                tsumogiri,
+            });
+            if riichi {
+                events.push(Event::ReachAccepted {
+                    actor,
+                    scores: None,
+                });
+            }
+            // a pon or kan goes before a chi
+            let call = (1..4)
+                .map(|by| (actor + by) % 4)
+                .filter_map(|caller| {
+                    let Some(Entry::Meld(text)) = takes[caller].get(taken[caller]) else {
+                        return None;
+                    };
+                    let meld = Meld::parse(text).ok()?;
+                    let claims = plain(meld.called) == plain(tile) && meld.source(caller) == actor;
+                    claims.then_some((caller, meld))
+                })
+                .min_by_key(|(_, meld)| meld.kind == 'c');
+            let Some((caller, meld)) = call else {
+                actor = (actor + 1) % 4;
+                continue;
+            };
+            taken[caller] += 1;
+            let (target, pai, consumed) = (actor, pai(meld.called)?, pais(&meld.others)?);
+            actor = caller;
+            match meld.kind {
+                'c' => events.push(Event::Chi {
+                    actor,
+                    target,
+                    pai,
+                    consumed,
+                }),
+                'p' => events.push(Event::Pon {
+                    actor,
+                    target,
+                    pai,
+                    consumed,
+                }),
+                'm' => {
+                    events.push(Event::Daiminkan {
+                        actor,
+                        target,
+                        pai,
+                        consumed,
+                    });
+                    flip(events)?;
+                    // the kan stands in for a discard, the seat draws from the dead wall
+                    if let Some(Entry::Tile(0)) = discards[actor].get(discarded[actor]) {
+                        discarded[actor] += 1;
+                    }
+                    continue;
+                }
+                kind => return Err(invalid(format!("no such call {kind:?}"))),
+            }
+            draws = false;
+        }
+        self.result(events)?;
+        events.push(Event::EndKyoku);
+        Ok(())
+    }
+    fn result(&self, events: &mut Vec<Event>) -> io::Result<()> {
+        let deltas = |value: &Value| serde_json::from_value::<Vec<i32>>(value.clone()).ok();
+        let result = &self.16;
+        if result.first().and_then(Value::as_str) == Some("和了") {
+            for win in result[1..].chunks(2) {
+                let [payments, agari] = win else {
+                    return Err(invalid("a win without its winner"));
+                };
+                let seats = serde_json::from_value::<Vec<Value>>(agari.clone())
+                    .ok()
+                    .and_then(|a| Some((a.first()?.as_u64()?, a.get(1)?.as_u64()?)));
+                let Some((winner, from)) = seats else {
+                    return Err(invalid("a win without its winner"));
+                };
+                events.push(Event::Hora {
+                    actor: winner as usize,
+                    target: from as usize,
+                    deltas: deltas(payments),
+                    scores: None,
+                });
+            }
+        } else {
+            events.push(Event::Ryukyoku {
+                tenpais: None,
+                deltas: result.get(1).and_then(deltas),
+                scores: None,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::{Call, Move, Replay, Viewer};
+    use rahjong::{player::Player, tile::SOUTH};
+
+    const LOG: &str = r#"{
+        "title": ["", ""],
+        "name": ["A", "B", "C", "D"],
+        "log": [[
+            [0, 0, 0], [25000, 25000, 25000, 25000], [36], [],
+            [11, 12, 13, 24, 25, 26, 37, 38, 39, 41, 41, 42, 43], [24, 41], [60, "r43"],
+            [11, 11, 22, 23, 51, 16, 17, 28, 29, 44, 44, 45, 45], [], [],
+            [12, 13, 14, 22, 23, 27, 32, 33, 34, 46, 46, 47, 47], [], [],
+            [19, 19, 24, 24, 31, 31, 31, 35, 36, 37, 43, 44, 42], ["2424p24"], [42],
+            ["流局", [3000, -1000, -1000, -1000]]
+        ]]
+    }"#;
+
+    #[test]
+    fn test_import() {
+        let mut viewer = Viewer::new(Replay::parse(LOG).unwrap());
+        assert_eq!(viewer.rounds().len(), 1);
+        assert_eq!(viewer.round().title(), "East 1, 0 honba");
+        let plays: Vec<&Move> = viewer.round().steps.iter().map(|s| &s.play).collect();
+        assert!(matches!(
+            plays[..],
+            [
+                Move::Deal,
+                Move::Draw(Player::EAST, _),
+                Move::Discard { riichi: false, .. },
+                Move::Call {
+                    player: Player::NORTH,
+                    call: Call::Pon
+                },
+                Move::Discard { .. },
+                Move::Draw(Player::EAST, _),
+                Move::Discard { riichi: true, .. },
+                Move::DrawGame { .. },
+            ]
+        ));
+        viewer.last();
+        assert_eq!(*viewer.step().scores.get(Player::EAST), 28000);
+        let analysis = viewer.analysis(Player::EAST);
+        assert_eq!(analysis.waits, vec![SOUTH]);
+
+        let meld = Meld::parse("c275226").unwrap();
+        assert_eq!(
+            (meld.kind, meld.called, meld.others),
+            ('c', 27, vec![52, 26])
+        );
+        assert_eq!(Meld::parse("2222p22").unwrap().source(1), 2);
+    }
+}
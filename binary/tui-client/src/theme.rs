@@ -0,0 +1,291 @@
+//! How tiles are drawn: the labels and the colors, read from a config file.
+//!
+//! ```toml
+//! tiles = "ascii"          # labels, ascii, unicode or cjk
+//! theme = "colorblind"     # default or colorblind
+//!
+//! [colors]                 # any color of the theme, by name or as "#rrggbb"
+//! dots = "light blue"
+//! ```
+//!
+//! The theme is set once at the start and read by every widget.
+use std::{fs, io, path::PathBuf, str::FromStr, sync::OnceLock};
+
+use rahjong::tile::{Dragon, Honer, Suit, SuitKind, TileFace, Wind};
+use ratatui::{style::Color, text::Span};
+use serde::Deserialize;
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// The theme set at the start, the default one if none was.
+pub fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
+/// Use `theme` from now on, `false` if one was set already.
+pub fn set_theme(theme: Theme) -> bool {
+    THEME.set(theme).is_ok()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TileStyle {
+    /// `[5M]` for the suits, `[東]` for the honors.
+    #[default]
+    Labels,
+    /// MPSZ notation, `[5m]` and `[1z]` for east.
+    Ascii,
+    /// The mahjong glyphs of Unicode, `🀋`. Few fonts draw them, and not all at the same width.
+    Unicode,
+    /// `五萬`, `五索`, `五筒` and `[東]`.
+    Cjk,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Colors {
+    pub characters: Color,
+    pub dots: Color,
+    pub bamboos: Color,
+    pub winds: Color,
+    pub dragons: Color,
+    pub back: Color,
+    /// The background of red fives.
+    pub red_five: Color,
+    /// The background of dora.
+    pub dora: Color,
+}
+
+impl Colors {
+    pub fn default_colors() -> Self {
+        Colors {
+            characters: Color::Red,
+            dots: Color::Blue,
+            bamboos: Color::Green,
+            winds: Color::White,
+            dragons: Color::Magenta,
+            back: Color::Yellow,
+            red_five: Color::Red,
+            dora: Color::Yellow,
+        }
+    }
+    /// Colors told apart without red and green, from the Okabe-Ito palette.
+    pub fn colorblind() -> Self {
+        Colors {
+            characters: Color::Rgb(0xd5, 0x5e, 0x00),
+            dots: Color::Rgb(0x00, 0x72, 0xb2),
+            bamboos: Color::Rgb(0xf0, 0xe4, 0x42),
+            winds: Color::White,
+            dragons: Color::Rgb(0xcc, 0x79, 0xa7),
+            back: Color::Rgb(0xe6, 0x9f, 0x00),
+            red_five: Color::Rgb(0xd5, 0x5e, 0x00),
+            dora: Color::Rgb(0x56, 0xb4, 0xe9),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub tiles: TileStyle,
+    pub colors: Colors,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            tiles: TileStyle::Labels,
+            colors: Colors::default_colors(),
+        }
+    }
+}
+
+/// The config file as written.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    #[serde(default)]
+    tiles: TileStyle,
+    theme: Option<String>,
+    #[serde(default)]
+    colors: ColorsConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColorsConfig {
+    characters: Option<String>,
+    dots: Option<String>,
+    bamboos: Option<String>,
+    winds: Option<String>,
+    dragons: Option<String>,
+    back: Option<String>,
+    red_five: Option<String>,
+    dora: Option<String>,
+}
+
+impl Theme {
+    /// `$XDG_CONFIG_HOME/rahjong/tui.toml`, or under `~/.config` without it.
+    pub fn default_path() -> Option<PathBuf> {
+        let config = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(config) => PathBuf::from(config),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config.join("rahjong").join("tui.toml"))
+    }
+    pub fn load(path: &PathBuf) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+        let config: Config = toml::from_str(text).map_err(|e| invalid(e.to_string()))?;
+        let mut colors = match config.theme.as_deref() {
+            None | Some("default") => Colors::default_colors(),
+            Some("colorblind") => Colors::colorblind(),
+            Some(theme) => return Err(invalid(format!("no theme named {theme:?}"))),
+        };
+        let set = |color: &mut Color, name: Option<String>| -> io::Result<()> {
+            if let Some(name) = name {
+                *color = Color::from_str(&name)
+                    .map_err(|_| invalid(format!("{name:?} is not a color")))?;
+            }
+            Ok(())
+        };
+        let custom = config.colors;
+        set(&mut colors.characters, custom.characters)?;
+        set(&mut colors.dots, custom.dots)?;
+        set(&mut colors.bamboos, custom.bamboos)?;
+        set(&mut colors.winds, custom.winds)?;
+        set(&mut colors.dragons, custom.dragons)?;
+        set(&mut colors.back, custom.back)?;
+        set(&mut colors.red_five, custom.red_five)?;
+        set(&mut colors.dora, custom.dora)?;
+        Ok(Theme {
+            tiles: config.tiles,
+            colors,
+        })
+    }
+    /// How many columns a tile takes.
+    pub fn tile_width(&self) -> u16 {
+        match self.tiles {
+            TileStyle::Unicode => 2,
+            _ => 4,
+        }
+    }
+    /// The text of `face`, padded to [`Theme::tile_width`].
+    pub fn label(&self, face: TileFace) -> String {
+        let label = match (self.tiles, face.try_into_suit(), face.try_into_honer()) {
+            (TileStyle::Unicode, _, _) => face.unicode().to_string(),
+            (TileStyle::Labels, Some(suit), _) => {
+                let kind = match suit.kind {
+                    SuitKind::Character => 'M',
+                    SuitKind::Dot => 'P',
+                    SuitKind::Bamboo => 'S',
+                };
+                format!("[{}{kind}]", suit.num)
+            }
+            (TileStyle::Ascii, Some(suit), _) => {
+                let kind = match suit.kind {
+                    SuitKind::Character => 'm',
+                    SuitKind::Dot => 'p',
+                    SuitKind::Bamboo => 's',
+                };
+                format!("[{}{kind}]", suit.num)
+            }
+            (TileStyle::Ascii, None, Some(honer)) => format!("[{}z]", honer_number(honer)),
+            (TileStyle::Cjk, Some(Suit { kind, num }), _) => {
+                let kind = match kind {
+                    SuitKind::Character => '萬',
+                    SuitKind::Dot => '筒',
+                    SuitKind::Bamboo => '索',
+                };
+                let num = ["一", "二", "三", "四", "五", "六", "七", "八", "九"][num as usize - 1];
+                format!("{num}{kind}")
+            }
+            (_, None, Some(honer)) => format!("[{}]", honer_glyph(honer)),
+            (_, None, None) => "[??]".to_owned(),
+        };
+        pad(label, self.tile_width() as usize)
+    }
+    pub fn color(&self, face: TileFace) -> Color {
+        match (face.try_into_suit(), face.try_into_honer()) {
+            (Some(suit), _) => match suit.kind {
+                SuitKind::Character => self.colors.characters,
+                SuitKind::Dot => self.colors.dots,
+                SuitKind::Bamboo => self.colors.bamboos,
+            },
+            (None, Some(Honer::Wind(_))) => self.colors.winds,
+            (None, Some(Honer::Dragon(_))) => self.colors.dragons,
+            (None, None) => Color::Black,
+        }
+    }
+}
+
+/// The honors in MPSZ order: the winds, then white, green and red.
+fn honer_number(honer: Honer) -> u8 {
+    match honer {
+        Honer::Wind(wind) => wind.as_index() as u8 + 1,
+        Honer::Dragon(Dragon::White) => 5,
+        Honer::Dragon(Dragon::Green) => 6,
+        Honer::Dragon(Dragon::Red) => 7,
+    }
+}
+
+fn honer_glyph(honer: Honer) -> &'static str {
+    match honer {
+        Honer::Wind(Wind::East) => "東",
+        Honer::Wind(Wind::South) => "南",
+        Honer::Wind(Wind::West) => "西",
+        Honer::Wind(Wind::North) => "北",
+        Honer::Dragon(Dragon::Red) => "中",
+        Honer::Dragon(Dragon::Green) => "發",
+        // the white dragon is a blank tile
+        Honer::Dragon(Dragon::White) => "  ",
+    }
+}
+
+/// Pad `label` with spaces to `width` columns, as the terminal counts them.
+fn pad(mut label: String, width: usize) -> String {
+    let columns = Span::raw(label.as_str()).width();
+    label.extend(std::iter::repeat_n(' ', width.saturating_sub(columns)));
+    label
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rahjong::tile::*;
+
+    #[test]
+    fn test_labels() {
+        let mut theme = Theme::default();
+        assert_eq!(theme.label(C5), "[5M]");
+        assert_eq!(theme.label(EAST), "[東]");
+        theme.tiles = TileStyle::Ascii;
+        assert_eq!(theme.label(D9), "[9p]");
+        assert_eq!(theme.label(RED), "[7z]");
+        theme.tiles = TileStyle::Cjk;
+        assert_eq!(theme.label(B3), "三索");
+        assert_eq!(theme.label(WHITE), "[  ]");
+        theme.tiles = TileStyle::Unicode;
+        assert_eq!(theme.label(C1), "🀇 ");
+    }
+
+    #[test]
+    fn test_parse() {
+        let theme = Theme::parse(
+            r##"
+            tiles = "cjk"
+            theme = "colorblind"
+
+            [colors]
+            dots = "#102030"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(theme.tiles, TileStyle::Cjk);
+        assert_eq!(theme.colors.dots, Color::Rgb(0x10, 0x20, 0x30));
+        assert_eq!(theme.colors.dora, Colors::colorblind().dora);
+        assert!(Theme::parse("theme = \"neon\"").is_err());
+        assert!(Theme::parse("[colors]\ndots = \"not a color\"").is_err());
+        assert_eq!(Theme::parse("").unwrap(), Theme::default());
+    }
+}
@@ -0,0 +1,543 @@
+//! Drawing a [`LocalGame`], a [`RemoteGame`] or a replay on a frame.
+use std::time::Instant;
+
+use rahjong::{
+    player::Player,
+    rules::jp::round::{Action, PlayerSight},
+    tile::{TileId, Wind},
+};
+use rahjong_server::protocol::{Event, SeatInfo};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::{
+    assist::{self, Danger},
+    game::{LocalGame, RoundResult},
+    prompt::CallPrompt,
+    remote::{RemoteGame, Status},
+    replay::{Analysis, Call, Move, Viewer},
+    widget::{tile_span, HandState, TableWidget},
+};
+
+/// Draw the local game, with the discard hints at the side if `hints` is on. `hand` keeps where
+/// the tiles of the hand were drawn.
+pub fn draw(
+    frame: &mut Frame,
+    game: &LocalGame,
+    prompt: Option<&CallPrompt>,
+    hints: bool,
+    hand: &mut HandState,
+) {
+    let sight = game.sight();
+    let [table, help] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.size());
+    let table = if hints {
+        let [table, side] = hint_layout(table);
+        draw_hints(frame, side, &sight, game.seat(), game.selected());
+        table
+    } else {
+        table
+    };
+    let table_widget = TableWidget::new(&sight, game.seat())
+        .round_wind(game.round_wind())
+        .honba(game.honba())
+        .scores(game.scores())
+        .selected(game.selected());
+    frame.render_stateful_widget(table_widget, table, hand);
+
+    if let Some(prompt) = prompt {
+        let height = prompt.choices().len() as u16 + 2;
+        frame.render_widget(prompt, centered(table, 44, height));
+    }
+
+    let keys = match (game.result(), prompt) {
+        (Some(_), _) => "n: next round  q: quit",
+        (None, Some(prompt)) if prompt.can_pass() => "↑/↓: choose  Enter: call  Esc: pass  q: quit",
+        _ => "←/→ or click: select  Enter or click again: discard  h: hints  q: quit",
+    };
+    frame.render_widget(Paragraph::new(keys).dim(), help);
+
+    if let Some(result) = game.result() {
+        draw_result(frame, result, game.seat());
+    }
+}
+
+/// Cover the table while the keyboard is passed to `next`, nothing of the last hand shows.
+pub fn draw_handover(frame: &mut Frame, next: Player) {
+    let [main, help] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.size());
+    let lines = vec![
+        Line::from(format!("Pass the keyboard to {:?}", next.wind())).bold(),
+        Line::default(),
+        Line::from("Press Enter when only they can see the screen"),
+    ];
+    let area = centered(main, 50, lines.len() as u16 + 2);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL)),
+        area,
+    );
+    frame.render_widget(Paragraph::new("Enter: take over  q: quit").dim(), help);
+}
+
+/// Draw the lobby, the room or the table, whichever the client is at. `code` is the room code
+/// being typed in the lobby, the discard hints are at the side of the table if `hints` is on.
+pub fn draw_remote(
+    frame: &mut Frame,
+    game: &RemoteGame,
+    prompt: Option<&CallPrompt>,
+    code: Option<&str>,
+    hints: bool,
+    hand: &mut HandState,
+) {
+    let [main, status, help] = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.size());
+    let keys = match (game.seat().zip(game.sight()), game.room()) {
+        (Some((seat, sight)), _) => {
+            let main = if hints {
+                let [table, side] = hint_layout(main);
+                draw_hints(frame, side, sight, seat, game.selected());
+                table
+            } else {
+                main
+            };
+            let table_widget = TableWidget::new(sight, seat)
+                .round_wind(Wind::East)
+                .scores(game.scores())
+                .selected(game.selected());
+            frame.render_stateful_widget(table_widget, main, hand);
+            if let Some(prompt) = prompt {
+                let height = prompt.choices().len() as u16 + 2;
+                frame.render_widget(prompt, centered(main, 44, height));
+            }
+            if let Some(result) = game.result() {
+                draw_result(frame, result, seat);
+            }
+            match (game.result(), prompt) {
+                (Some(_), _) => "n: back to the room  q: quit",
+                (None, Some(prompt)) if prompt.can_pass() => {
+                    "↑/↓: choose  Enter: call  Esc: pass  q: quit"
+                }
+                _ => "←/→ or click: select  Enter or click again: discard  h: hints  q: quit",
+            }
+        }
+        (None, Some(room)) => {
+            let mut lines = vec![
+                Line::from(format!("Room {}", room.code.0)).bold(),
+                Line::from(format!(
+                    "{} members, {} spectators",
+                    room.members, room.spectators
+                )),
+                Line::default(),
+            ];
+            for (i, (seat, info)) in room.seats.iter().enumerate() {
+                let info = match info {
+                    SeatInfo::Empty => "empty".to_owned(),
+                    SeatInfo::Human { name, ready: true } => format!("{name} (ready)"),
+                    SeatInfo::Human { name, ready: false } => name.clone(),
+                    SeatInfo::Bot => "bot".to_owned(),
+                };
+                lines.push(Line::from(format!(
+                    "{} {:<6} {info}",
+                    i + 1,
+                    format!("{:?}", seat.wind())
+                )));
+            }
+            let block = Block::default().borders(Borders::ALL).title("Room");
+            frame.render_widget(Paragraph::new(lines).block(block), main);
+            "1-4: take a seat  Space: ready  l: leave  q: quit"
+        }
+        (None, None) => {
+            let items: Vec<ListItem> = game
+                .rooms()
+                .iter()
+                .map(|room| {
+                    let seated = room
+                        .seats
+                        .iter()
+                        .filter(|(_, s)| **s != SeatInfo::Empty)
+                        .count();
+                    let started = if room.started { "playing" } else { "waiting" };
+                    ListItem::new(format!("{:<8} {seated}/4 {started}", room.code.0))
+                })
+                .collect();
+            let mut title = "Rooms".to_owned();
+            if let Some(waiting) = game.queued() {
+                title = format!("Rooms, queued with {waiting} waiting");
+            }
+            let list = List::new(items)
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .highlight_symbol("> ");
+            let mut state = ListState::default().with_selected(Some(game.room_cursor()));
+            frame.render_stateful_widget(list, main, &mut state);
+            if let Some(code) = code {
+                let area = centered(main, 30, 3);
+                let block = Block::default().borders(Borders::ALL).title("Room code");
+                frame.render_widget(Clear, area);
+                frame.render_widget(Paragraph::new(format!("{code}_")).block(block), area);
+                "Enter: join  Esc: cancel"
+            } else {
+                "↑/↓: choose  Enter: join  j: join by code  c: create  u: queue  r: refresh  q: quit"
+            }
+        }
+    };
+    frame.render_widget(status_line(game), status);
+    frame.render_widget(Paragraph::new(keys).dim(), help);
+}
+
+/// Draw the step of the replay, `picker` is the round under the cursor while picking one.
+pub fn draw_replay(frame: &mut Frame, viewer: &Viewer, picker: Option<usize>) {
+    let round = viewer.round();
+    let step = viewer.step();
+    let seats: Vec<Player> = if viewer.reveal() {
+        step.sights.iter().map(|(seat, _)| seat).collect()
+    } else {
+        vec![viewer.seat()]
+    };
+    let [header, table, analysis, help] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(seats.len() as u16),
+        Constraint::Length(1),
+    ])
+    .areas(frame.size());
+
+    let mut line = Line::from(vec![
+        Span::raw(round.title()).bold(),
+        Span::raw(format!(
+            "  step {}/{}  ",
+            viewer.step_index() + 1,
+            round.steps.len()
+        )),
+    ]);
+    line.spans.extend(move_spans(&step.play, viewer.seat()));
+    frame.render_widget(line, header);
+
+    let mut table_widget = TableWidget::new(viewer.sight(), viewer.seat())
+        .round_wind(round.round_wind)
+        .honba(round.honba)
+        .scores(&step.scores);
+    if viewer.reveal() {
+        table_widget = table_widget.revealed(&step.sights);
+    }
+    frame.render_widget(table_widget, table);
+
+    let lines: Vec<Line> = seats
+        .iter()
+        .map(|seat| {
+            let name = round.names.get(*seat);
+            let mut line = Line::from(format!("{:<6} {name:<12} ", format!("{:?}", seat.wind())));
+            line.spans.extend(analysis_spans(&viewer.analysis(*seat)));
+            line
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), analysis);
+
+    let keys = if picker.is_some() {
+        "↑/↓: choose  Enter: go to the round  Esc: back"
+    } else {
+        "←/→: step  Home/End: first/last  PgUp/PgDn: round  g: pick a round  s: seat  v: reveal  q: quit"
+    };
+    frame.render_widget(Paragraph::new(keys).dim(), help);
+
+    if let Some(cursor) = picker {
+        let items: Vec<ListItem> = (viewer.rounds().iter())
+            .map(|r| ListItem::new(r.title()))
+            .collect();
+        let height = items.len() as u16 + 2;
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Rounds"))
+            .highlight_symbol("> ");
+        let area = centered(frame.size(), 30, height);
+        let mut state = ListState::default().with_selected(Some(cursor));
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+}
+
+fn analysis_spans(analysis: &Analysis) -> Vec<Span<'static>> {
+    match analysis.shanten {
+        -1 => vec![Span::raw("complete").green()],
+        0 => {
+            let mut spans = vec![Span::raw("tenpai, waits ").yellow()];
+            spans.extend(analysis.waits.iter().map(|face| tile_span(*face)));
+            spans
+        }
+        shanten => vec![Span::raw(format!("{shanten}-shanten"))],
+    }
+}
+
+fn move_spans(play: &Move, me: Player) -> Vec<Span<'static>> {
+    let name = |seat: Player| seat_name(seat, me);
+    match play {
+        Move::Deal => vec![Span::raw("Dealt")],
+        Move::Draw(player, tile) => vec![
+            Span::raw(format!("{} drew ", name(*player))),
+            tile_span(tile.face()),
+        ],
+        Move::Discard { discard, riichi } => {
+            let verb = if *riichi {
+                "declared riichi with"
+            } else {
+                "discarded"
+            };
+            vec![
+                Span::raw(format!("{} {verb} ", name(discard.source()))),
+                tile_span(discard.tile().face()),
+            ]
+        }
+        Move::Call { player, call } => {
+            let call = match call {
+                Call::Chi => "chi",
+                Call::Pon => "pon",
+                Call::Kan => "kan",
+                Call::Nukidora => "nukidora",
+            };
+            vec![Span::raw(format!("{} called {call}", name(*player)))]
+        }
+        Move::Dora(tile) => vec![Span::raw("New dora indicator "), tile_span(tile.face())],
+        Move::Win { player, from, .. } => {
+            let how = match from {
+                Some(from) => format!("ron off {}", name(*from)),
+                None => "tsumo".to_owned(),
+            };
+            vec![Span::raw(format!("{} won by {how}", name(*player)))]
+        }
+        Move::DrawGame { .. } => vec![Span::raw("Exhaustive draw")],
+        Move::Resigned(player) => vec![Span::raw(format!("{} resigned", name(*player)))],
+        Move::Abandoned => vec![Span::raw("Abandoned")],
+    }
+}
+
+fn hint_layout(area: Rect) -> [Rect; 2] {
+    Layout::horizontal([Constraint::Min(0), Constraint::Length(36)]).areas(area)
+}
+
+/// What every discard of `seat` leaves, the waits of the `selected` one are listed below.
+fn draw_hints(
+    frame: &mut Frame,
+    area: Rect,
+    sight: &PlayerSight,
+    seat: Player,
+    selected: Option<TileId>,
+) {
+    let block = Block::default().borders(Borders::ALL).title("Hints");
+    let hints = assist::hints(sight, seat);
+    if hints.is_empty() {
+        let idle = Paragraph::new("Hints show on your turn").dim();
+        frame.render_widget(idle.block(block), area);
+        return;
+    }
+    let mut lines = vec![Line::from("  Tile Shanten Tiles Danger").dim()];
+    for hint in &hints {
+        let chosen = Some(hint.tile) == selected;
+        let mut line = Line::from(vec![
+            Span::raw(if chosen { "> " } else { "  " }),
+            tile_span(hint.tile.face()),
+            Span::raw(format!(" {:>7} {:>5} ", hint.shanten, hint.ukeire)),
+        ]);
+        for (riichi, danger) in &hint.danger {
+            let wind = format!("{:?}", riichi.wind());
+            let danger = match danger {
+                Danger::Safe => Span::raw("safe").green(),
+                Danger::Low => Span::raw("low").cyan(),
+                Danger::Medium => Span::raw("mid").yellow(),
+                Danger::High => Span::raw("high").red(),
+            };
+            line.spans.extend([
+                Span::raw(format!("{} ", &wind[..1])),
+                danger,
+                Span::raw(" "),
+            ]);
+        }
+        if chosen {
+            line = line.patch_style(Style::default().add_modifier(Modifier::BOLD));
+        }
+        lines.push(line);
+    }
+    if let Some(hint) = hints.iter().find(|h| Some(h.tile) == selected) {
+        let label = if hint.shanten == 0 { "Waits" } else { "Takes" };
+        lines.push(Line::default());
+        let mut waits = vec![Span::raw(format!("{label} "))];
+        waits.extend(
+            (hint.waits.iter()).map(|face| tile_span(*face).add_modifier(Modifier::REVERSED)),
+        );
+        lines.push(Line::from(waits));
+    }
+    let hints = Paragraph::new(lines).wrap(Wrap { trim: false });
+    frame.render_widget(hints.block(block), area);
+}
+
+fn status_line(game: &RemoteGame) -> Line<'static> {
+    let status = match game.status() {
+        Status::Connecting => Span::raw("Connecting").yellow(),
+        Status::Online => Span::raw("Online").green(),
+        Status::Offline { reason, retry_at } => {
+            let left = retry_at.saturating_duration_since(Instant::now());
+            let retry = format!("Offline ({reason}), retrying in {}s", left.as_secs());
+            Span::raw(retry).red()
+        }
+    };
+    let mut line = Line::from(vec![status, Span::raw("  ")]);
+    if let Some(error) = game.error() {
+        line.spans.push(Span::raw(error.to_owned()).red());
+    } else if let Some(event) = game.log().last() {
+        let me = game.seat().unwrap_or(Player::EAST);
+        line.spans.extend(event_spans(event, me));
+    }
+    line
+}
+
+fn event_spans(event: &Event, me: Player) -> Vec<Span<'static>> {
+    let name = |seat: Player| seat_name(seat, me);
+    match event {
+        Event::Started { dealer } => vec![Span::raw(format!("Started, {} deals", name(*dealer)))],
+        Event::Draw { player, tile } => {
+            let mut spans = vec![Span::raw(format!("{} drew ", name(*player)))];
+            spans.extend(tile.map(|t| tile_span(t.face())));
+            spans
+        }
+        Event::Discard(discard) => vec![
+            Span::raw(format!("{} discarded ", name(discard.source()))),
+            tile_span(discard.tile().face()),
+        ],
+        Event::Call { player, action } => {
+            let call = match action {
+                Action::Chi(_) => "chi",
+                Action::Pon(_) => "pon",
+                Action::Kan(_) => "kan",
+                Action::Richii(_) => "riichi",
+                Action::Nukidora(_) => "nukidora",
+                _ => "a call",
+            };
+            vec![Span::raw(format!("{} called {call}", name(*player)))]
+        }
+        Event::Win { player, .. } => vec![Span::raw(format!("{} won", name(*player)))],
+        Event::DrawGame { .. } => vec![Span::raw("Exhaustive draw")],
+        Event::Resign(player) => vec![Span::raw(format!("{} resigned", name(*player)))],
+    }
+}
+
+fn draw_result(frame: &mut Frame, result: &RoundResult, me: Player) {
+    let mut lines = Vec::new();
+    for agari in &result.wins {
+        let name = seat_name(agari.player, me);
+        let how = match agari.from {
+            Some(from) => format!("{name} ron from {}", seat_name(from, me)),
+            None => format!("{name} tsumo"),
+        };
+        let score = &agari.score;
+        lines.push(Line::from(how).bold());
+        lines.push(Line::from(format!("{} han {} fu", score.han, score.fu)));
+        for yaku in &score.yaku {
+            lines.push(Line::from(format!("  {yaku:?}")));
+        }
+        if score.dora > 0 {
+            lines.push(Line::from(format!("  Dora {}", score.dora)));
+        }
+        lines.push(Line::default());
+    }
+    if result.wins.is_empty() {
+        lines.push(Line::from("Exhaustive draw").bold());
+        lines.push(Line::default());
+    }
+    for (seat, paid) in result.payments.iter() {
+        let ready = match (result.wins.is_empty(), result.tenpai.contains(&seat)) {
+            (false, _) => "",
+            (true, true) => "tenpai",
+            (true, false) => "noten",
+        };
+        let name = seat_name(seat, me);
+        lines.push(Line::from(format!("{name:<6} {ready:<7} {paid:+}")));
+    }
+    let area = centered(frame.size(), 30, lines.len() as u16 + 2);
+    let block = Block::default().borders(Borders::ALL).title("Result");
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn seat_name(seat: Player, me: Player) -> String {
+    if seat == me {
+        "You".to_owned()
+    } else {
+        format!("{:?}", seat.wind())
+    }
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rahjong::bot::Tsumogiri;
+    use ratatui::backend::TestBackend;
+
+    #[test]
+    fn test_draw() {
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        let mut game = LocalGame::new(Player::EAST).with_bots(|_| Box::new(Tsumogiri));
+        let mut hand = HandState::default();
+        let screen = |terminal: &Terminal<TestBackend>| -> String {
+            let buffer = terminal.backend().buffer();
+            buffer.content().iter().map(|cell| cell.symbol()).collect()
+        };
+        // the dealer discards first, with a hint for every tile
+        terminal
+            .draw(|frame| draw(frame, &game, None, true, &mut hand))
+            .unwrap();
+        assert!(screen(&terminal).contains("Shanten"));
+        // the cursor starts on the drawn tile, the last one shown
+        assert_eq!(hand.selected(), game.selected());
+        while game.result().is_none() {
+            while game.step() {}
+            if game.discard().is_err() {
+                let _ = game.act(Action::Pass);
+            }
+        }
+        terminal
+            .draw(|frame| draw(frame, &game, None, false, &mut hand))
+            .unwrap();
+        assert!(screen(&terminal).contains("Exhaustive draw"));
+    }
+
+    #[test]
+    fn test_draw_lobby() {
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        let game = RemoteGame::default();
+        terminal
+            .draw(|frame| {
+                draw_remote(
+                    frame,
+                    &game,
+                    None,
+                    Some("ab12"),
+                    false,
+                    &mut HandState::default(),
+                )
+            })
+            .unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("Rooms"));
+        assert!(screen.contains("ab12_"));
+        assert!(screen.contains("Connecting"));
+    }
+}
@@ -1,19 +1,121 @@
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
 use rahjong::{
+    discard::Discard,
     draw::Draw,
-    hand::Hand,
-    tile::{Dragon, Honer, Suit, SuitKind, TileFace, TileId, Wind},
+    hand::{Gang, Hand, Melded},
+    player::{Player, WindSet},
+    rules::jp::round::PlayerSight,
+    tile::{Suit, SuitKind, TileFace, TileId, Wind},
 };
 use ratatui::{
-    prelude::Text,
+    layout::Position,
+    prelude::*,
     style::{Color, Modifier, Style, Stylize},
     text::Span,
-    widgets::StatefulWidget,
+    widgets::{Block, Borders, Paragraph, StatefulWidget, Widget},
 };
+
+use crate::theme::theme;
+
+/// The label of a tile in the style and the colors of the [`theme`].
+pub fn tile_span(face: TileFace) -> Span<'static> {
+    let theme = theme();
+    let span = Span::styled(theme.label(face), Style::default().fg(theme.color(face)));
+    if face.try_into_suit().is_none() && face.try_into_honer().is_none() {
+        return span.black().on_white();
+    }
+    span
+}
+
+/// The order a hand is shown in: characters, dots, bamboos, then winds and dragons.
+fn suit_order(tile: &TileId) -> (u8, u8, u8) {
+    let face = tile.face();
+    let rank = match face.try_into_suit() {
+        Some(Suit {
+            kind: SuitKind::Character,
+            ..
+        }) => 0,
+        Some(Suit {
+            kind: SuitKind::Dot,
+            ..
+        }) => 1,
+        Some(Suit {
+            kind: SuitKind::Bamboo,
+            ..
+        }) => 2,
+        None => 3,
+    };
+    (rank, face.into_inner(), tile.into_inner())
+}
+
+/// The tiles of `hand` in the order they are shown, the drawn tile last.
+pub fn hand_order(hand: &Hand, draw: Option<Draw>) -> Vec<TileId> {
+    let mut tiles: Vec<TileId> = hand.tiles.iter().collect();
+    tiles.sort_by_key(suit_order);
+    tiles.extend(draw.map(|d| d.tile()));
+    tiles
+}
+
+/// The cursor of a [`JpHandWidget`] and where its tiles were drawn, to find the tile under the
+/// mouse.
+#[derive(Debug, Default, Clone)]
+pub struct HandState {
+    /// `None` until a tile is selected.
+    cursor: Option<usize>,
+    /// The tiles drawn at the last render in order, with their cells.
+    tiles: Vec<(TileId, Rect)>,
+}
+
+impl HandState {
+    pub fn cursor(&self) -> Option<usize> {
+        self.cursor
+    }
+    /// The tile under the cursor.
+    pub fn selected(&self) -> Option<TileId> {
+        self.tiles.get(self.cursor?).map(|(tile, _)| *tile)
+    }
+    /// Move the cursor `by` tiles, wrapping around the ends. Without a cursor it starts from the
+    /// drawn tile.
+    pub fn move_cursor(&mut self, by: isize) {
+        let len = self.tiles.len() as isize;
+        if len > 0 {
+            let cursor = self.cursor.map_or(len - 1, |c| c as isize + by);
+            self.cursor = Some(cursor.rem_euclid(len) as usize);
+        }
+    }
+    /// Put the cursor on `tile`, `false` if it wasn't shown.
+    pub fn select(&mut self, tile: TileId) -> bool {
+        let cursor = self.tiles.iter().position(|(t, _)| *t == tile);
+        self.cursor = cursor.or(self.cursor);
+        cursor.is_some()
+    }
+    pub fn tile_at(&self, column: u16, row: u16) -> Option<TileId> {
+        let position = Position { x: column, y: row };
+        self.tiles
+            .iter()
+            .find(|(_, area)| area.contains(position))
+            .map(|(tile, _)| *tile)
+    }
+    /// Select the tile under a left click, and return it.
+    pub fn click(&mut self, mouse: MouseEvent) -> Option<TileId> {
+        if mouse.kind != MouseEventKind::Down(MouseButton::Left) {
+            return None;
+        }
+        let tile = self.tile_at(mouse.column, mouse.row)?;
+        self.select(tile);
+        Some(tile)
+    }
+}
+
+/// A hand in a row sorted by suit, the drawn tile apart and the open melds to the right. Red
+/// fives and the dora are shown on the background colors of the [`theme`].
 pub struct JpHandWidget<'a> {
     hand: &'a Hand,
     selected: Option<TileId>,
     draw: Option<Draw>,
+    /// The dora indicators, not the dora.
     doras: Vec<TileId>,
+    melded: Option<(&'a Melded, Player)>,
 }
 
 impl<'a> JpHandWidget<'a> {
@@ -28,90 +130,408 @@ impl<'a> JpHandWidget<'a> {
             doras,
             selected,
             draw,
+            melded: None,
         }
     }
+    /// The open melds of `seat`, drawn after the hand.
+    pub fn melded(mut self, melded: &'a Melded, seat: Player) -> Self {
+        self.melded = Some((melded, seat));
+        self
+    }
+    fn is_dora(&self, face: TileFace) -> bool {
+        self.doras.iter().any(|t| t.face().successor() == face)
+    }
 }
 
 impl<'a> StatefulWidget for JpHandWidget<'a> {
-    type State = &'a Hand;
+    type State = HandState;
+
+    /// The `selected` tile of the widget moves the cursor, otherwise it stays where the state
+    /// has it.
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let tiles = hand_order(self.hand, self.draw);
+        let drawn = self.draw.map(|d| d.tile());
+        state.tiles.clear();
+        let width = theme().tile_width();
+        let mut x = area.x;
+        for tile in tiles {
+            if Some(tile) == drawn {
+                x += 2;
+            }
+            let cell = Rect::new(x, area.y, width, 1).intersection(area);
+            state.tiles.push((tile, cell));
+            x += width;
+        }
+        if let Some(selected) = self.selected {
+            state.select(selected);
+        }
+        let len = state.tiles.len();
+        state.cursor = state.cursor.map(|c| c.min(len.saturating_sub(1)));
+        for (i, (tile, cell)) in state.tiles.iter().enumerate() {
+            let mut code = tile_span(tile.face());
+            let colors = theme().colors;
+            if tile.is_red_dora() {
+                code = code.white().bg(colors.red_five);
+            } else if self.is_dora(tile.face()) {
+                code = code.black().bg(colors.dora);
+            }
+            if Some(*tile) == drawn {
+                code = code.add_modifier(Modifier::BOLD);
+            }
+            if state.cursor == Some(i) {
+                code = code.add_modifier(Modifier::UNDERLINED);
+            }
+            buf.set_span(cell.x, cell.y, &code, cell.width);
+        }
+        if let Some((melded, seat)) = self.melded {
+            x += 2;
+            if x < area.right() {
+                let melds = Rect::new(x, area.y, area.right() - x, 1);
+                Line::from(meld_spans(melded, seat)).render(melds, buf);
+            }
+        }
+    }
+}
 
-    fn render(
-        self,
-        area: ratatui::prelude::Rect,
-        buf: &mut ratatui::prelude::Buffer,
-        state: &mut Self::State,
-    ) {
-        todo!()
+impl<'a> Widget for JpHandWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        StatefulWidget::render(self, area, buf, &mut HandState::default());
     }
 }
 
-impl<'a> ratatui::widgets::Widget for JpHandWidget<'a> {
-    fn render(self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer)
-    where
-        Self: Sized,
-    {
-        let hand = self.hand;
-        let mut x = area.x;
-        let y = area.y;
-        let span = |face: TileFace| -> Span {
-            if let Some(honer) = face.try_into_honer() {
-                match honer {
-                    Honer::Wind(Wind::East) => {
-                        Span::styled("[東]", Style::default().fg(Color::White))
-                    }
-                    Honer::Wind(Wind::South) => {
-                        Span::styled("[南]", Style::default().fg(Color::White))
-                    }
-                    Honer::Wind(Wind::West) => {
-                        Span::styled("[西]", Style::default().fg(Color::White))
-                    }
-                    Honer::Wind(Wind::North) => {
-                        Span::styled("[北]", Style::default().fg(Color::White))
-                    }
-                    Honer::Dragon(Dragon::Red) => {
-                        Span::styled("[中]", Style::default().fg(Color::Magenta))
-                    }
-                    Honer::Dragon(Dragon::Green) => {
-                        Span::styled("[發]", Style::default().fg(Color::Magenta))
-                    }
-                    Honer::Dragon(Dragon::White) => {
-                        Span::styled("[  ]", Style::default().fg(Color::Magenta))
-                    }
-                }
-            } else if let Some(suit) = face.try_into_suit() {
-                match suit {
-                    Suit {
-                        kind: SuitKind::Bamboo,
-                        num,
-                    } => Span::styled(format!("[{}S]", num), Style::default().fg(Color::Green)),
-                    Suit {
-                        kind: SuitKind::Character,
-                        num,
-                    } => Span::styled(format!("[{}M]", num), Style::default().fg(Color::Red)),
-                    Suit {
-                        kind: SuitKind::Dot,
-                        num,
-                    } => Span::styled(format!("[{}P]", num), Style::default().fg(Color::Blue)),
+/// Tiles in a row of a discard river.
+pub const RIVER_ROW: usize = 6;
+
+/// The back of a hidden tile.
+pub fn tile_back() -> Span<'static> {
+    Span::styled("▮", Style::default().fg(theme().colors.back))
+}
+
+/// A called tile, or the tile a riichi was declared with. It would lie sideways on a table, here
+/// it is drawn reversed.
+pub fn sideways(span: Span<'static>) -> Span<'static> {
+    span.add_modifier(Modifier::REVERSED)
+}
+
+/// How many seats to the right of `seat` the `other` seat is, on a table of four.
+pub fn seats_after(seat: Player, other: Player) -> usize {
+    (other.wind().as_index() + 4 - seat.wind().as_index()) % 4
+}
+
+/// The open melds of `seat`, the called tile lies on the side of the seat it came from.
+pub fn meld_spans(melded: &Melded, seat: Player) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let called = |claim: Discard, tiles: &[TileId]| -> Vec<Span<'static>> {
+        let mut meld: Vec<Span<'static>> = tiles.iter().map(|t| tile_span(t.face())).collect();
+        let claimed = sideways(tile_span(claim.tile().face()));
+        // left for the seat before, in the middle for the seat across, right for the seat after
+        let at = match seats_after(seat, claim.source()) {
+            3 => 0,
+            2 => 1,
+            _ => meld.len(),
+        };
+        meld.insert(at, claimed);
+        meld
+    };
+    for chi in &melded.chi {
+        spans.extend(called(chi.claim, &chi.tiles));
+        spans.push(Span::raw(" "));
+    }
+    for peng in &melded.peng {
+        spans.extend(called(peng.claim, &peng.tiles));
+        spans.push(Span::raw(" "));
+    }
+    for gang in &melded.gang {
+        let face = tile_span(gang.face());
+        match gang {
+            Gang::AnGang { .. } => {
+                spans.extend([tile_back(), face.clone(), face, tile_back()]);
+            }
+            Gang::MingGang { .. } => {
+                spans.push(sideways(face.clone()));
+                spans.extend([face.clone(), face.clone(), face]);
+            }
+            Gang::JiaGang { peng, claim } => {
+                spans.extend(called(peng.claim, &peng.tiles));
+                spans.push(sideways(tile_span(claim.face())));
+            }
+            Gang::Kong { tiles } => spans.extend(tiles.iter().map(|t| tile_span(t.face()))),
+        }
+        spans.push(Span::raw(" "));
+    }
+    spans
+}
+
+/// The discards of `seat` in rows of [`RIVER_ROW`]. Discards of the tile just drawn are dimmed
+/// and the riichi tile lies sideways.
+pub fn river_lines(sight: &PlayerSight, seat: Player) -> Vec<Line<'static>> {
+    let riichi = sight.riichi.get(seat).as_ref().map(|r| r.discard);
+    let discards: Vec<Span<'static>> = sight
+        .discards
+        .iter_tsumogiri()
+        .filter(|(d, _)| d.source() == seat)
+        .map(|(d, tsumogiri)| {
+            let mut span = tile_span(d.tile().face());
+            if tsumogiri {
+                span = span.add_modifier(Modifier::DIM);
+            }
+            if riichi == Some(*d) {
+                span = sideways(span);
+            }
+            span
+        })
+        .collect();
+    discards
+        .chunks(RIVER_ROW)
+        .map(|row| Line::from(row.to_vec()))
+        .collect()
+}
+
+/// The whole table as `seat` sees it: their hand and river at the bottom, the seat after them on
+/// the right, the one across at the top and the one before on the left, with the round in the
+/// middle.
+pub struct TableWidget<'a> {
+    sight: &'a PlayerSight,
+    seat: Player,
+    round_wind: Wind,
+    honba: u8,
+    scores: Option<&'a WindSet<i32>>,
+    selected: Option<TileId>,
+    revealed: Option<&'a WindSet<PlayerSight>>,
+}
+
+impl<'a> TableWidget<'a> {
+    pub fn new(sight: &'a PlayerSight, seat: Player) -> Self {
+        Self {
+            sight,
+            seat,
+            round_wind: Wind::East,
+            honba: 0,
+            scores: None,
+            selected: None,
+            revealed: None,
+        }
+    }
+    pub fn round_wind(mut self, round_wind: Wind) -> Self {
+        self.round_wind = round_wind;
+        self
+    }
+    pub fn honba(mut self, honba: u8) -> Self {
+        self.honba = honba;
+        self
+    }
+    pub fn scores(mut self, scores: &'a WindSet<i32>) -> Self {
+        self.scores = Some(scores);
+        self
+    }
+    /// The tile of the own hand under the cursor.
+    pub fn selected(mut self, selected: Option<TileId>) -> Self {
+        self.selected = selected;
+        self
+    }
+
+    /// Show the hands of the other seats too, from what each of them sees.
+    pub fn revealed(mut self, sights: &'a WindSet<PlayerSight>) -> Self {
+        self.revealed = Some(sights);
+        self
+    }
+
+    fn seat_block(&self, seat: Player) -> Block<'static> {
+        let mut title = format!("{:?}", seat.wind());
+        if seat.wind() == self.sight.dealer {
+            title.push_str(" (dealer)");
+        }
+        if let Some(scores) = self.scores {
+            title.push_str(&format!(" {}", scores.get(seat)));
+        }
+        let mut block = Block::default().borders(Borders::ALL).title(title);
+        if self.sight.to_discard == Some(seat) {
+            block = block.border_style(Style::default().fg(Color::Yellow));
+        }
+        block
+    }
+
+    fn render_other(&self, seat: Player, area: Rect, buf: &mut Buffer) {
+        let mut hand: Vec<Span<'static>> = match self.revealed.map(|s| s.get(seat)) {
+            Some(sight) => {
+                let draw = sight.draw.filter(|_| sight.to_discard == Some(seat));
+                let mut tiles: Vec<Span<'static>> = (sight.self_deck.hand.tiles.iter())
+                    .map(|t| tile_span(t.face()))
+                    .collect();
+                if let Some(draw) = draw {
+                    tiles.push(Span::raw(" "));
+                    tiles.push(tile_span(draw.tile().face()));
                 }
-            } else {
-                Span::styled("[??]", Style::default().fg(Color::Black).bg(Color::White))
+                tiles
             }
+            None => vec![tile_back(); *self.sight.other_hand_size.get(seat) as usize],
         };
-        for tile in &hand.tiles {
-            let face = tile.face();
-            let mut code = span(face);
-            if self.selected == Some(tile) {
-                code = code.add_modifier(Modifier::UNDERLINED)
-            }
-            buf.set_span(x, y, &code, 4);
-            x += 4;
+        hand.push(Span::raw(" "));
+        hand.extend(meld_spans(self.sight.other_hand_melded.get(seat), seat));
+        let mut lines = vec![Line::from(hand)];
+        lines.extend(river_lines(self.sight, seat));
+        Paragraph::new(lines)
+            .block(self.seat_block(seat))
+            .render(area, buf);
+    }
+
+    fn render_own(&self, area: Rect, buf: &mut Buffer, state: &mut HandState) {
+        let block = self.seat_block(self.seat);
+        let inner = block.inner(area);
+        block.render(area, buf);
+        let [river, hand] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+        Paragraph::new(river_lines(self.sight, self.seat)).render(river, buf);
+        let deck = &self.sight.self_deck;
+        let draw = self
+            .sight
+            .draw
+            .filter(|_| self.sight.to_discard == Some(self.seat));
+        let hand_widget = JpHandWidget::new(
+            &deck.hand,
+            self.sight.dora_indicators.clone(),
+            self.selected,
+            draw,
+        )
+        .melded(&deck.melded, self.seat);
+        StatefulWidget::render(hand_widget, hand, buf, state);
+    }
+
+    fn render_center(&self, area: Rect, buf: &mut Buffer) {
+        let mut lines = vec![
+            Line::from(format!("{:?} round", self.round_wind)).bold(),
+            Line::from(format!("{} honba", self.honba)),
+            Line::from(format!("Wall {}", self.sight.wall_rest)),
+        ];
+        let mut dora = vec![Span::raw("Dora ")];
+        dora.extend(
+            self.sight
+                .dora_indicators
+                .iter()
+                .map(|t| tile_span(t.face())),
+        );
+        lines.push(Line::from(dora));
+        let bonus: usize = self.sight.bonus.iter().map(|(_, b)| b.len()).sum();
+        if bonus > 0 {
+            lines.push(Line::from(format!("Bonus {bonus}")));
         }
-        if let Some(draw) = self.draw {
-            x += 2;
-            let face = draw.tile().face();
-            let mut code = span(face);
-            code = code.add_modifier(Modifier::BOLD);
-            buf.set_span(x, y, &code, 4);
+        Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL))
+            .render(area, buf);
+    }
+}
+
+impl<'a> Widget for TableWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        StatefulWidget::render(self, area, buf, &mut HandState::default());
+    }
+}
+
+/// The state is that of the own hand.
+impl<'a> StatefulWidget for TableWidget<'a> {
+    type State = HandState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut HandState) {
+        let [top, middle, bottom] = Layout::vertical([
+            Constraint::Length(7),
+            Constraint::Min(7),
+            Constraint::Length(8),
+        ])
+        .areas(area);
+        let river_width = RIVER_ROW as u16 * theme().tile_width() + 2;
+        let [left, center, right] = Layout::horizontal([
+            Constraint::Length(river_width),
+            Constraint::Min(0),
+            Constraint::Length(river_width),
+        ])
+        .areas(middle);
+        let after = self.seat.next_in(4);
+        let across = after.next_in(4);
+        let before = across.next_in(4);
+        self.render_other(across, top, buf);
+        self.render_other(before, left, buf);
+        self.render_other(after, right, buf);
+        self.render_center(center, buf);
+        self.render_own(bottom, buf, state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::LocalGame;
+    use crossterm::event::KeyModifiers;
+    use rahjong::{bot::Tsumogiri, rules::jp::round::Action, tile::*};
+    use ratatui::backend::TestBackend;
+
+    #[test]
+    fn test_hand_widget() {
+        let hand = Hand::from_faces([EAST, B1, C9, D5, C5]);
+        let draw = Draw::new(TileId::from_face_idx(D2, TileIndex::T1));
+        let indicator = TileId::from_face_idx(C8, TileIndex::T1);
+        let widget = JpHandWidget::new(&hand, vec![indicator], None, Some(draw));
+        let area = Rect::new(0, 0, 40, 1);
+        let mut buf = Buffer::empty(area);
+        let mut state = HandState::default();
+        StatefulWidget::render(widget, area, &mut buf, &mut state);
+
+        let row: String = (0..area.width).map(|x| buf.get(x, 0).symbol()).collect();
+        assert_eq!(row.trim_end(), "[5M][9M][5P][1S][東 ]  [2P]");
+        // the first copies of the fives are the red ones, 8m shows 9m as dora
+        assert_eq!(buf.get(1, 0).bg, Color::Red);
+        assert_eq!(buf.get(5, 0).bg, Color::Yellow);
+        assert_eq!(buf.get(13, 0).bg, Color::Reset);
+        assert_eq!(state.selected(), None);
+
+        let click = |column| MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row: 0,
+            modifiers: KeyModifiers::NONE,
+        };
+        assert_eq!(state.click(click(21)), None);
+        assert_eq!(state.click(click(23)), Some(draw.tile()));
+        assert_eq!(state.cursor(), Some(5));
+        state.move_cursor(1);
+        assert_eq!(state.selected().map(|t| t.face()), Some(C5));
+    }
+
+    #[test]
+    fn test_table_widget() {
+        let mut game = LocalGame::new(Player::EAST).with_bots(|_| Box::new(Tsumogiri));
+        for _ in 0..3 {
+            game.discard().unwrap();
+            while !game.is_my_turn() {
+                if !game.step() {
+                    game.act(Action::Pass).unwrap();
+                }
+            }
         }
+        let sight = game.sight();
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        terminal
+            .draw(|frame| {
+                let table = TableWidget::new(&sight, game.seat())
+                    .honba(2)
+                    .scores(game.scores());
+                frame.render_widget(table, frame.size());
+            })
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        let line = |y: u16| -> String {
+            (0..buffer.area.width)
+                .map(|x| buffer.get(x, y).symbol())
+                .collect()
+        };
+        let screen: Vec<String> = (0..buffer.area.height).map(line).collect();
+        assert!(screen.iter().any(|l| l.contains("2 honba")));
+        assert!(screen[0].contains("West 25000"));
+        // the seat across holds 13 tiles and discarded three drawn tiles
+        assert_eq!(screen[1].matches('▮').count(), 13);
+        let dimmed = (0..buffer.area.width)
+            .map(|x| buffer.get(x, 2))
+            .filter(|cell| cell.symbol() == "[" && cell.modifier.contains(Modifier::DIM))
+            .count();
+        assert_eq!(dimmed, 3);
     }
 }
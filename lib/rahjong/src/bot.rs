@@ -0,0 +1,430 @@
+//! Bots to fill seats and to test against.
+//!
+//! A [`Strategy`] picks one of the legal actions from what its seat sees. A [`Bot`] keeps the
+//! last sight and actions of its seat and plays the strategy through the [`Player`] trait.
+use std::future::{ready, Future};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::{
+    hand::{decompose::FaceCounts, Deck, Gang, Melded},
+    player::Player as Seat,
+    rules::jp::{
+        player::{Discard, Player, Reaction},
+        round::{Action, PlayerSight},
+    },
+    tile::{Honer, Num, Suit, SuitKind, TileFace, TileId, Wind},
+};
+
+pub trait Strategy {
+    /// One of `actions`, which is not empty.
+    fn choose(&mut self, seat: Seat, sight: &PlayerSight, actions: &[Action]) -> Action;
+}
+
+/// Any legal action, at random.
+pub struct RandomBot {
+    rng: StdRng,
+}
+
+impl RandomBot {
+    pub fn new() -> Self {
+        RandomBot {
+            rng: StdRng::from_entropy(),
+        }
+    }
+    /// The same seed makes the same choices.
+    pub fn with_seed(seed: u64) -> Self {
+        RandomBot {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Default for RandomBot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Strategy for RandomBot {
+    fn choose(&mut self, _: Seat, _: &PlayerSight, actions: &[Action]) -> Action {
+        actions.choose(&mut self.rng).expect("an action").clone()
+    }
+}
+
+/// Discards every tile it draws and never calls.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Tsumogiri;
+
+impl Strategy for Tsumogiri {
+    fn choose(&mut self, seat: Seat, sight: &PlayerSight, actions: &[Action]) -> Action {
+        let drawn = sight.draw.filter(|_| sight.to_discard == Some(seat));
+        let discard = actions.iter().find(|a| match a {
+            Action::Discard(d) => Some(d.tile()) == drawn.map(|d| d.tile()),
+            _ => false,
+        });
+        let pass = actions.iter().find(|a| matches!(a, Action::Pass));
+        (discard.or(pass))
+            .or(actions.iter().find(|a| matches!(a, Action::Discard(_))))
+            .unwrap_or(&actions[0])
+            .clone()
+    }
+}
+
+/// Wins when it can, discards for the lowest shanten and then the most tiles that lower it, and
+/// calls only when the call lowers the shanten and the open hand keeps a yaku.
+#[derive(Debug, Clone, Copy)]
+pub struct Greedy {
+    pub round_wind: Wind,
+}
+
+impl Default for Greedy {
+    fn default() -> Self {
+        Greedy {
+            round_wind: Wind::East,
+        }
+    }
+}
+
+impl Strategy for Greedy {
+    fn choose(&mut self, seat: Seat, sight: &PlayerSight, actions: &[Action]) -> Action {
+        let win = actions
+            .iter()
+            .find(|a| matches!(a, Action::Tsumo(_) | Action::Ron(_) | Action::Chankan(_)));
+        if let Some(win) = win {
+            return win.clone();
+        }
+        if let Some(nukidora) = actions.iter().find(|a| matches!(a, Action::Nukidora(_))) {
+            return nukidora.clone();
+        }
+        let seen = seen(seat, sight);
+        let mut hand: Vec<TileId> = sight.self_deck.hand.tiles.iter().collect();
+        if sight.to_discard == Some(seat) {
+            hand.extend(sight.draw.map(|d| d.tile()));
+            return self.discard(&hand, &seen, actions);
+        }
+        // a call on a discard, the hand has 3n + 1 tiles
+        let shanten = FaceCounts::new(hand.iter().copied()).shanten();
+        let best = actions
+            .iter()
+            .filter_map(|action| {
+                let (taken, claim) = match action {
+                    Action::Chi(chi) => (chi.tiles, chi.claim.tile()),
+                    Action::Pon(pon) => (pon.tiles, pon.claim.tile()),
+                    _ => return None,
+                };
+                let rest: Vec<TileId> = (hand.iter().copied())
+                    .filter(|t| !taken.contains(t))
+                    .collect();
+                let after = best_discard(&rest, &seen)?;
+                let mut meld: Vec<TileFace> = taken.iter().map(|t| t.face()).collect();
+                meld.push(claim.face());
+                let keeps = self.keeps_yaku(seat, sight, &rest, &meld);
+                (after.shanten < shanten && keeps).then_some((after, action))
+            })
+            .min_by_key(|(after, _)| (after.shanten, std::cmp::Reverse(after.ukeire)));
+        match best {
+            Some((_, action)) => action.clone(),
+            None => (actions.iter().find(|a| matches!(a, Action::Pass)))
+                .unwrap_or(&actions[0])
+                .clone(),
+        }
+    }
+}
+
+impl Greedy {
+    fn discard(&self, hand: &[TileId], seen: &FaceCounts, actions: &[Action]) -> Action {
+        let discards: Vec<&Action> = (actions.iter())
+            .filter(|a| matches!(a, Action::Discard(_) | Action::Richii(_)))
+            .collect();
+        let tile = |a: &Action| match a {
+            Action::Discard(d) => Some(d.tile()),
+            Action::Richii(r) => Some(r.discard.tile()),
+            _ => None,
+        };
+        let Some(best) = best_discard(hand, seen) else {
+            return actions[0].clone();
+        };
+        // declare riichi with the best discard if it may be declared
+        let riichi = discards
+            .iter()
+            .find(|a| matches!(a, Action::Richii(_)) && tile(a) == Some(best.tile));
+        let discard = discards.iter().find(|a| tile(a) == Some(best.tile));
+        (riichi.or(discard))
+            .map(|a| (*a).clone())
+            .unwrap_or_else(|| actions[0].clone())
+    }
+
+    /// Whether the open hand of `seat` keeps a yaku with a call of `meld` leaving `rest` in the
+    /// hand: a pon of a value honor, or nothing but simples.
+    fn keeps_yaku(
+        &self,
+        seat: Seat,
+        sight: &PlayerSight,
+        rest: &[TileId],
+        meld: &[TileFace],
+    ) -> bool {
+        let seat_wind =
+            Wind::from_index((seat.wind().as_index() + 4 - sight.dealer.as_index()) % 4);
+        let valued = |face: TileFace| match face.try_into_honer() {
+            Some(Honer::Dragon(_)) => true,
+            Some(Honer::Wind(wind)) => wind == seat_wind || wind == self.round_wind,
+            None => false,
+        };
+        let melded = &sight.self_deck.melded;
+        let mut pons = (melded.peng.iter().map(|p| p.claim.tile().face()))
+            .chain(melded.gang.iter().map(Gang::face))
+            .chain((meld.iter().all(|f| *f == meld[0])).then_some(meld[0]));
+        if pons.any(valued) {
+            return true;
+        }
+        let simple = |face: TileFace| {
+            face.try_into_suit()
+                .is_some_and(|s| s.num != Num::N1 && s.num != Num::N9)
+        };
+        (rest.iter().map(|t| t.face()))
+            .chain(meld.iter().copied())
+            .chain(meld_faces(melded))
+            .all(simple)
+    }
+}
+
+/// What a discard leaves.
+struct Choice {
+    tile: TileId,
+    shanten: i8,
+    ukeire: usize,
+}
+
+/// The discard of a `3n + 2` tile hand with the lowest shanten, then the most tiles to lower it.
+/// Ties go to honors and terminals, then to the last tile.
+fn best_discard(hand: &[TileId], seen: &FaceCounts) -> Option<Choice> {
+    let terminal = |face: TileFace| face.is_terminal() as u8;
+    hand.iter()
+        .rev()
+        .map(|tile| {
+            let mut counts = FaceCounts::new(hand.iter().copied());
+            counts.remove(tile.face());
+            let shanten = counts.shanten();
+            let ukeire = faces()
+                .filter(|face| {
+                    let mut more = counts;
+                    more.add(*face);
+                    more.shanten() < shanten
+                })
+                .map(|face| 4usize.saturating_sub(seen.get(face) as usize))
+                .sum();
+            Choice {
+                tile: *tile,
+                shanten,
+                ukeire,
+            }
+        })
+        .min_by_key(|c| {
+            let face = c.tile.face();
+            (
+                c.shanten,
+                std::cmp::Reverse(c.ukeire),
+                std::cmp::Reverse(terminal(face)),
+            )
+        })
+}
+
+fn faces() -> impl Iterator<Item = TileFace> {
+    let suits = [SuitKind::Character, SuitKind::Dot, SuitKind::Bamboo]
+        .into_iter()
+        .flat_map(|kind| Num::enumerate().map(move |num| TileFace::from_suit(Suit { kind, num })));
+    suits.chain(Honer::enumerate().map(TileFace::from_honer))
+}
+
+fn meld_faces(melded: &Melded) -> Vec<TileFace> {
+    let mut faces = Vec::new();
+    for chi in &melded.chi {
+        faces.extend(chi.tiles.iter().map(|t| t.face()));
+        faces.push(chi.claim.tile().face());
+    }
+    for peng in &melded.peng {
+        faces.extend([peng.claim.tile().face(); 3]);
+    }
+    for gang in &melded.gang {
+        faces.extend([gang.face(); 4]);
+    }
+    faces
+}
+
+/// The copies of every face `seat` can see.
+fn seen(seat: Seat, sight: &PlayerSight) -> FaceCounts {
+    let mut seen = FaceCounts::new(sight.self_deck.hand.tiles.iter());
+    let drawn = sight.draw.filter(|_| sight.to_discard == Some(seat));
+    let tiles = (drawn.map(|d| d.tile()).into_iter())
+        .chain(sight.discards.iter().map(|d| d.tile()))
+        .chain(sight.dora_indicators.iter().copied());
+    for tile in tiles {
+        seen.add(tile.face());
+    }
+    for (_, melded) in sight.other_hand_melded.iter() {
+        for face in meld_faces(melded) {
+            seen.add(face);
+        }
+    }
+    seen
+}
+
+/// A seat played by a [`Strategy`], told what it sees with [`Bot::observe`].
+pub struct Bot<S> {
+    seat: Seat,
+    strategy: S,
+    sight: Option<Box<PlayerSight>>,
+    actions: Vec<Action>,
+    deck: Deck,
+}
+
+impl<S: Strategy> Bot<S> {
+    pub fn new(seat: Seat, strategy: S) -> Self {
+        Bot {
+            seat,
+            strategy,
+            sight: None,
+            actions: Vec::new(),
+            deck: Deck::default(),
+        }
+    }
+    pub fn seat(&self) -> Seat {
+        self.seat
+    }
+    /// What the seat sees now and what it may do.
+    pub fn observe(&mut self, sight: PlayerSight, actions: Vec<Action>) {
+        self.deck = sight.self_deck.clone();
+        self.sight = Some(Box::new(sight));
+        self.actions = actions;
+    }
+    /// The action of the strategy, `None` if nothing is expected from the seat.
+    pub fn choose(&mut self) -> Option<Action> {
+        let sight = self.sight.as_ref()?;
+        if self.actions.is_empty() {
+            return None;
+        }
+        let action = self.strategy.choose(self.seat, sight, &self.actions);
+        // the choice is spent, the next one waits for the next sight
+        self.actions.clear();
+        Some(action)
+    }
+}
+
+impl<S: Strategy> Player for Bot<S> {
+    fn draw_and_discard(&mut self) -> impl Future<Output = Discard> + Send + 'static {
+        let drawn = self.sight.as_ref().and_then(|s| s.draw).map(|d| d.tile());
+        let discard = match self.choose() {
+            Some(Action::Discard(discard)) => Discard::Discard(discard.tile()),
+            Some(Action::Richii(riichi)) => Discard::Riichi(riichi.discard.tile()),
+            Some(Action::Tsumo(tsumo)) => Discard::Tsumo(tsumo.tile.tile()),
+            Some(Action::Kan(gang)) => Discard::Kan(gang),
+            Some(Action::Ryukyoku) => Discard::Draw,
+            _ => match drawn {
+                Some(tile) => Discard::Discard(tile),
+                None => Discard::Draw,
+            },
+        };
+        ready(discard)
+    }
+    fn reaction(&mut self, discard: TileId) -> impl Future<Output = Reaction> + Send + 'static {
+        let reaction = match self.choose() {
+            Some(Action::Chi(chi)) => Reaction::Chi(chi),
+            Some(Action::Pon(pon)) => Reaction::Pon(pon),
+            Some(Action::Kan(gang)) => Reaction::Kan(gang),
+            Some(Action::Ron(_)) => Reaction::Ron { tile: discard },
+            _ => Reaction::Pass,
+        };
+        ready(reaction)
+    }
+    fn deck(&self) -> &Deck {
+        &self.deck
+    }
+}
+
+#[cfg(test)]
+fn play_round(
+    strategies: &mut [Box<dyn Strategy>; 4],
+    seed: u64,
+) -> crate::rules::jp::round::Round {
+    use crate::rules::jp::{
+        round::{Round, RoundOptions},
+        Jp,
+    };
+
+    let mut round: Round = Round::with_seed(Jp, Wind::East, RoundOptions::default(), seed);
+    round.start().unwrap();
+    while !round.is_end() {
+        let (seat, actions) = Wind::enumerate()
+            .map(Seat::from)
+            .map(|seat| (seat, round.legal_actions(seat)))
+            .find(|(_, actions)| !actions.is_empty())
+            .expect("a seat to act");
+        let sight = round.player_sight(seat);
+        let action = strategies[seat.wind().as_index()].choose(seat, &sight, &actions);
+        round.apply(&action, seat).unwrap();
+    }
+    round
+}
+
+#[test]
+fn test_bots_play_a_round() {
+    for seed in 0..2 {
+        let mut strategies: [Box<dyn Strategy>; 4] = [
+            Box::new(RandomBot::with_seed(seed)),
+            Box::new(Tsumogiri),
+            Box::new(Greedy::default()),
+            Box::new(Greedy::default()),
+        ];
+        let round = play_round(&mut strategies, seed);
+        // the tsumogiri bot never calls
+        let south = round.player_sight(Seat::SOUTH).self_deck;
+        assert!(south.melded.chi.is_empty() && south.melded.peng.is_empty());
+    }
+}
+
+#[test]
+fn test_greedy_discard() {
+    use crate::{draw::Draw, hand::Hand, tile::*};
+
+    // 123m 456p 789s 11z 5m and a lone north, drawn 6m: the north goes
+    let hand = Hand::from_faces([C1, C2, C3, D4, D5, D6, B7, B8, B9, EAST, EAST, C5, NORTH]);
+    let draw = TileId::from_face_idx(C6, TileIndex::T1);
+    let mut round: crate::rules::jp::round::Round = crate::rules::jp::round::Round::new(Wind::East);
+    round.start().unwrap();
+    let mut sight = round.player_sight(Seat::EAST);
+    sight.self_deck.hand = hand;
+    sight.draw = Some(Draw::new(draw));
+    sight.to_discard = Some(Seat::EAST);
+    let tiles: Vec<TileId> = (sight.self_deck.hand.tiles.iter()).chain([draw]).collect();
+    let actions: Vec<Action> = tiles
+        .iter()
+        .map(|t| Action::Discard(Seat::EAST.discard(*t)))
+        .collect();
+    let Action::Discard(discard) = Greedy::default().choose(Seat::EAST, &sight, &actions) else {
+        panic!("expected a discard");
+    };
+    assert_eq!(discard.tile().face(), NORTH);
+    // the drawn tile only counts for the seat that drew it
+    assert_eq!(
+        seen(Seat::EAST, &sight).get(C6),
+        seen(Seat::SOUTH, &sight).get(C6) + 1
+    );
+
+    let mut bot = Bot::new(Seat::EAST, Tsumogiri);
+    bot.observe(sight.clone(), actions);
+    let future = Player::draw_and_discard(&mut bot);
+    let played = futures_util::FutureExt::now_or_never(future);
+    assert!(matches!(played, Some(Discard::Discard(tile)) if tile == draw));
+    assert!(bot.choose().is_none());
+
+    // a riichi stays a riichi through the trait
+    let riichi = Action::Richii(crate::rules::jp::riichi::Richii {
+        discard: Seat::EAST.discard(draw),
+    });
+    let mut bot = Bot::new(Seat::EAST, RandomBot::with_seed(0));
+    bot.observe(sight, vec![riichi]);
+    let future = Player::draw_and_discard(&mut bot);
+    let played = futures_util::FutureExt::now_or_never(future);
+    assert!(matches!(played, Some(Discard::Riichi(tile)) if tile == draw));
+}
@@ -1,6 +1,7 @@
 use crate::{player::Player, tile::TileId};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Discard {
     source: Player,
     tile: TileId,
@@ -19,22 +20,40 @@ impl Discard {
 }
 
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DiscardSet {
     discards: Vec<Discard>,
+    /// Whether each discard was the tile just drawn.
+    #[cfg_attr(feature = "serde", serde(default))]
+    tsumogiri: Vec<bool>,
 }
 
 impl DiscardSet {
     pub fn new() -> Self {
         DiscardSet {
             discards: Vec::new(),
+            tsumogiri: Vec::new(),
         }
     }
     pub fn add(&mut self, discard: Discard) {
-        self.discards.push(discard);
+        self.push(discard, false);
+    }
+    /// Add a discard of the tile that was just drawn.
+    pub fn add_tsumogiri(&mut self, discard: Discard) {
+        self.push(discard, true);
     }
     pub fn iter(&self) -> impl Iterator<Item = &Discard> {
         self.discards.iter()
     }
+    /// Every discard with whether it was the tile just drawn.
+    pub fn iter_tsumogiri(&self) -> impl Iterator<Item = (&Discard, bool)> {
+        let tsumogiri = self.tsumogiri.iter().copied().chain(std::iter::repeat(false));
+        self.discards.iter().zip(tsumogiri)
+    }
+    fn push(&mut self, discard: Discard, tsumogiri: bool) {
+        self.discards.push(discard);
+        self.tsumogiri.push(tsumogiri);
+    }
 }
 
 pub trait AsyncDiscardSet {
@@ -1,11 +1,15 @@
+pub mod decompose;
+pub mod joker;
+
 use crate::{
     discard::Discard,
     tile::{
         tile_set::{TileIndexSet, TileSet},
-        Num, Suit, TileFace, TileId,
+        Num, Suit, TileFace, TileId, TileIndex,
     },
 };
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Hand {
     pub tiles: TileSet,
 }
@@ -83,6 +87,18 @@ impl Hand {
             tiles: iter.into_iter().collect(),
         }
     }
+    /// Build a hand from faces, each copy of a face takes the next free index.
+    pub fn from_faces(faces: impl IntoIterator<Item = TileFace>) -> Self {
+        let mut tiles = TileSet::new();
+        for face in faces {
+            if let Some(idx) =
+                TileIndex::enumerate().find(|idx| !tiles.has(TileId::from_face_idx(face, *idx)))
+            {
+                tiles.insert(TileId::from_face_idx(face, idx));
+            }
+        }
+        Hand { tiles }
+    }
 
     pub fn can_peng(&self, claim: TileId) -> bool {
         self.tiles.count_face(claim.face()) >= 2
@@ -153,6 +169,7 @@ impl Hand {
 }
 
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Melded {
     pub chi: Vec<Chi>,
     pub peng: Vec<Peng>,
@@ -173,14 +190,26 @@ pub struct ChiOptions {
     pub claim: TileId,
     pub tiles: [(TileFace, TileIndexSet); 2],
 }
-#[derive(Debug, Clone, Copy)]
 
+impl ChiOptions {
+    /// The chi of `claim` with the first tile of each face in `hand`.
+    pub fn chi(&self, claim: Discard, hand: &Hand) -> Option<Chi> {
+        let pick = |face: TileFace| hand.tiles.iter().find(|t| t.face() == face);
+        Some(Chi {
+            claim,
+            tiles: [pick(self.tiles[0].0)?, pick(self.tiles[1].0)?],
+        })
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Peng {
     pub claim: Discard,
     pub tiles: [TileId; 2],
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Gang {
     AnGang { displayed: TileId },
     MingGang { claim: TileId },
@@ -188,19 +217,31 @@ pub enum Gang {
     Kong { tiles: [TileId; 4] },
 }
 
-#[derive(Debug, Clone, Copy)]
-
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chi {
     pub claim: Discard,
     pub tiles: [TileId; 2],
 }
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Deck {
     pub hand: Hand,
     pub melded: Melded,
+    /// Flowers and seasons declared by this player.
+    pub bonus: Vec<TileId>,
 }
 
 impl Deck {
+    /// Move every bonus tile out of the hand, returns how many replacement draws are owed.
+    pub fn declare_bonus(&mut self) -> usize {
+        let bonus: Vec<TileId> = self.hand.tiles.iter().filter(|t| t.is_bonus()).collect();
+        for tile in &bonus {
+            self.hand.remove(*tile);
+        }
+        self.bonus.extend_from_slice(&bonus);
+        bonus.len()
+    }
     pub fn chi(&mut self, chi: Chi) -> Result<(), Chi> {
         if !self.hand.tiles.has(chi.tiles[0]) || !self.hand.tiles.has(chi.tiles[1]) {
             return Err(chi);
@@ -211,8 +252,53 @@ impl Deck {
         self.melded.chi.push(chi);
         Ok(())
     }
+    pub fn peng(&mut self, peng: Peng) -> Result<(), Peng> {
+        if !self.hand.tiles.has(peng.tiles[0]) || !self.hand.tiles.has(peng.tiles[1]) {
+            return Err(peng);
+        }
+        self.hand.tiles.remove(peng.tiles[0]);
+        self.hand.tiles.remove(peng.tiles[1]);
+        self.melded.peng.push(peng);
+        Ok(())
+    }
+    /// Take the kong tiles out of the hand, for a [`Gang::JiaGang`] the pong is upgraded.
     pub fn gang(&mut self, gang: Gang) -> Result<(), Gang> {
-        todo!()
+        let face = gang.face();
+        let from_hand: Vec<TileId> = match gang {
+            Gang::AnGang { .. } => self.hand.tiles.iter().filter(|t| t.face() == face).collect(),
+            Gang::MingGang { claim } => self
+                .hand
+                .tiles
+                .iter()
+                .filter(|t| t.face() == face && *t != claim)
+                .collect(),
+            Gang::JiaGang { claim, .. } => vec![claim],
+            Gang::Kong { tiles } => tiles.to_vec(),
+        };
+        let needed = match gang {
+            Gang::AnGang { .. } | Gang::Kong { .. } => 4,
+            Gang::MingGang { .. } => 3,
+            Gang::JiaGang { .. } => 1,
+        };
+        if from_hand.len() != needed || from_hand.iter().any(|t| !self.hand.contains(*t)) {
+            return Err(gang);
+        }
+        if let Gang::JiaGang { peng, .. } = gang {
+            let Some(index) = self
+                .melded
+                .peng
+                .iter()
+                .position(|p| p.claim.tile() == peng.claim.tile())
+            else {
+                return Err(gang);
+            };
+            self.melded.peng.remove(index);
+        }
+        for tile in from_hand {
+            self.hand.remove(tile);
+        }
+        self.melded.gang.push(gang);
+        Ok(())
     }
 }
 
@@ -0,0 +1,403 @@
+use super::{Chi, Gang, Hand, Melded};
+use crate::tile::{self, tile_set::TileSet, TileFace, TileId};
+
+/// Faces `0..34` are the honors and the three suits, bonus tiles are never counted.
+pub const FACE_COUNT: usize = 34;
+
+pub const ORPHANS: [TileFace; 13] = [
+    tile::C1,
+    tile::C9,
+    tile::B1,
+    tile::B9,
+    tile::D1,
+    tile::D9,
+    tile::EAST,
+    tile::SOUTH,
+    tile::WEST,
+    tile::NORTH,
+    tile::RED,
+    tile::GREEN,
+    tile::WHITE,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct FaceCounts {
+    counts: [u8; FACE_COUNT],
+}
+
+impl Default for FaceCounts {
+    fn default() -> Self {
+        Self {
+            counts: [0; FACE_COUNT],
+        }
+    }
+}
+
+impl std::fmt::Debug for FaceCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl FaceCounts {
+    pub fn new(tiles: impl IntoIterator<Item = TileId>) -> Self {
+        let mut counts = Self::default();
+        for tile in tiles {
+            counts.add(tile.face());
+        }
+        counts
+    }
+    pub fn get(&self, face: TileFace) -> u8 {
+        self.counts
+            .get(face.into_inner() as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+    pub fn add(&mut self, face: TileFace) {
+        if let Some(count) = self.counts.get_mut(face.into_inner() as usize) {
+            *count += 1;
+        }
+    }
+    pub fn remove(&mut self, face: TileFace) -> bool {
+        match self.counts.get_mut(face.into_inner() as usize) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.counts.iter().map(|c| *c as usize).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub(crate) fn counts(&self) -> [u8; FACE_COUNT] {
+        self.counts
+    }
+    /// Faces with a non-zero count, in face order.
+    pub fn iter(&self) -> impl Iterator<Item = (TileFace, u8)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c > 0)
+            .map(|(i, c)| (TileFace(i as u8), *c))
+    }
+    /// Every way to split the tiles into one pair and melds, the tile count must be `3n + 2`.
+    pub fn decompose(&self) -> Vec<Decomposition> {
+        let mut result = Vec::new();
+        if self.len() % 3 != 2 {
+            return result;
+        }
+        let mut counts = self.counts;
+        for pair in 0..FACE_COUNT {
+            if counts[pair] < 2 {
+                continue;
+            }
+            counts[pair] -= 2;
+            let mut found = Vec::new();
+            split_melds(&mut counts, &mut Vec::new(), &mut found);
+            counts[pair] += 2;
+            result.extend(found.into_iter().map(|melds| Decomposition {
+                pair: TileFace(pair as u8),
+                melds,
+            }));
+        }
+        result
+    }
+    pub fn is_complete(&self) -> bool {
+        !self.decompose().is_empty()
+    }
+    /// One of each terminal and honor, and a pair of one of them.
+    pub fn is_thirteen_orphans(&self) -> bool {
+        self.len() == 14
+            && ORPHANS.iter().all(|f| self.get(*f) > 0)
+            && ORPHANS.iter().map(|f| self.get(*f) as usize).sum::<usize>() == 14
+    }
+    /// Seven different pairs.
+    pub fn is_seven_pairs(&self) -> bool {
+        self.len() == 14 && self.iter().all(|(_, count)| count == 2)
+    }
+    /// Regular form shanten, `-1` is a complete hand and `0` is ready.
+    ///
+    /// The number of melds to build follows the tile count, so a 13 tile hand needs four and
+    /// a 16 tile hand five, open melds already taken out of the hand are accounted for.
+    pub fn shanten(&self) -> i8 {
+        self.shanten_with_sets((self.len() / 3) as i8)
+    }
+    /// Regular form shanten for a hand that should build `sets` melds.
+    pub fn shanten_with_sets(&self, sets: i8) -> i8 {
+        let mut counts = self.counts;
+        let mut best = 2 * sets;
+        search_shanten(&mut counts, 0, sets, Partial::default(), &mut best);
+        best
+    }
+    /// Faces that complete a `3n + 1` tile hand, in face order.
+    pub fn waits(&self) -> Vec<TileFace> {
+        if self.len() % 3 != 1 {
+            return Vec::new();
+        }
+        (0..FACE_COUNT as u8)
+            .map(TileFace)
+            .filter(|face| {
+                let mut counts = *self;
+                counts.add(*face);
+                counts.get(*face) <= 4 && counts.is_complete()
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Partial {
+    melds: i8,
+    taatsu: i8,
+    pair: bool,
+}
+
+impl Partial {
+    fn shanten(self, sets: i8) -> i8 {
+        let taatsu = self.taatsu.min(sets - self.melds);
+        2 * (sets - self.melds) - taatsu - self.pair as i8
+    }
+}
+
+/// `true` when `face + offset` is the same suit as `face`.
+const fn same_suit(face: usize, offset: usize) -> bool {
+    let other = face + offset;
+    match face {
+        7..=15 => other <= 15,
+        16..=24 => other <= 24,
+        25..=33 => other <= 33,
+        _ => false,
+    }
+}
+
+fn search_shanten(
+    counts: &mut [u8; FACE_COUNT],
+    from: usize,
+    sets: i8,
+    partial: Partial,
+    best: &mut i8,
+) {
+    let Some(i) = (from..FACE_COUNT).find(|i| counts[*i] > 0) else {
+        *best = (*best).min(partial.shanten(sets));
+        return;
+    };
+    let mut take = |counts: &mut [u8; FACE_COUNT], faces: &[usize], partial: Partial| {
+        faces.iter().for_each(|f| counts[*f] -= 1);
+        search_shanten(counts, i, sets, partial, best);
+        faces.iter().for_each(|f| counts[*f] += 1);
+    };
+    let meld = Partial {
+        melds: partial.melds + 1,
+        ..partial
+    };
+    let taatsu = Partial {
+        taatsu: partial.taatsu + 1,
+        ..partial
+    };
+    if counts[i] >= 3 {
+        take(counts, &[i, i, i], meld);
+    }
+    if starts_sequence(i) && counts[i + 1] > 0 && counts[i + 2] > 0 {
+        take(counts, &[i, i + 1, i + 2], meld);
+    }
+    if counts[i] >= 2 {
+        if !partial.pair {
+            let pair = Partial {
+                pair: true,
+                ..partial
+            };
+            take(counts, &[i, i], pair);
+        }
+        take(counts, &[i, i], taatsu);
+    }
+    if same_suit(i, 1) && counts[i + 1] > 0 {
+        take(counts, &[i, i + 1], taatsu);
+    }
+    if same_suit(i, 2) && counts[i + 2] > 0 {
+        take(counts, &[i, i + 2], taatsu);
+    }
+    take(counts, &[i], partial);
+}
+
+impl From<&TileSet> for FaceCounts {
+    fn from(set: &TileSet) -> Self {
+        Self::new(set.iter())
+    }
+}
+
+/// The lowest face of a run, `true` when `face` can start one.
+pub(crate) const fn starts_sequence(face: usize) -> bool {
+    matches!(face, 7..=13 | 16..=22 | 25..=31)
+}
+
+fn split_melds(counts: &mut [u8; FACE_COUNT], acc: &mut Vec<Meld>, found: &mut Vec<Vec<Meld>>) {
+    let Some(first) = counts.iter().position(|c| *c > 0) else {
+        found.push(acc.clone());
+        return;
+    };
+    if counts[first] >= 3 {
+        counts[first] -= 3;
+        acc.push(Meld::Triplet(TileFace(first as u8)));
+        split_melds(counts, acc, found);
+        acc.pop();
+        counts[first] += 3;
+    }
+    if starts_sequence(first) && counts[first + 1] > 0 && counts[first + 2] > 0 {
+        counts[first..first + 3].iter_mut().for_each(|c| *c -= 1);
+        acc.push(Meld::Sequence(TileFace(first as u8)));
+        split_melds(counts, acc, found);
+        acc.pop();
+        counts[first..first + 3].iter_mut().for_each(|c| *c += 1);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Meld {
+    /// A run, holding its lowest face.
+    Sequence(TileFace),
+    Triplet(TileFace),
+}
+
+impl Meld {
+    pub fn faces(&self) -> [TileFace; 3] {
+        match *self {
+            Meld::Sequence(face) => [face, TileFace(face.0 + 1), TileFace(face.0 + 2)],
+            Meld::Triplet(face) => [face; 3],
+        }
+    }
+    pub fn face(&self) -> TileFace {
+        match *self {
+            Meld::Sequence(face) | Meld::Triplet(face) => face,
+        }
+    }
+    pub fn is_triplet(&self) -> bool {
+        matches!(self, Meld::Triplet(_))
+    }
+    pub fn is_sequence(&self) -> bool {
+        matches!(self, Meld::Sequence(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decomposition {
+    pub pair: TileFace,
+    pub melds: Vec<Meld>,
+}
+
+impl Decomposition {
+    /// Every face in the pair and the melds.
+    pub fn faces(&self) -> impl Iterator<Item = TileFace> + '_ {
+        [self.pair, self.pair]
+            .into_iter()
+            .chain(self.melds.iter().flat_map(Meld::faces))
+    }
+}
+
+impl Chi {
+    pub fn meld(&self) -> Meld {
+        let lowest = self
+            .tiles
+            .iter()
+            .map(|t| t.face())
+            .chain([self.claim.tile().face()])
+            .min()
+            .expect("chi has three tiles");
+        Meld::Sequence(lowest)
+    }
+}
+
+impl Gang {
+    pub fn face(&self) -> TileFace {
+        match self {
+            Gang::AnGang { displayed } => displayed.face(),
+            Gang::MingGang { claim } => claim.face(),
+            Gang::JiaGang { claim, .. } => claim.face(),
+            Gang::Kong { tiles } => tiles[0].face(),
+        }
+    }
+    pub fn is_concealed(&self) -> bool {
+        matches!(self, Gang::AnGang { .. })
+    }
+}
+
+impl Melded {
+    /// Open melds as decomposition melds, a kong counts as a triplet.
+    pub fn melds(&self) -> Vec<Meld> {
+        let chi = self.chi.iter().map(Chi::meld);
+        let peng = self
+            .peng
+            .iter()
+            .map(|p| Meld::Triplet(p.claim.tile().face()));
+        let gang = self.gang.iter().map(|g| Meld::Triplet(g.face()));
+        chi.chain(peng).chain(gang).collect()
+    }
+    pub fn len(&self) -> usize {
+        self.chi.len() + self.peng.len() + self.gang.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// No melds were called from other players, concealed kongs are allowed.
+    pub fn is_concealed(&self) -> bool {
+        self.chi.is_empty() && self.peng.is_empty() && self.gang.iter().all(Gang::is_concealed)
+    }
+}
+
+impl Hand {
+    pub fn face_counts(&self) -> FaceCounts {
+        FaceCounts::from(&self.tiles)
+    }
+    pub fn decompose(&self) -> Vec<Decomposition> {
+        self.face_counts().decompose()
+    }
+    pub fn shanten(&self) -> i8 {
+        self.face_counts().shanten()
+    }
+    pub fn waits(&self) -> Vec<TileFace> {
+        self.face_counts().waits()
+    }
+}
+
+#[test]
+fn test_decompose() {
+    use crate::tile::*;
+    let hand = Hand::from_faces([C1, C1, C1, C2, C3, C4, C5, C6, C7, C8, C9, C9, C9, C5]);
+    let decompositions = hand.decompose();
+    assert!(!decompositions.is_empty());
+    for d in &decompositions {
+        assert_eq!(d.melds.len(), 4);
+        assert_eq!(d.faces().count(), 14);
+    }
+    let hand = Hand::from_faces([
+        C1, C2, C3, B4, B5, B6, D7, D8, D9, EAST, EAST, EAST, RED, GREEN,
+    ]);
+    assert!(hand.decompose().is_empty());
+}
+
+#[test]
+fn test_shanten() {
+    use crate::tile::*;
+    let ready = Hand::from_faces([C1, C2, C3, B4, B5, B6, D7, D8, D9, EAST, EAST, EAST, RED]);
+    assert_eq!(ready.shanten(), 0);
+    assert_eq!(ready.waits(), vec![RED]);
+    let complete = Hand::from_faces([
+        C1, C2, C3, B4, B5, B6, D7, D8, D9, EAST, EAST, EAST, RED, RED,
+    ]);
+    assert_eq!(complete.shanten(), -1);
+    let pairs = Hand::from_faces([C1, C1, C4, C4, B2, B2, B8, B8, D3, D3, D9, D9, RED]);
+    assert_eq!(pairs.shanten(), 3);
+    // sixteen tiles build five melds
+    let ready = Hand::from_faces([
+        C1, C2, C3, C4, C5, C6, B4, B5, B6, D7, D8, D9, EAST, EAST, EAST, D5,
+    ]);
+    assert_eq!(ready.shanten(), 0);
+    assert_eq!(ready.waits(), vec![D5]);
+    let far = Hand::from_faces([
+        C1, C4, C7, B1, B4, B7, D1, D4, D7, EAST, SOUTH, WEST, NORTH, RED, GREEN, WHITE,
+    ]);
+    assert_eq!(far.shanten(), 10);
+}
@@ -0,0 +1,323 @@
+use super::{
+    decompose::{starts_sequence, Decomposition, FaceCounts, Meld, FACE_COUNT, ORPHANS},
+    Hand,
+};
+use crate::{
+    tile::{TileFace, TileId},
+    Tiles,
+};
+
+/// Wildcard faces and where they may be used. A joker stands for any face, but never for a
+/// fifth copy of one.
+#[derive(Debug, Clone)]
+pub struct Jokers {
+    pub faces: Vec<TileFace>,
+    /// Jokers may be used in the pair, seven pairs count as pairs too.
+    pub in_pair: bool,
+    /// Jokers may be used in a run, otherwise only in triplets.
+    pub in_sequence: bool,
+}
+
+impl Default for Jokers {
+    fn default() -> Self {
+        Self {
+            faces: Vec::new(),
+            in_pair: true,
+            in_sequence: true,
+        }
+    }
+}
+
+/// A decomposition where some tiles were jokers, the melds hold the faces they stood for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JokerDecomposition {
+    pub decomposition: Decomposition,
+    /// Every joker in the hand and the face it stood for.
+    pub substitutes: Vec<(TileId, TileFace)>,
+}
+
+impl Jokers {
+    pub fn new(faces: impl IntoIterator<Item = TileFace>) -> Self {
+        Self {
+            faces: faces.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+    /// The wild faces a tile set marks.
+    pub fn from_tiles(tiles: &impl Tiles) -> Self {
+        Self::new(tiles.wild_faces())
+    }
+    pub fn is_wild(&self, face: TileFace) -> bool {
+        self.faces.contains(&face)
+    }
+    /// Split a hand into counts of its natural tiles and its jokers.
+    pub fn split(&self, hand: &Hand) -> (FaceCounts, Vec<TileId>) {
+        let (jokers, natural): (Vec<TileId>, Vec<TileId>) =
+            hand.tiles.iter().partition(|t| self.is_wild(t.face()));
+        (FaceCounts::new(natural), jokers)
+    }
+    /// Every way to split the hand into one pair and melds with the jokers filled in, the tile
+    /// count must be `3n + 2`. A meld made only of jokers is reported as a triplet.
+    pub fn decompose(&self, hand: &Hand) -> Vec<JokerDecomposition> {
+        let (counts, jokers) = self.split(hand);
+        self.decompose_counts(&counts, jokers.len())
+            .into_iter()
+            .map(|(decomposition, faces)| JokerDecomposition {
+                decomposition,
+                substitutes: jokers.iter().copied().zip(faces).collect(),
+            })
+            .collect()
+    }
+    pub fn is_complete(&self, hand: &Hand) -> bool {
+        let (counts, jokers) = self.split(hand);
+        !self.decompose_counts(&counts, jokers.len()).is_empty()
+    }
+    pub fn is_seven_pairs(&self, hand: &Hand) -> bool {
+        let (counts, jokers) = self.split(hand);
+        let singles = counts.iter().filter(|(_, c)| *c == 1).count();
+        counts.len() + jokers.len() == 14
+            && counts.iter().all(|(_, c)| c <= 2)
+            && singles <= jokers.len()
+            && (jokers.len() - singles) % 2 == 0
+            && (jokers.is_empty() || self.in_pair)
+    }
+    pub fn is_thirteen_orphans(&self, hand: &Hand) -> bool {
+        let (counts, jokers) = self.split(hand);
+        let pairs = counts.iter().filter(|(_, c)| *c == 2).count();
+        counts.len() + jokers.len() == 14
+            && counts.iter().all(|(f, c)| ORPHANS.contains(&f) && c <= 2)
+            && pairs <= 1
+            && (pairs == 1 || self.in_pair)
+    }
+    /// Regular form shanten, every joker brings the hand one tile closer.
+    pub fn shanten(&self, hand: &Hand) -> i8 {
+        let (counts, jokers) = self.split(hand);
+        let sets = ((counts.len() + jokers.len()) / 3) as i8;
+        let shanten = (counts.shanten_with_sets(sets) - jokers.len() as i8).max(-1);
+        if shanten == -1 && self.decompose_counts(&counts, jokers.len()).is_empty() {
+            // the restrictions can leave the last joker unusable
+            return 0;
+        }
+        shanten
+    }
+    /// Faces that complete a `3n + 1` tile hand, drawing a joker completes any ready hand.
+    pub fn waits(&self, hand: &Hand) -> Vec<TileFace> {
+        let (counts, jokers) = self.split(hand);
+        if (counts.len() + jokers.len()) % 3 != 1 {
+            return Vec::new();
+        }
+        (0..FACE_COUNT as u8)
+            .map(TileFace)
+            .filter(|face| {
+                if self.is_wild(*face) {
+                    return !self.decompose_counts(&counts, jokers.len() + 1).is_empty();
+                }
+                let mut counts = counts;
+                counts.add(*face);
+                counts.get(*face) <= 4 && !self.decompose_counts(&counts, jokers.len()).is_empty()
+            })
+            .collect()
+    }
+
+    fn decompose_counts(
+        &self,
+        counts: &FaceCounts,
+        jokers: usize,
+    ) -> Vec<(Decomposition, Vec<TileFace>)> {
+        let mut found = Vec::new();
+        if (counts.len() + jokers) % 3 != 2 {
+            return found;
+        }
+        let mut search = Search {
+            jokers: self,
+            natural: counts.counts(),
+            counts: counts.counts(),
+            free: jokers,
+            substitutes: Vec::new(),
+            melds: Vec::new(),
+            found: &mut found,
+        };
+        for pair in 0..FACE_COUNT {
+            let natural = search.counts[pair].min(2) as usize;
+            if natural < 2 && !self.in_pair {
+                continue;
+            }
+            search.take(&vec![pair; natural], &vec![pair; 2 - natural], |search| {
+                search.split(TileFace(pair as u8))
+            });
+        }
+        found
+    }
+}
+
+struct Search<'a> {
+    jokers: &'a Jokers,
+    natural: [u8; FACE_COUNT],
+    counts: [u8; FACE_COUNT],
+    free: usize,
+    substitutes: Vec<TileFace>,
+    melds: Vec<Meld>,
+    found: &'a mut Vec<(Decomposition, Vec<TileFace>)>,
+}
+
+impl Search<'_> {
+    /// Use up the `natural` tiles and jokers standing for `wild`, then continue.
+    fn take(&mut self, natural: &[usize], wild: &[usize], next: impl FnOnce(&mut Self)) {
+        if wild.len() > self.free || wild.iter().any(|f| !self.can_substitute(*f, wild)) {
+            return;
+        }
+        natural.iter().for_each(|f| self.counts[*f] -= 1);
+        self.free -= wild.len();
+        let before = self.substitutes.len();
+        self.substitutes
+            .extend(wild.iter().map(|f| TileFace(*f as u8)));
+        next(self);
+        self.substitutes.truncate(before);
+        self.free += wild.len();
+        natural.iter().for_each(|f| self.counts[*f] += 1);
+    }
+    /// A joker can't be a fifth copy of a face.
+    fn can_substitute(&self, face: usize, adding: &[usize]) -> bool {
+        let used = self
+            .substitutes
+            .iter()
+            .filter(|f| f.into_inner() as usize == face)
+            .count();
+        let adding = adding.iter().filter(|f| **f == face).count();
+        self.natural[face] as usize + used + adding <= 4
+    }
+    fn split(&mut self, pair: TileFace) {
+        let Some(first) = self.counts.iter().position(|c| *c > 0) else {
+            self.fill(pair, 0);
+            return;
+        };
+        let natural = (self.counts[first] as usize).min(3);
+        let triplet = Meld::Triplet(TileFace(first as u8));
+        self.meld(
+            triplet,
+            &vec![first; natural],
+            &vec![first; 3 - natural],
+            pair,
+        );
+        // the first natural tile can be the low, middle or high tile of a run, anything below it
+        // has to be a joker
+        for start in (first.saturating_sub(2)..=first).filter(|s| starts_sequence(*s)) {
+            let (natural, wild): (Vec<usize>, Vec<usize>) =
+                (start..start + 3).partition(|f| self.counts[*f] > 0);
+            if !wild.is_empty() && !self.jokers.in_sequence {
+                continue;
+            }
+            self.meld(Meld::Sequence(TileFace(start as u8)), &natural, &wild, pair);
+        }
+    }
+    fn meld(&mut self, meld: Meld, natural: &[usize], wild: &[usize], pair: TileFace) {
+        self.take(natural, wild, |search| {
+            search.melds.push(meld);
+            search.split(pair);
+            search.melds.pop();
+        });
+    }
+    /// The natural tiles are used up, the remaining jokers become triplets of their own.
+    fn fill(&mut self, pair: TileFace, from: usize) {
+        if self.free == 0 {
+            let decomposition = Decomposition {
+                pair,
+                melds: self.melds.clone(),
+            };
+            let result = (decomposition, self.substitutes.clone());
+            if !self.found.contains(&result) {
+                self.found.push(result);
+            }
+            return;
+        }
+        for face in from..FACE_COUNT {
+            self.take(&[], &[face; 3], |search| {
+                search.melds.push(Meld::Triplet(TileFace(face as u8)));
+                search.fill(pair, face);
+                search.melds.pop();
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::*;
+
+    #[test]
+    fn test_joker_decompose() {
+        let jokers = Jokers::new([WHITE]);
+        // the white dragon fills the 5m kanchan
+        let hand = Hand::from_faces([
+            C1, C2, C3, C4, C6, B4, B5, B6, D7, D8, D9, EAST, EAST, WHITE,
+        ]);
+        let decompositions = jokers.decompose(&hand);
+        assert!(!decompositions.is_empty());
+        for d in &decompositions {
+            assert_eq!(d.substitutes.len(), 1);
+            assert_eq!(d.decomposition.faces().count(), 14);
+        }
+        assert!(decompositions.iter().any(|d| d.substitutes[0].1 == C5));
+        // without jokers the same hand is not complete
+        assert!(!hand.face_counts().is_complete());
+    }
+
+    #[test]
+    fn test_joker_restrictions() {
+        let hand = Hand::from_faces([
+            C1, C2, C3, B4, B5, B6, D7, D8, D9, EAST, EAST, EAST, RED, WHITE,
+        ]);
+        let jokers = Jokers::new([WHITE]);
+        let d = jokers.decompose(&hand);
+        assert!(d
+            .iter()
+            .all(|d| d.decomposition.pair == RED && d.substitutes[0].1 == RED));
+        let no_pair = Jokers {
+            in_pair: false,
+            ..Jokers::new([WHITE])
+        };
+        assert!(!no_pair.is_complete(&hand));
+        assert_eq!(no_pair.shanten(&hand), 0);
+        let runs = Hand::from_faces([
+            C1, C3, B4, B5, B6, D7, D8, D9, EAST, EAST, EAST, RED, RED, WHITE,
+        ]);
+        assert!(jokers.is_complete(&runs));
+        let no_runs = Jokers {
+            in_sequence: false,
+            ..Jokers::new([WHITE])
+        };
+        assert!(!no_runs.is_complete(&runs));
+    }
+
+    #[test]
+    fn test_joker_shanten_waits() {
+        let jokers = Jokers::new([WHITE]);
+        let hand = Hand::from_faces([C1, C2, C3, B4, B5, B6, D7, D8, D9, EAST, EAST, RED, WHITE]);
+        assert_eq!(jokers.shanten(&hand), 0);
+        let waits = jokers.waits(&hand);
+        assert!(waits.contains(&EAST));
+        assert!(waits.contains(&RED));
+        assert!(waits.contains(&WHITE));
+        // four jokers and nothing else to speak of
+        let hand = Hand::from_faces([
+            C1, C4, B7, D2, SOUTH, NORTH, GREEN, WHITE, WHITE, WHITE, WHITE, C9, D9,
+        ]);
+        let (_, wild) = jokers.split(&hand);
+        assert_eq!(wild.len(), 4);
+        assert!(jokers.shanten(&hand) < hand.shanten());
+    }
+
+    #[test]
+    fn test_joker_special_hands() {
+        let jokers = Jokers::new([WHITE]);
+        let pairs = Hand::from_faces([
+            C1, C1, C4, B2, B2, D9, D9, EAST, EAST, RED, RED, B7, B7, WHITE,
+        ]);
+        assert!(jokers.is_seven_pairs(&pairs));
+        let orphans = Hand::from_faces([
+            C1, C9, B1, B9, D1, D9, EAST, SOUTH, WEST, NORTH, RED, GREEN, C9, WHITE,
+        ]);
+        assert!(jokers.is_thirteen_orphans(&orphans));
+    }
+}
@@ -1,4 +1,4 @@
-use tile::TileId;
+use tile::{TileFace, TileId};
 pub mod hand;
 pub mod rules;
 pub mod tile;
@@ -7,8 +7,72 @@ pub mod player;
 pub mod discard;
 pub mod draw;
 pub mod game;
+pub mod bot;
 
 pub trait Tiles {
+    /// Tiles dealt to every seat at the start of a round.
+    const HAND_SIZE: usize = 13;
     fn new_wall(&self) -> Vec<TileId>;
     fn size(&self) -> usize;
+    /// Faces that are jokers, none by default.
+    fn wild_faces(&self) -> Vec<TileFace> {
+        Vec::new()
+    }
+    /// The dora an indicator of `face` points to, the next face of the set by default.
+    fn dora_successor(face: TileFace) -> TileFace {
+        face.successor()
+    }
+}
+
+/// Adds the eight flowers and seasons to another tile set.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WithBonus<T>(pub T);
+
+impl<T: Tiles> Tiles for WithBonus<T> {
+    const HAND_SIZE: usize = T::HAND_SIZE;
+
+    fn new_wall(&self) -> Vec<TileId> {
+        let mut tiles = self.0.new_wall();
+        tiles.extend_from_slice(&tile::BONUS_TILES);
+        tiles
+    }
+
+    fn size(&self) -> usize {
+        self.0.size() + tile::BONUS_TILES.len()
+    }
+
+    fn wild_faces(&self) -> Vec<TileFace> {
+        self.0.wild_faces()
+    }
+
+    fn dora_successor(face: TileFace) -> TileFace {
+        T::dora_successor(face)
+    }
+}
+
+/// Marks some faces of another tile set as jokers, like the white dragons in Shanghai rules.
+#[derive(Debug, Clone, Default)]
+pub struct WithJokers<T> {
+    pub tiles: T,
+    pub faces: Vec<TileFace>,
+}
+
+impl<T: Tiles> Tiles for WithJokers<T> {
+    const HAND_SIZE: usize = T::HAND_SIZE;
+
+    fn new_wall(&self) -> Vec<TileId> {
+        self.tiles.new_wall()
+    }
+
+    fn size(&self) -> usize {
+        self.tiles.size()
+    }
+
+    fn wild_faces(&self) -> Vec<TileFace> {
+        self.faces.clone()
+    }
+
+    fn dora_successor(face: TileFace) -> TileFace {
+        T::dora_successor(face)
+    }
 }
@@ -1,8 +1,10 @@
 use crate::{
     discard::Discard,
+    draw::Draw,
     tile::{TileId, Wind},
 };
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Player {
     wind: Wind,
 }
@@ -13,11 +15,13 @@ impl Player {
     pub const NORTH: Player = Player { wind: Wind::North };
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
-pub struct WindSet<T> {
-    set: [T; 4],
+/// A value for each seat, `N` is 4 for a full table and 3 for a three-player one which has no
+/// north seat.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct WindSet<T, const N: usize = 4> {
+    set: [T; N],
 }
-impl<T> WindSet<T> {
+impl<T, const N: usize> WindSet<T, N> {
     pub fn iter(&self) -> impl Iterator<Item = (Player, &T)> {
         self.set
             .iter()
@@ -33,20 +37,55 @@ impl<T> WindSet<T> {
     pub fn insert<W: Into<Wind>>(&mut self, wind: W, value: T) {
         self.set[wind.into() as usize] = value;
     }
+    pub const fn seats(&self) -> usize {
+        N
+    }
+    pub fn from_fn(mut f: impl FnMut(Player) -> T) -> Self {
+        WindSet {
+            set: std::array::from_fn(|i| f(Player::from(Wind::from_index(i)))),
+        }
+    }
 }
 
-impl<P, T> FromIterator<(P, T)> for WindSet<T>
+/// Serialized as a sequence with one value per seat.
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize, const N: usize> serde::Serialize for WindSet<T, N> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.set.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>, const N: usize> serde::Deserialize<'de> for WindSet<T, N> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let set = Vec::<T>::deserialize(deserializer)?;
+        let len = set.len();
+        let set = set
+            .try_into()
+            .map_err(|_| serde::de::Error::invalid_length(len, &"one value per seat"))?;
+        Ok(WindSet { set })
+    }
+}
+
+impl<T: Default, const N: usize> Default for WindSet<T, N> {
+    fn default() -> Self {
+        WindSet {
+            set: std::array::from_fn(|_| T::default()),
+        }
+    }
+}
+
+impl<P, T, const N: usize> FromIterator<(P, T)> for WindSet<T, N>
 where
     P: Into<Player>,
     T: Default,
 {
     fn from_iter<I: IntoIterator<Item = (P, T)>>(iter: I) -> Self {
-        let mut set: [T; 4] = Default::default();
+        let mut set = Self::default();
         for (player, value) in iter {
-            let player: Player = player.into();
-            set[player.wind.as_index()] = value;
+            set.insert(player.into(), value);
         }
-        WindSet { set }
+        set
     }
 }
 
@@ -62,6 +101,12 @@ impl Player {
             wind: self.wind.next(),
         }
     }
+    /// The next seat on a table of `seats` players.
+    pub fn next_in(&self, seats: usize) -> Player {
+        Player {
+            wind: Wind::from_index((self.wind.as_index() + 1) % seats),
+        }
+    }
 }
 
 impl From<Wind> for Player {
@@ -0,0 +1,439 @@
+use rand::Rng;
+
+use crate::{
+    discard::Discard,
+    hand::{
+        decompose::Meld,
+        joker::{JokerDecomposition, Jokers},
+        Deck, Gang, Hand,
+    },
+    player::{Player, WindSet},
+    tile::{Honer, TileFace, TileId, Wind, WHITE},
+    wall::Wall,
+    Tiles,
+};
+
+use super::jp::{HAND_SIZE, JP_TILES};
+
+/// Guangdong (推倒胡) tiles, the 136 standard tiles without flowers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gd;
+
+impl Tiles for Gd {
+    fn new_wall(&self) -> Vec<TileId> {
+        JP_TILES.to_vec()
+    }
+
+    fn size(&self) -> usize {
+        136
+    }
+}
+
+/// Which face is the ghost (鬼牌).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ghost {
+    None,
+    /// The white dragons are ghosts (白板做鬼).
+    WhiteDragon,
+    /// A tile is flipped from the back of the wall, the one after it is the ghost (翻鬼).
+    Flipped,
+}
+
+#[derive(Debug, Clone)]
+pub struct GdRules {
+    pub ghost: Ghost,
+    /// How many horse tiles (馬) are set aside before the deal.
+    pub horses: usize,
+    /// What a chicken hand pays from every paying seat.
+    pub base: u32,
+    /// Whether a discard can be won on, otherwise only self-drawn wins count.
+    pub discard_win: bool,
+    /// Whether a ghost may be used in the pair.
+    pub ghost_pair: bool,
+}
+
+impl Default for GdRules {
+    fn default() -> Self {
+        Self {
+            ghost: Ghost::None,
+            horses: 4,
+            base: 1,
+            discard_win: true,
+            ghost_pair: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GdHand {
+    /// 雞胡, any complete hand.
+    Chicken,
+    AllPungs,
+    SevenPairs,
+    FullFlush,
+    ThirteenOrphans,
+}
+
+impl GdHand {
+    pub const fn multiplier(self) -> u32 {
+        match self {
+            GdHand::Chicken => 1,
+            GdHand::AllPungs | GdHand::SevenPairs => 2,
+            GdHand::FullFlush => 4,
+            GdHand::ThirteenOrphans => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GdSettlement {
+    pub hand: GdHand,
+    pub horses: Vec<TileId>,
+    /// How many horses landed on the winner, each one pays the hand once more.
+    pub hits: usize,
+    pub payments: WindSet<i32>,
+}
+
+impl GdRules {
+    pub fn jokers(&self, ghost: Option<TileFace>) -> Jokers {
+        Jokers {
+            in_pair: self.ghost_pair,
+            ..Jokers::new(ghost)
+        }
+    }
+
+    /// The best hand the deck makes, the winning tile must already be in `deck.hand`. Tiles
+    /// of the `ghost` face stand for whatever completes the hand.
+    pub fn evaluate(&self, deck: &Deck, ghost: Option<TileFace>) -> Option<GdHand> {
+        let jokers = self.jokers(ghost);
+        let open = deck.melded.melds();
+        let mut best = None;
+        if open.is_empty() && jokers.is_thirteen_orphans(&deck.hand) {
+            best = Some(GdHand::ThirteenOrphans);
+        }
+        if open.is_empty() && jokers.is_seven_pairs(&deck.hand) {
+            best = best.max(Some(GdHand::SevenPairs));
+        }
+        for JokerDecomposition { decomposition, .. } in jokers.decompose(&deck.hand) {
+            let mut melds = decomposition.melds;
+            melds.extend_from_slice(&open);
+            best = best.max(Some(Self::hand(decomposition.pair, &melds)));
+        }
+        best
+    }
+
+    fn hand(pair: TileFace, melds: &[Meld]) -> GdHand {
+        let faces = || [pair].into_iter().chain(melds.iter().map(Meld::face));
+        let mut kinds = faces().map(|f| f.try_into_suit().map(|s| s.kind));
+        let first = kinds.next().flatten();
+        if first.is_some() && kinds.all(|k| k == first) {
+            GdHand::FullFlush
+        } else if melds.iter().all(Meld::is_triplet) {
+            GdHand::AllPungs
+        } else {
+            GdHand::Chicken
+        }
+    }
+
+    /// Point changes for a kong: the discarder pays 3 for an exposed kong, everyone else pays 1
+    /// for an added kong and 2 for a concealed one.
+    pub fn kong_payments(
+        &self,
+        gang: &Gang,
+        player: Player,
+        discarder: Option<Player>,
+    ) -> WindSet<i32> {
+        let mut payments = WindSet::<i32>::default();
+        let base = self.base as i32;
+        for wind in Wind::enumerate().filter(|w| *w != player.wind()) {
+            let pay = match gang {
+                Gang::MingGang { .. } if discarder.is_some_and(|d| d.wind() == wind) => 3 * base,
+                Gang::MingGang { .. } => 0,
+                Gang::JiaGang { .. } => base,
+                Gang::AnGang { .. } | Gang::Kong { .. } => 2 * base,
+            };
+            *payments.get_mut(wind) -= pay;
+            *payments.get_mut(player) += pay;
+        }
+        payments
+    }
+
+    /// Point changes for a win, `discarder` is `None` for a self-drawn win where all three
+    /// opponents pay.
+    pub fn payments(
+        &self,
+        hand: GdHand,
+        hits: usize,
+        winner: Player,
+        discarder: Option<Player>,
+    ) -> WindSet<i32> {
+        let pay = (self.base * hand.multiplier() * (1 + hits as u32)) as i32;
+        let mut payments = WindSet::<i32>::default();
+        let payers = Wind::enumerate()
+            .filter(|w| *w != winner.wind())
+            .filter(|w| discarder.is_none_or(|d| d.wind() == *w));
+        for wind in payers {
+            *payments.get_mut(wind) -= pay;
+            *payments.get_mut(winner) += pay;
+        }
+        payments
+    }
+}
+
+/// The seat a horse points at counted from the dealer: 1, 5 and 9 are the dealer, 2, 6 the next
+/// seat and so on. Winds point at their own seat, red, green and white at the first three.
+pub fn horse_seat(horse: TileFace) -> usize {
+    if let Some(suit) = horse.try_into_suit() {
+        return (suit.num as usize - 1) % 4;
+    }
+    match horse.try_into_honer() {
+        Some(Honer::Wind(wind)) => wind.as_index(),
+        Some(Honer::Dragon(dragon)) => dragon as usize,
+        None => 0,
+    }
+}
+
+pub struct GdRound<R> {
+    pub rules: GdRules,
+    pub dealer: Wind,
+    pub wall: Wall<Gd, R>,
+    pub decks: WindSet<Deck>,
+    pub horses: Vec<TileId>,
+    /// The flipped tile when [`Ghost::Flipped`] is used.
+    pub indicator: Option<TileId>,
+    pub ghost: Option<TileFace>,
+    /// Kong payouts settled so far.
+    pub scores: WindSet<i32>,
+}
+
+impl<R: Rng> GdRound<R> {
+    /// Shuffle, set the horses and the ghost aside from the back of the wall and deal.
+    pub fn new(rules: GdRules, dealer: Wind, rng: R) -> Self {
+        let mut wall = Wall::new(Gd, rng);
+        wall.shuffle();
+        let horses = (0..rules.horses)
+            .filter_map(|_| wall.draw_replacement())
+            .map(|draw| draw.tile())
+            .collect();
+        let (indicator, ghost) = match rules.ghost {
+            Ghost::None => (None, None),
+            Ghost::WhiteDragon => (None, Some(WHITE)),
+            Ghost::Flipped => {
+                let indicator = wall.draw_replacement().map(|draw| draw.tile());
+                (indicator, indicator.map(|t| t.face().successor()))
+            }
+        };
+        let decks = wall
+            .draw_init::<4, HAND_SIZE>()
+            .into_iter()
+            .enumerate()
+            .map(|(i, hand)| {
+                let deck = Deck {
+                    hand: Hand::new(hand),
+                    ..Default::default()
+                };
+                (Wind::from_index(i), deck)
+            })
+            .collect();
+        Self {
+            rules,
+            dealer,
+            wall,
+            decks,
+            horses,
+            indicator,
+            ghost,
+            scores: WindSet::default(),
+        }
+    }
+}
+
+impl<R> GdRound<R> {
+    /// Draw the next tile into the hand of `player`, `None` when the wall is exhausted.
+    pub fn draw(&mut self, player: Player) -> Option<TileId> {
+        let tile = self.wall.draw_next()?.tile();
+        self.decks.get_mut(player).hand.tiles.insert(tile);
+        Some(tile)
+    }
+
+    pub fn discard(&mut self, player: Player, tile: TileId) -> Result<Discard, TileId> {
+        let hand = &mut self.decks.get_mut(player).hand;
+        if !hand.contains(tile) {
+            return Err(tile);
+        }
+        hand.remove(tile);
+        Ok(player.discard(tile))
+    }
+
+    /// Declare a kong, it is paid at once and a replacement is drawn from the back of the wall.
+    /// An exposed kong needs the `discarder` of the claimed tile, which stays out of the hand.
+    pub fn kong(
+        &mut self,
+        player: Player,
+        gang: Gang,
+        discarder: Option<Player>,
+    ) -> Result<Option<TileId>, Gang> {
+        let deck = self.decks.get_mut(player);
+        if matches!(gang, Gang::MingGang { .. }) && discarder.is_none() {
+            return Err(gang);
+        }
+        deck.gang(gang)?;
+        let replacement = self.wall.draw_replacement().map(|draw| draw.tile());
+        if let Some(tile) = replacement {
+            deck.hand.tiles.insert(tile);
+        }
+        let payments = self.rules.kong_payments(&gang, player, discarder);
+        for (seat, pay) in payments.iter() {
+            *self.scores.get_mut(seat) += pay;
+        }
+        Ok(replacement)
+    }
+
+    /// Declare a win, with `discard` for a win on someone else's tile. The horses are revealed
+    /// and every one pointing at the winner's seat pays the hand once more.
+    pub fn win(&mut self, winner: Player, discard: Option<Discard>) -> Option<GdSettlement> {
+        if discard.is_some() && !self.rules.discard_win {
+            return None;
+        }
+        let mut deck = self.decks.get(winner).clone();
+        if let Some(discard) = discard {
+            deck.hand.tiles.insert(discard.tile());
+        }
+        let hand = self.rules.evaluate(&deck, self.ghost)?;
+        let seat = (winner.wind().as_index() + 4 - self.dealer.as_index()) % 4;
+        let hits = self
+            .horses
+            .iter()
+            .filter(|h| horse_seat(h.face()) == seat)
+            .count();
+        let payments = self
+            .rules
+            .payments(hand, hits, winner, discard.map(|d| d.source()));
+        *self.decks.get_mut(winner) = deck;
+        Some(GdSettlement {
+            hand,
+            horses: self.horses.clone(),
+            hits,
+            payments,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn deck(faces: impl IntoIterator<Item = TileFace>) -> Deck {
+        Deck {
+            hand: Hand::from_faces(faces),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_new_round() {
+        let rules = GdRules {
+            ghost: Ghost::Flipped,
+            ..Default::default()
+        };
+        let round = GdRound::new(rules, Wind::East, StdRng::seed_from_u64(30));
+        assert_eq!(round.horses.len(), 4);
+        let indicator = round.indicator.unwrap();
+        assert_eq!(round.ghost, Some(indicator.face().successor()));
+        assert_eq!(round.wall.len() + 5 + 4 * HAND_SIZE, 136);
+        assert_eq!(NORTH.successor(), EAST);
+        assert_eq!(C9.successor(), C1);
+        assert_eq!(WHITE.successor(), RED);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let rules = GdRules::default();
+        let chicken = deck([C1, C2, C3, B4, B5, B6, D7, D8, D9, C7, C8, C9, SOUTH, SOUTH]);
+        assert_eq!(rules.evaluate(&chicken, None), Some(GdHand::Chicken));
+        let flush = deck([B1, B1, B1, B2, B3, B4, B5, B6, B7, B8, B8, B8, B9, B9]);
+        assert_eq!(rules.evaluate(&flush, None), Some(GdHand::FullFlush));
+        let pairs = deck([C1, C1, C4, C4, B2, B2, D9, D9, EAST, EAST, RED, RED, B7, B7]);
+        assert_eq!(rules.evaluate(&pairs, None), Some(GdHand::SevenPairs));
+        let orphans = deck([
+            C1, C9, B1, B9, D1, D9, EAST, SOUTH, WEST, NORTH, RED, GREEN, WHITE, C9,
+        ]);
+        assert_eq!(
+            rules.evaluate(&orphans, None),
+            Some(GdHand::ThirteenOrphans)
+        );
+        let broken = deck([
+            C1, C9, B1, B9, D1, D9, EAST, SOUTH, WEST, NORTH, RED, GREEN, WHITE, C5,
+        ]);
+        assert_eq!(rules.evaluate(&broken, None), None);
+        // the ghost stands in for the missing 8s
+        let ghost = deck([B1, B1, B1, B2, B3, B4, B5, B6, B7, B8, B8, D5, B9, B9]);
+        assert_eq!(rules.evaluate(&ghost, None), None);
+        assert_eq!(rules.evaluate(&ghost, Some(D5)), Some(GdHand::FullFlush));
+        let ghost_pair = deck([C1, C2, C3, B4, B5, B6, D7, D8, D9, C7, C8, C9, SOUTH, D5]);
+        assert_eq!(rules.evaluate(&ghost_pair, Some(D5)), Some(GdHand::Chicken));
+        let rules = GdRules {
+            ghost_pair: false,
+            ..Default::default()
+        };
+        assert_eq!(rules.evaluate(&ghost_pair, Some(D5)), None);
+    }
+
+    #[test]
+    fn test_horses() {
+        assert_eq!(horse_seat(C1), 0);
+        assert_eq!(horse_seat(B6), 1);
+        assert_eq!(horse_seat(D8), 3);
+        assert_eq!(horse_seat(WEST), 2);
+        assert_eq!(horse_seat(GREEN), 1);
+        let mut round = GdRound::new(GdRules::default(), Wind::South, StdRng::seed_from_u64(5));
+        round.horses = [C5, D2, B9, NORTH]
+            .map(|f| TileId::from_face_idx(f, TileIndex::T0))
+            .to_vec();
+        *round.decks.get_mut(Player::SOUTH) =
+            deck([C1, C2, C3, B4, B5, B6, D7, D8, D9, C7, C8, C9, SOUTH, SOUTH]);
+        // south deals, so 1, 5 and 9 land on south
+        let settlement = round.win(Player::SOUTH, None).unwrap();
+        assert_eq!(settlement.hits, 2);
+        assert_eq!(*settlement.payments.get(Wind::South), 9);
+        assert_eq!(*settlement.payments.get(Wind::North), -3);
+    }
+
+    #[test]
+    fn test_kong_payments() {
+        let mut round = GdRound::new(GdRules::default(), Wind::East, StdRng::seed_from_u64(9));
+        *round.decks.get_mut(Player::EAST) = deck([C1, C1, C1, C1, D5, D5, D5, B2, B3]);
+        let displayed = TileId::from_face_idx(C1, TileIndex::T0);
+        let first = round
+            .kong(Player::EAST, Gang::AnGang { displayed }, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(*round.scores.get(Wind::East), 6);
+        assert_eq!(*round.scores.get(Wind::West), -2);
+        let claim = TileId::from_face_idx(D5, TileIndex::T3);
+        let second = round
+            .kong(Player::EAST, Gang::MingGang { claim }, Some(Player::NORTH))
+            .unwrap()
+            .unwrap();
+        assert_eq!(*round.scores.get(Wind::East), 9);
+        assert_eq!(*round.scores.get(Wind::North), -5);
+        let deck = round.decks.get(Player::EAST);
+        assert_eq!(deck.melded.gang.len(), 2);
+        // the claimed 5p is only in the kong, the hand keeps 2s 3s and the replacements
+        assert_eq!(deck.hand.len(), 4);
+        assert!(!deck.hand.contains(claim));
+        assert!(deck
+            .hand
+            .tiles
+            .iter()
+            .all(|t| t.face() != D5 || t == first || t == second));
+        assert!(deck.hand.contains(first) && deck.hand.contains(second));
+        let claim = TileId::from_face_idx(B2, TileIndex::T3);
+        assert!(round
+            .kong(Player::EAST, Gang::MingGang { claim }, Some(Player::NORTH))
+            .is_err());
+        assert!(!round.decks.get(Player::EAST).hand.contains(claim));
+    }
+}
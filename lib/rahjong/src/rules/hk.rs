@@ -0,0 +1,353 @@
+use crate::{
+    hand::{
+        decompose::{FaceCounts, Meld},
+        Deck,
+    },
+    player::{Player, WindSet},
+    tile::{Bonus, Dragon, Honer, SuitKind, TileFace, TileId, Wind},
+    Tiles, WithBonus,
+};
+
+use super::jp::Jp;
+
+/// Hong Kong old style, the 136 standard tiles plus flowers and seasons.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hk;
+
+impl Tiles for Hk {
+    fn new_wall(&self) -> Vec<TileId> {
+        WithBonus(Jp).new_wall()
+    }
+
+    fn size(&self) -> usize {
+        144
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Payment {
+    /// 全銃, the discarder pays for the whole table: twice the base.
+    DiscarderPaysAll,
+    /// 半銃, the discarder pays the base and the other two pay half of it.
+    EveryonePays,
+}
+
+#[derive(Debug, Clone)]
+pub struct HkRules {
+    /// Hands below this are not allowed to win, 3 by default.
+    pub min_faan: u8,
+    /// Limit hands score this, and it caps everything else.
+    pub limit_faan: u8,
+    pub payment: Payment,
+}
+
+impl Default for HkRules {
+    fn default() -> Self {
+        Self {
+            min_faan: 3,
+            limit_faan: 10,
+            payment: Payment::DiscarderPaysAll,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WinContext {
+    pub seat: Wind,
+    pub prevailing: Wind,
+    pub self_drawn: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Faan {
+    SelfDrawn,
+    ConcealedHand,
+    AllChows,
+    AllPungs,
+    HalfFlush,
+    FullFlush,
+    DragonPung(Dragon),
+    SeatWind,
+    PrevailingWind,
+    SeatFlower(Bonus),
+    NoFlowers,
+    ThirteenOrphans,
+    BigThreeDragons,
+    NineGates,
+    AllHonors,
+}
+
+impl Faan {
+    /// `None` for limit hands.
+    pub const fn value(self) -> Option<u8> {
+        match self {
+            Faan::SelfDrawn
+            | Faan::ConcealedHand
+            | Faan::AllChows
+            | Faan::DragonPung(_)
+            | Faan::SeatWind
+            | Faan::PrevailingWind
+            | Faan::SeatFlower(_)
+            | Faan::NoFlowers => Some(1),
+            Faan::AllPungs | Faan::HalfFlush => Some(3),
+            Faan::FullFlush => Some(7),
+            Faan::ThirteenOrphans | Faan::BigThreeDragons | Faan::NineGates | Faan::AllHonors => {
+                None
+            }
+        }
+    }
+    pub const fn is_limit(self) -> bool {
+        self.value().is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HkScore {
+    pub faans: Vec<Faan>,
+    pub faan: u8,
+    pub limit: bool,
+}
+
+/// Base points by faan, the "half spicy" table used by most Hong Kong tables.
+const BASE_POINTS: [u32; 11] = [1, 2, 4, 8, 16, 24, 32, 48, 64, 96, 128];
+
+impl HkRules {
+    /// Score a complete hand, the winning tile must already be in `deck.hand`.
+    ///
+    /// Returns `None` when the hand is not complete or doesn't reach `min_faan`.
+    pub fn evaluate(&self, deck: &Deck, ctx: &WinContext) -> Option<HkScore> {
+        let counts = deck.hand.face_counts();
+        let open = deck.melded.melds();
+        let concealed = deck.melded.is_concealed();
+        let mut best: Option<HkScore> = None;
+        let mut consider = |faans: Vec<Faan>| {
+            let score = self.score(faans);
+            if best.as_ref().is_none_or(|b| score.faan > b.faan) {
+                best = Some(score);
+            }
+        };
+        if open.is_empty() && counts.is_thirteen_orphans() {
+            consider(vec![Faan::ThirteenOrphans]);
+        }
+        if open.is_empty() && is_nine_gates(&counts) {
+            consider(vec![Faan::NineGates]);
+        }
+        for decomposition in counts.decompose() {
+            let mut melds = decomposition.melds;
+            melds.extend_from_slice(&open);
+            consider(self.faans(decomposition.pair, &melds, deck, ctx, concealed));
+        }
+        best.filter(|score| score.faan >= self.min_faan)
+    }
+
+    fn faans(
+        &self,
+        pair: TileFace,
+        melds: &[Meld],
+        deck: &Deck,
+        ctx: &WinContext,
+        concealed: bool,
+    ) -> Vec<Faan> {
+        let mut faans = Vec::new();
+        if ctx.self_drawn {
+            faans.push(Faan::SelfDrawn);
+        }
+        if concealed {
+            faans.push(Faan::ConcealedHand);
+        }
+        if melds.iter().all(Meld::is_sequence) {
+            faans.push(Faan::AllChows);
+        }
+        if melds.iter().all(Meld::is_triplet) {
+            faans.push(Faan::AllPungs);
+        }
+        let faces = || [pair].into_iter().chain(melds.iter().map(Meld::face));
+        let mut kinds = faces().filter_map(|f| f.try_into_suit()).map(|s| s.kind);
+        let has_honor = faces().any(|f| f.is_honor());
+        match kinds.next() {
+            None => faans.push(Faan::AllHonors),
+            Some(kind) if kinds.all(|k: SuitKind| k == kind) => {
+                faans.push(if has_honor {
+                    Faan::HalfFlush
+                } else {
+                    Faan::FullFlush
+                });
+            }
+            _ => {}
+        }
+        let pungs = melds.iter().filter(|m| m.is_triplet()).map(Meld::face);
+        let mut dragons = Vec::new();
+        for face in pungs {
+            match face.try_into_honer() {
+                Some(Honer::Dragon(dragon)) => dragons.push(dragon),
+                Some(Honer::Wind(wind)) => {
+                    if wind == ctx.seat {
+                        faans.push(Faan::SeatWind);
+                    }
+                    if wind == ctx.prevailing {
+                        faans.push(Faan::PrevailingWind);
+                    }
+                }
+                None => {}
+            }
+        }
+        if dragons.len() == 3 {
+            faans.push(Faan::BigThreeDragons);
+        } else {
+            faans.extend(dragons.into_iter().map(Faan::DragonPung));
+        }
+        if deck.bonus.is_empty() {
+            faans.push(Faan::NoFlowers);
+        }
+        faans.extend(
+            deck.bonus
+                .iter()
+                .filter_map(|t| t.face().try_into_bonus())
+                .filter(|b| b.wind() == ctx.seat)
+                .map(Faan::SeatFlower),
+        );
+        faans
+    }
+
+    fn score(&self, faans: Vec<Faan>) -> HkScore {
+        let limit = faans.iter().any(|f| f.is_limit());
+        let total: u8 = faans.iter().filter_map(|f| f.value()).sum();
+        let faan = if limit {
+            self.limit_faan
+        } else {
+            total.min(self.limit_faan)
+        };
+        HkScore {
+            faans,
+            faan,
+            limit: faan >= self.limit_faan,
+        }
+    }
+
+    pub fn base_points(&self, faan: u8) -> u32 {
+        let faan = faan.min(self.limit_faan) as usize;
+        BASE_POINTS[faan.min(BASE_POINTS.len() - 1)]
+    }
+
+    /// Point changes for everyone, `discarder` is `None` for a self-drawn win where each
+    /// opponent pays the base.
+    pub fn payments(
+        &self,
+        score: &HkScore,
+        winner: Player,
+        discarder: Option<Player>,
+    ) -> WindSet<i32> {
+        let base = self.base_points(score.faan) as i32;
+        let mut payments = WindSet::<i32>::default();
+        for wind in Wind::enumerate().filter(|w| *w != winner.wind()) {
+            let pay = match (discarder, self.payment) {
+                (None, _) => base,
+                (Some(d), Payment::DiscarderPaysAll) if d.wind() == wind => base * 2,
+                (Some(_), Payment::DiscarderPaysAll) => 0,
+                (Some(d), Payment::EveryonePays) if d.wind() == wind => base,
+                (Some(_), Payment::EveryonePays) => base / 2,
+            };
+            *payments.get_mut(wind) -= pay;
+            *payments.get_mut(winner) += pay;
+        }
+        payments
+    }
+}
+
+fn is_nine_gates(counts: &FaceCounts) -> bool {
+    const GATES: [u8; 9] = [3, 1, 1, 1, 1, 1, 1, 1, 3];
+    if counts.len() != 14 {
+        return false;
+    }
+    SuitKind::enumerate().any(|kind| {
+        let start = TileFace::const_from_char(kind.unicode_start());
+        GATES
+            .iter()
+            .enumerate()
+            .all(|(n, gate)| counts.get(TileFace(start.into_inner() + n as u8)) >= *gate)
+            && counts
+                .iter()
+                .all(|(f, _)| f.try_into_suit().is_some_and(|s| s.kind == kind))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hand::Hand, tile::*};
+
+    fn deck(faces: impl IntoIterator<Item = TileFace>) -> Deck {
+        Deck {
+            hand: Hand::from_faces(faces),
+            ..Default::default()
+        }
+    }
+
+    const DISCARD_WIN: WinContext = WinContext {
+        seat: Wind::South,
+        prevailing: Wind::East,
+        self_drawn: false,
+    };
+
+    #[test]
+    fn test_all_pungs_half_flush() {
+        let deck = deck([
+            C1, C1, C1, C5, C5, C5, C9, C9, C9, RED, RED, RED, EAST, EAST,
+        ]);
+        let score = HkRules::default().evaluate(&deck, &DISCARD_WIN).unwrap();
+        assert!(score.faans.contains(&Faan::AllPungs));
+        assert!(score.faans.contains(&Faan::HalfFlush));
+        assert!(score.faans.contains(&Faan::DragonPung(Dragon::Red)));
+        assert_eq!(score.faan, 9);
+        assert!(!score.limit);
+    }
+
+    #[test]
+    fn test_limit_hands() {
+        let rules = HkRules::default();
+        let orphans = deck([
+            C1, C9, B1, B9, D1, D9, EAST, SOUTH, WEST, NORTH, RED, GREEN, WHITE, WHITE,
+        ]);
+        let score = rules.evaluate(&orphans, &DISCARD_WIN).unwrap();
+        assert_eq!(score.faans, vec![Faan::ThirteenOrphans]);
+        assert!(score.limit);
+        // the 14th tile has to pair one of the orphans
+        let stray = deck([
+            C1, C9, B1, B9, D1, D9, EAST, SOUTH, WEST, NORTH, RED, GREEN, WHITE, C2,
+        ]);
+        assert!(rules.evaluate(&stray, &DISCARD_WIN).is_none());
+        let gates = deck([B1, B1, B1, B2, B3, B4, B5, B6, B7, B8, B9, B9, B9, B7]);
+        let score = rules.evaluate(&gates, &DISCARD_WIN).unwrap();
+        assert_eq!(score.faan, rules.limit_faan);
+    }
+
+    #[test]
+    fn test_min_faan() {
+        let mut deck = deck([C1, C2, C3, B4, B5, B6, D7, D8, D9, C7, C8, C9, SOUTH, SOUTH]);
+        deck.bonus.push(TileId::from_face_idx(PLUM, TileIndex::T0));
+        let rules = HkRules::default();
+        assert!(rules.evaluate(&deck, &DISCARD_WIN).is_none());
+        let rules = HkRules {
+            min_faan: 0,
+            ..Default::default()
+        };
+        let score = rules.evaluate(&deck, &DISCARD_WIN).unwrap();
+        assert_eq!(score.faans, vec![Faan::ConcealedHand, Faan::AllChows]);
+    }
+
+    #[test]
+    fn test_payments() {
+        for payment in [Payment::DiscarderPaysAll, Payment::EveryonePays] {
+            let rules = HkRules {
+                payment,
+                ..Default::default()
+            };
+            let score = rules.score(vec![Faan::AllPungs]);
+            for discarder in [None, Some(Player::NORTH)] {
+                let payments = rules.payments(&score, Player::EAST, discarder);
+                assert_eq!(payments.iter().map(|(_, p)| *p).sum::<i32>(), 0);
+                assert!(*payments.get(Player::EAST) > 0);
+            }
+        }
+    }
+}
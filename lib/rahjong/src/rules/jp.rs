@@ -2,7 +2,9 @@ use rand::{seq::SliceRandom, Rng, SeedableRng};
 pub mod player;
 pub mod riichi;
 pub mod round;
+pub mod sanma;
 pub mod win;
+pub mod yaku;
 use crate::{
     hand::{Chi, Deck, Gang, Hand, Peng},
     tile::{
@@ -35,6 +37,7 @@ fn build_jp_tiles() {
     std::fs::write("./src/rules/jp/tiles", bytes).expect("Failed to write file");
 }
 
+pub const HAND_SIZE: usize = 13;
 pub const JP_TILES: [TileId; 136] = unsafe { std::mem::transmute_copy(include_bytes!("jp/tiles")) };
 pub const RED_B5: TileId = TileId::from_face_idx(B5, TileIndex::T0);
 pub const RED_C5: TileId = TileId::from_face_idx(C5, TileIndex::T0);
@@ -85,13 +88,22 @@ pub struct DoraSet {
 
 impl DoraSet {
     pub fn indicator(&self) -> TileId {
-        self.ura[0]
+        self.indicator
     }
+    /// The first indicator and one more for every kan.
     pub fn shown_indicators(&self) -> Vec<TileId> {
         let mut v = vec![self.indicator];
-        v.extend_from_slice(&self.ura[0..self.kan_index]);
+        v.extend_from_slice(&self.kan[0..self.kan_index]);
         v
     }
+    /// The tiles under the shown indicators, counted for a riichi win.
+    pub fn ura_indicators(&self) -> Vec<TileId> {
+        self.ura[0..=self.kan_index].to_vec()
+    }
+    /// Whether another kan may be declared, there are four at most.
+    pub fn can_kan(&self) -> bool {
+        self.kan_index < 4
+    }
     pub fn kan(&mut self) -> Option<TileId> {
         if self.kan_index < 4 {
             let tile = self.rinshan[self.kan_index];
@@ -112,7 +124,7 @@ impl DoraSet {
     }
 }
 
-impl<R> Wall<Jp, R> {
+impl<S: Tiles, R> Wall<S, R> {
     /// Panics if the wall has less than 14 tiles.
     pub fn take_doras(&mut self) -> DoraSet {
         let doras = self.take_n::<14>().expect("wall has less than 14 tiles");
@@ -152,24 +164,28 @@ where
     pub fn new(wind: Wind, dealer: Wind, number: usize, rng: R) -> Self {
         let mut wall = Wall::new(Jp, rng);
         wall.shuffle();
-        let hands = wall.draw_init::<4, 13>();
+        let hands = wall.draw_init::<4, HAND_SIZE>();
         let dora_set = wall.take_doras();
         let decks = [
             Deck {
                 hand: Hand::new(hands[0]),
                 melded: Default::default(),
+                bonus: Default::default(),
             },
             Deck {
                 hand: Hand::new(hands[1]),
                 melded: Default::default(),
+                bonus: Default::default(),
             },
             Deck {
                 hand: Hand::new(hands[2]),
                 melded: Default::default(),
+                bonus: Default::default(),
             },
             Deck {
                 hand: Hand::new(hands[3]),
                 melded: Default::default(),
+                bonus: Default::default(),
             },
         ];
         GameRound {
@@ -20,8 +20,12 @@ pub enum Discard {
     Tsumo(TileId),
     Draw,
     Discard(TileId),
+    /// Declare riichi with the discard of the tile.
+    Riichi(TileId),
 }
 pub enum Reaction {
+    /// Let the discard go.
+    Pass,
     Chi(Chi),
     Pon(Peng),
     Kan(Gang),
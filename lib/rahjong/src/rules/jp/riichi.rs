@@ -1,6 +1,7 @@
 use crate::discard::Discard;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Richii {
     pub discard: Discard,
 }
\ No newline at end of file
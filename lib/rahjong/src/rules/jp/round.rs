@@ -1,51 +1,454 @@
 use std::borrow::Cow;
 
-use rand::rngs::ThreadRng;
+use rand::{rngs::StdRng, SeedableRng};
 
 use crate::{
     discard::{Discard, DiscardSet},
     draw::Draw,
-    hand::{Chi, Deck, Gang, Hand, Melded, Peng},
+    hand::{
+        decompose::{FaceCounts, FACE_COUNT, ORPHANS},
+        Chi, Deck, Gang, Hand, Melded, Peng,
+    },
     player::{Player, WindSet},
-    tile::{TileId, Wind},
+    tile::{TileFace, TileId, TileIndex, Wind, NORTH},
     wall::Wall,
+    Tiles,
 };
 
 use super::{
     riichi::Richii,
-    win::{Chankan, Ron, Tsumo, Win},
+    sanma::SanmaPayment,
+    win::{Agari, Chankan, Ron, Tsumo, Win},
+    yaku::{self, Score, WinContext},
     DoraSet, Jp,
 };
 
-pub struct Round {
+/// A hand of riichi mahjong on a table of `N` seats, `S` is the tile set of the wall.
+pub struct Round<S = Jp, const N: usize = 4> {
     dealer: Wind,
-    wall: Wall<Jp, ThreadRng>,
-    decks: WindSet<Deck>,
+    wall: Wall<S, StdRng>,
+    seed: u64,
+    decks: WindSet<Deck, N>,
     discards: DiscardSet,
     dora_set: Option<DoraSet>,
-    riichi: WindSet<Option<Richii>>,
+    riichi: WindSet<Option<Richii>, N>,
     draw: Option<Draw>,
     state: RoundState,
-    action_history: Vec<Action>,
-    reaction: WindSet<Option<Action>>,
+    action_history: Vec<(Player, Action)>,
+    reaction: WindSet<Option<Action>, N>,
+    options: RoundOptions,
+    round_wind: Wind,
+    honba: u8,
+    /// Riichi sticks left on the table by earlier hands.
+    deposits: u8,
+    /// Seats that let a win go by, until their next discard or for the rest of a riichi.
+    furiten: WindSet<bool, N>,
+    /// The drawn tile replaced a kan.
+    rinshan: bool,
+    end: Option<RoundEnd<N>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoundOptions {
+    pub chi: bool,
+    /// North is set aside as a bonus dora with a replacement draw.
+    pub nukidora: bool,
+    /// Who covers the missing seat on a tsumo, only with three seats.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sanma_payment: SanmaPayment,
+}
+
+impl Default for RoundOptions {
+    fn default() -> Self {
+        Self {
+            chi: true,
+            nukidora: false,
+            sanma_payment: SanmaPayment::default(),
+        }
+    }
+}
+
+/// How a round ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RoundEnd<const N: usize = 4> {
+    /// The wall ran out.
+    Exhausted,
+    /// Kyuushu kyuuhai, the seat showed nine different terminals and honors on its first turn.
+    Aborted(Player),
+    /// One seat won, or more on the same tile.
+    Won(Vec<Agari<N>>),
+}
+
+impl<const N: usize> RoundEnd<N> {
+    /// The points every seat won or lost in all the wins, nothing for a draw.
+    pub fn payments(&self) -> WindSet<i32, N> {
+        let mut payments = WindSet::default();
+        if let RoundEnd::Won(wins) = self {
+            for win in wins {
+                for (seat, pay) in win.payments.iter() {
+                    *payments.get_mut(seat) += pay;
+                }
+            }
+        }
+        payments
+    }
 }
 
 #[derive(Debug, Clone)]
-pub struct PlayerSight {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlayerSight<const N: usize = 4> {
     pub dealer: Wind,
     pub wall_rest: usize,
     pub self_deck: Deck,
-    pub other_hand_size: WindSet<u8>,
-    pub other_hand_melded: WindSet<Melded>,
+    pub other_hand_size: WindSet<u8, N>,
+    pub other_hand_melded: WindSet<Melded, N>,
+    /// Bonus tiles set aside by every seat, these are public.
+    pub bonus: WindSet<Vec<TileId>, N>,
     pub discards: DiscardSet,
     pub dora_indicators: Vec<TileId>,
-    pub riichi: WindSet<Option<Richii>>,
+    pub riichi: WindSet<Option<Richii>, N>,
     pub draw: Option<Draw>,
     pub to_discard: Option<Player>,
 }
 
 impl Round {
-    pub fn player_sight(&self, player: Player) -> PlayerSight {
+    pub fn new(dealer: Wind) -> Self {
+        Self::with_options(Jp, dealer, RoundOptions::default())
+    }
+}
+
+impl<S: Tiles, const N: usize> Round<S, N> {
+    pub fn with_options(tiles: S, dealer: Wind, options: RoundOptions) -> Self {
+        Self::with_seed(tiles, dealer, options, rand::random())
+    }
+    /// The wall is shuffled from `seed`, the same seed deals the same round again.
+    pub fn with_seed(tiles: S, dealer: Wind, options: RoundOptions, seed: u64) -> Self {
+        Self {
+            dealer,
+            wall: Wall::new(tiles, StdRng::seed_from_u64(seed)),
+            seed,
+            decks: Default::default(),
+            discards: Default::default(),
+            dora_set: None,
+            riichi: Default::default(),
+            draw: None,
+            state: RoundState::Init,
+            action_history: Default::default(),
+            reaction: Default::default(),
+            options,
+            round_wind: Wind::East,
+            honba: 0,
+            deposits: 0,
+            furiten: Default::default(),
+            rinshan: false,
+            end: None,
+        }
+    }
+    /// Play the round in `round_wind` with the honba and riichi sticks of the hands before.
+    pub fn with_counters(mut self, round_wind: Wind, honba: u8, deposits: u8) -> Self {
+        self.round_wind = round_wind;
+        self.honba = honba;
+        self.deposits = deposits;
+        self
+    }
+    pub fn round_wind(&self) -> Wind {
+        self.round_wind
+    }
+    /// How the round ended, `None` until it did.
+    pub fn end(&self) -> Option<&RoundEnd<N>> {
+        self.end.as_ref()
+    }
+    pub fn options(&self) -> &RoundOptions {
+        &self.options
+    }
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    pub fn dealer(&self) -> Wind {
+        self.dealer
+    }
+    pub fn state(&self) -> &RoundState {
+        &self.state
+    }
+    /// The tile drawn by the seat to discard, if they haven't played it yet.
+    pub fn draw(&self) -> Option<Draw> {
+        self.draw
+    }
+    pub fn is_end(&self) -> bool {
+        matches!(self.state, RoundState::End)
+    }
+    /// Every action applied so far, in order.
+    pub fn history(&self) -> &[(Player, Action)] {
+        &self.action_history
+    }
+    /// The 1000 points every seat that declared riichi put on the table.
+    pub fn riichi_payments(&self) -> WindSet<i32, N> {
+        self.riichi
+            .iter()
+            .map(|(p, r)| (p, -1000 * r.is_some() as i32))
+            .collect()
+    }
+    /// Seats whose hand is one tile from complete.
+    pub fn tenpai(&self) -> Vec<Player> {
+        self.decks
+            .iter()
+            .filter(|(_, d)| !waits(&d.hand.face_counts()).is_empty())
+            .map(|(p, _)| p)
+            .collect()
+    }
+    /// The noten penalty of an exhaustive draw, see [`noten_payments`].
+    pub fn noten_payments(&self) -> WindSet<i32, N> {
+        noten_payments(&self.tenpai())
+    }
+    /// What a seat that can't decide does: discard the drawn tile, or pass on a discard.
+    pub fn default_action(&self, player: Player) -> Option<Action> {
+        match &self.state {
+            RoundState::WaitDiscard(p) if *p == player => match self.draw {
+                Some(draw) => Some(Action::Discard(player.discard(draw.tile()))),
+                None => self.legal_actions(player).into_iter().next(),
+            },
+            RoundState::WaitDiscardReaction(_) | RoundState::WaitGanReaction(..)
+                if self.reaction.get(player).is_none() =>
+            {
+                Some(Action::Pass)
+            }
+            _ => None,
+        }
+    }
+    /// What `player` may do now, empty when nothing is expected from them.
+    pub fn legal_actions(&self, player: Player) -> Vec<Action> {
+        match &self.state {
+            RoundState::WaitDiscard(p) if *p == player => self.turn_actions(player),
+            RoundState::WaitDiscardReaction(discard) if self.reaction.get(player).is_none() => {
+                let mut actions = vec![Action::Pass];
+                let hand = &self.decks.get(player).hand;
+                let claim = discard.tile();
+                if self.can_ron(player, claim, false) {
+                    actions.push(Action::Ron(Ron { discard: claim }));
+                }
+                // nothing is called in riichi or on the last discard
+                if self.riichi.get(player).is_some() || self.wall.len() == 0 {
+                    return actions;
+                }
+                if hand.can_peng(claim) {
+                    let mut same = hand.tiles.iter().filter(|t| t.face() == claim.face());
+                    if let (Some(a), Some(b)) = (same.next(), same.next()) {
+                        actions.push(Action::Pon(Peng {
+                            claim: *discard,
+                            tiles: [a, b],
+                        }));
+                    }
+                }
+                if hand.can_gang(claim) && self.can_kan() {
+                    actions.push(Action::Kan(Gang::MingGang { claim }));
+                }
+                if self.options.chi && discard.source().next_in(N) == player {
+                    actions.extend(
+                        hand.chi_options(claim)
+                            .iter()
+                            .filter_map(|option| option.chi(*discard, hand))
+                            .map(Action::Chi),
+                    );
+                }
+                actions
+            }
+            RoundState::WaitGanReaction(from, gang) if self.reaction.get(player).is_none() => {
+                let mut actions = vec![Action::Pass];
+                let Gang::JiaGang { claim, .. } = gang else {
+                    return actions;
+                };
+                if self.can_ron(player, *claim, true) {
+                    actions.push(Action::Chankan(Chankan {
+                        tile: *claim,
+                        from: *from,
+                    }));
+                }
+                actions
+            }
+            _ => Vec::new(),
+        }
+    }
+    /// The discards, then riichi, tsumo, kans, nukidora and the abortive draw.
+    fn turn_actions(&self, player: Player) -> Vec<Action> {
+        let deck = self.decks.get(player);
+        let tiles: Vec<TileId> = deck
+            .hand
+            .tiles
+            .iter()
+            .chain(self.draw.map(|d| d.tile()))
+            .collect();
+        let in_riichi = self.riichi.get(player).is_some();
+        let mut actions: Vec<Action> = match (in_riichi, self.draw) {
+            // the drawn tile is the only one to let go of in riichi
+            (true, Some(draw)) => vec![Action::Discard(player.discard(draw.tile()))],
+            _ => tiles
+                .iter()
+                .map(|t| Action::Discard(player.discard(*t)))
+                .collect(),
+        };
+        let Some(draw) = self.draw else {
+            return actions;
+        };
+        let counts = FaceCounts::new(tiles.iter().copied());
+        if !in_riichi && deck.melded.is_concealed() && self.wall.len() >= N {
+            let mut faces = Vec::new();
+            for tile in &tiles {
+                let mut rest = counts;
+                rest.remove(tile.face());
+                if !faces.contains(&tile.face()) && !waits(&rest).is_empty() {
+                    faces.push(tile.face());
+                    actions.push(Action::Richii(Richii {
+                        discard: player.discard(*tile),
+                    }));
+                }
+            }
+        }
+        if self.score(player, draw.tile(), WinKind::Tsumo).is_some() {
+            actions.push(Action::Tsumo(Tsumo { tile: draw }));
+        }
+        if self.can_kan() {
+            for (face, count) in counts.iter() {
+                if count < 4 {
+                    continue;
+                }
+                // a kan in riichi may not change the waits
+                if in_riichi {
+                    let mut rest = counts;
+                    (0..4).for_each(|_| {
+                        rest.remove(face);
+                    });
+                    let before = waits(&deck.hand.face_counts());
+                    if draw.tile().face() != face || waits(&rest) != before {
+                        continue;
+                    }
+                }
+                let displayed = tiles.iter().find(|t| t.face() == face).copied();
+                actions.extend(displayed.map(|displayed| Action::Kan(Gang::AnGang { displayed })));
+            }
+            if !in_riichi {
+                for peng in &deck.melded.peng {
+                    let claim = tiles.iter().find(|t| t.face() == peng.claim.tile().face());
+                    actions.extend(claim.map(|claim| {
+                        Action::Kan(Gang::JiaGang {
+                            peng: *peng,
+                            claim: *claim,
+                        })
+                    }));
+                }
+            }
+        }
+        if self.options.nukidora {
+            actions.extend(
+                tiles
+                    .iter()
+                    .filter(|t| t.face() == NORTH)
+                    .copied()
+                    .map(Action::Nukidora),
+            );
+        }
+        let orphans = ORPHANS.iter().filter(|f| counts.get(**f) > 0).count();
+        if orphans >= 9 && self.first_turn(player) {
+            actions.push(Action::Ryukyoku);
+        }
+        actions
+    }
+    /// No call was made yet and `player` has not discarded.
+    fn first_turn(&self, player: Player) -> bool {
+        let called = self
+            .action_history
+            .iter()
+            .any(|(_, action)| matches!(action, Action::Chi(_) | Action::Pon(_) | Action::Kan(_)));
+        !called && self.discards.iter().all(|d| d.source() != player)
+    }
+    fn can_kan(&self) -> bool {
+        self.wall.len() > 0 && self.dora_set.as_ref().is_some_and(DoraSet::can_kan)
+    }
+    /// Whether `player` may win on a tile of someone else, robbed from a kan or discarded.
+    fn can_ron(&self, player: Player, tile: TileId, chankan: bool) -> bool {
+        let kind = if chankan {
+            WinKind::Chankan
+        } else {
+            WinKind::Ron
+        };
+        self.score(player, tile, kind).is_some() && !self.is_furiten(player)
+    }
+    /// A seat can't ron on its waits if it discarded one of them, or let a win go by.
+    pub fn is_furiten(&self, player: Player) -> bool {
+        if *self.furiten.get(player) {
+            return true;
+        }
+        let waits = waits(&self.decks.get(player).hand.face_counts());
+        self.discards
+            .iter()
+            .any(|d| d.source() == player && waits.contains(&d.tile().face()))
+    }
+    fn seat_wind(&self, player: Player) -> Wind {
+        Wind::from_index((player.wind().as_index() + N - self.dealer.as_index()) % N)
+    }
+    /// The hand of `player` with `tile` scored, `None` without a win or a yaku.
+    fn score(&self, player: Player, tile: TileId, kind: WinKind) -> Option<Score> {
+        let mut deck = self.decks.get(player).clone();
+        deck.hand.tiles.insert(tile);
+        let riichi = self.riichi.get(player).is_some();
+        let tsumo = kind == WinKind::Tsumo;
+        let ctx = WinContext {
+            seat_wind: self.seat_wind(player),
+            round_wind: self.round_wind,
+            win_tile: tile.face(),
+            tsumo,
+            riichi,
+            last_tile: self.wall.len() == 0,
+            rinshan: tsumo && self.rinshan,
+            chankan: kind == WinKind::Chankan,
+            dora: self.dora(&deck, riichi),
+        };
+        yaku::evaluate(&deck, &ctx)
+    }
+    /// Dora and red fives in the hand and the melds, ura dora in riichi, and every nukidora.
+    fn dora(&self, deck: &Deck, riichi: bool) -> u32 {
+        let Some(dora_set) = &self.dora_set else {
+            return 0;
+        };
+        let mut indicators = dora_set.shown_indicators();
+        if riichi {
+            indicators.extend(dora_set.ura_indicators());
+        }
+        let mut tiles: Vec<TileId> = deck
+            .hand
+            .tiles
+            .iter()
+            .chain(deck.bonus.iter().copied())
+            .collect();
+        for chi in &deck.melded.chi {
+            tiles.extend(chi.tiles.iter().copied().chain([chi.claim.tile()]));
+        }
+        for peng in &deck.melded.peng {
+            tiles.extend(peng.tiles.iter().copied().chain([peng.claim.tile()]));
+        }
+        for gang in &deck.melded.gang {
+            tiles.extend(
+                TileIndex::enumerate().map(|index| TileId::from_face_idx(gang.face(), index)),
+            );
+        }
+        let dora = tiles
+            .iter()
+            .map(|tile| {
+                let shown = indicators
+                    .iter()
+                    .filter(|i| S::dora_successor(i.face()) == tile.face());
+                shown.count() as u32 + tile.is_red_dora() as u32
+            })
+            .sum::<u32>();
+        let nukidora = if self.options.nukidora {
+            deck.bonus.len() as u32
+        } else {
+            0
+        };
+        dora + nukidora
+    }
+    pub fn player_sight(&self, player: Player) -> PlayerSight<N> {
         let dealer = self.dealer;
         let wall_rest = self.wall.len();
         let self_deck = self.decks.get(player).clone();
@@ -61,6 +464,11 @@ impl Round {
             .filter(|(p, _)| *p != player)
             .map(|(p, d)| (p, d.melded.clone()))
             .collect();
+        let bonus = self
+            .decks
+            .iter()
+            .map(|(p, d)| (p, d.bonus.clone()))
+            .collect();
         let discards = self.discards.clone();
         let dora_indicators = self
             .dora_set
@@ -68,7 +476,9 @@ impl Round {
             .map(|d| d.shown_indicators())
             .unwrap_or_default();
         let riichi = self.riichi.clone();
-        let draw = self.draw;
+        let draw = self
+            .draw
+            .filter(|_| matches!(self.state, RoundState::WaitDiscard(p) if p == player));
         let to_discard = match &self.state {
             RoundState::WaitDiscard(p) => Some(*p),
             _ => None,
@@ -79,6 +489,7 @@ impl Round {
             self_deck,
             other_hand_size,
             other_hand_melded,
+            bonus,
             discards,
             dora_indicators,
             riichi,
@@ -86,32 +497,18 @@ impl Round {
             to_discard,
         }
     }
-    pub fn new(dealer: Wind) -> Self {
-        Self {
-            dealer,
-            wall: Wall::new(Jp, Default::default()),
-            decks: Default::default(),
-            discards: Default::default(),
-            dora_set: None,
-            riichi: Default::default(),
-            draw: None,
-            state: RoundState::Init,
-            action_history: Default::default(),
-            reaction: Default::default(),
-        }
-    }
     pub fn start(&mut self) -> Result<(), Reason> {
         self.wall.shuffle();
-        let hands = self.wall.draw_init::<4, 13>();
+        let hands = self.wall.deal::<N>(S::HAND_SIZE);
         self.dora_set.replace(self.wall.take_doras());
         for (i, hand) in hands.into_iter().enumerate() {
-            self.decks.insert(
-                Wind::from_index(i),
-                Deck {
-                    hand: Hand::new(hand),
-                    melded: Default::default(),
-                },
-            );
+            let mut deck = Deck {
+                hand: Hand::new(hand),
+                melded: Default::default(),
+                bonus: Default::default(),
+            };
+            self.wall.replace_bonus(&mut deck);
+            self.decks.insert(Wind::from_index(i), deck);
         }
         self.draw_next(self.dealer.into());
         Ok(())
@@ -132,25 +529,297 @@ impl Round {
         }
         None
     }
+    pub fn someone_chi(&self) -> Option<(Player, Chi)> {
+        for (p, a) in self.reaction.iter() {
+            if let Some(Action::Chi(chi)) = a {
+                return Some((p, *chi));
+            }
+        }
+        None
+    }
+    /// Once every seat reacted to `discard`, a ron goes before a pon or a kan, which go
+    /// before a chi, and the next seat draws if nobody called.
+    fn resolve_reaction(&mut self, discard: Discard) {
+        if self.reaction.iter().any(|(_, r)| r.is_none()) {
+            return;
+        }
+        let from = discard.source();
+        let wins = self.winners(from, |action| match action {
+            Action::Ron(ron) => Some(Win::Ron {
+                discard: ron.discard,
+            }),
+            _ => None,
+        });
+        if !wins.is_empty() {
+            // a riichi is only paid for once its discard goes through
+            if self
+                .riichi
+                .get(from)
+                .as_ref()
+                .is_some_and(|r| r.discard == discard)
+            {
+                self.riichi.insert(from, None);
+            }
+            self.settle(wins);
+            return;
+        }
+        let caller = if let Some((player, pon)) = self.someone_pon() {
+            let deck = self.decks.get_mut(player);
+            deck.peng(pon).expect("the pon was legal");
+            player
+        } else if let Some((player, gang)) = self.someone_kan() {
+            self.decks
+                .get_mut(player)
+                .gang(gang)
+                .expect("the kan was legal");
+            self.clear_reaction();
+            self.draw_rinshan(player);
+            return;
+        } else if let Some((player, chi)) = self.someone_chi() {
+            let deck = self.decks.get_mut(player);
+            deck.chi(chi).expect("the chi was legal");
+            player
+        } else {
+            self.draw_next(from.next_in(N));
+            return;
+        };
+        self.clear_reaction();
+        self.draw = None;
+        self.state = RoundState::WaitDiscard(caller);
+    }
+    pub fn someone_kan(&self) -> Option<(Player, Gang)> {
+        for (p, a) in self.reaction.iter() {
+            if let Some(Action::Kan(gang)) = a {
+                return Some((p, *gang));
+            }
+        }
+        None
+    }
+    /// Once every seat reacted to the added kan of `from`, it is robbed or completed.
+    fn resolve_gang(&mut self, from: Player, gang: Gang) {
+        if self.reaction.iter().any(|(_, r)| r.is_none()) {
+            return;
+        }
+        let wins = self.winners(from, |action| match action {
+            Action::Chankan(chankan) => Some(Win::ChanKan {
+                tile: chankan.tile,
+                from: chankan.from,
+            }),
+            _ => None,
+        });
+        if !wins.is_empty() {
+            self.settle(wins);
+            return;
+        }
+        self.clear_reaction();
+        self.decks
+            .get_mut(from)
+            .gang(gang)
+            .expect("the kan was legal");
+        self.draw_rinshan(from);
+    }
+    /// The seats that won on the tile of `from`, in turn order after them.
+    fn winners(
+        &self,
+        from: Player,
+        win: impl Fn(&Action) -> Option<Win>,
+    ) -> Vec<(Player, Win, Option<Player>)> {
+        let mut player = from.next_in(N);
+        let mut wins = Vec::new();
+        while player != from {
+            if let Some(win) = self.reaction.get(player).as_ref().and_then(&win) {
+                wins.push((player, win, Some(from)));
+            }
+            player = player.next_in(N);
+        }
+        wins
+    }
+    /// Reveal a kan dora and draw the replacement tile of a kan.
+    fn draw_rinshan(&mut self, player: Player) {
+        let rinshan = self.dora_set.as_mut().and_then(DoraSet::kan);
+        // the dead wall stays at 14 tiles, the live wall gives one up
+        let _ = self.wall.draw_replacement();
+        self.draw = rinshan.map(Draw::new);
+        self.rinshan = true;
+        self.state = RoundState::WaitDiscard(player);
+        if self.draw.is_none() {
+            self.exhaust();
+        }
+    }
+    /// End the round on the wins, the payments are for the hand, the honba and the riichi
+    /// sticks on the table.
+    fn settle(&mut self, wins: Vec<(Player, Win, Option<Player>)>) {
+        let dealer = Player::from(self.dealer);
+        let honba = self.honba as i32;
+        let declared = self.riichi.iter().filter(|(_, r)| r.is_some()).count() as i32;
+        let mut agari = Vec::new();
+        for (i, (player, win, from)) in wins.into_iter().enumerate() {
+            let (tile, kind) = match &win {
+                Win::Tsumo { tile } => (tile.tile(), WinKind::Tsumo),
+                Win::Ron { discard } => (*discard, WinKind::Ron),
+                Win::ChanKan { tile, .. } => (*tile, WinKind::Chankan),
+            };
+            let score = self.score(player, tile, kind).expect("the win was legal");
+            let first = i == 0;
+            let honba = if first { honba } else { 0 };
+            let mut payments = WindSet::<i32, N>::default();
+            let mut pay = |payer: Player, points: i32| {
+                *payments.get_mut(payer) -= points;
+                *payments.get_mut(player) += points;
+            };
+            let payers = Wind::enumerate()
+                .take(N)
+                .map(Player::from)
+                .filter(|p| *p != player);
+            match from {
+                Some(from) => pay(from, score.ron(player == dealer) as i32 + 300 * honba),
+                None if N == 3 => {
+                    let sanma = self
+                        .options
+                        .sanma_payment
+                        .tsumo(score.base(), player, self.dealer);
+                    for payer in payers {
+                        pay(payer, -sanma.get(payer) + 100 * honba);
+                    }
+                }
+                None => {
+                    for payer in payers {
+                        let dealt = player == dealer || payer == dealer;
+                        pay(payer, score.tsumo(dealt) as i32 + 100 * honba);
+                    }
+                }
+            }
+            if first {
+                for (seat, stick) in self.riichi_payments().iter() {
+                    *payments.get_mut(seat) += stick;
+                }
+                *payments.get_mut(player) += 1000 * (self.deposits as i32 + declared);
+            }
+            agari.push(Agari {
+                player,
+                win,
+                from,
+                score,
+                payments,
+            });
+        }
+        self.clear_reaction();
+        self.end = Some(RoundEnd::Won(agari));
+        self.state = RoundState::End;
+    }
+    fn exhaust(&mut self) {
+        self.end = Some(RoundEnd::Exhausted);
+        self.state = RoundState::End;
+    }
+    /// Draw the next tile for `for_player`, bonus tiles are set aside and replaced.
     pub fn draw_next(&mut self, for_player: Player) {
-        self.draw = self.wall.draw_next();
-        if let Some(next) = self.draw {
+        self.draw = self.wall.draw_with_bonus(self.decks.get_mut(for_player));
+        self.rinshan = false;
+        if self.draw.is_some() {
             self.state = RoundState::WaitDiscard(for_player);
         } else {
-            self.state = RoundState::End;
+            self.exhaust();
+        }
+    }
+    /// Put `discard` on the table and wait for the others to react to it.
+    fn discard(&mut self, discard: Discard) -> Result<(), Reason> {
+        let player = discard.source();
+        let tsumogiri = self.draw.is_some_and(|draw| draw.tile() == discard.tile());
+        self.take_tile(player, discard.tile())?;
+        if tsumogiri {
+            self.discards.add_tsumogiri(discard);
+        } else {
+            self.discards.add(discard);
+        }
+        // a win let go by in riichi stays furiten for the rest of the round
+        if self.riichi.get(player).is_none() {
+            self.furiten.insert(player, false);
+        }
+        self.rinshan = false;
+        self.clear_reaction();
+        self.reaction.get_mut(player).replace(Action::Pass);
+        self.state = RoundState::WaitDiscardReaction(discard);
+        Ok(())
+    }
+    /// Take `tile` out of the hand or the drawn tile, the drawn tile joins the hand otherwise.
+    fn take_tile(&mut self, player: Player, tile: TileId) -> Result<(), Reason> {
+        let deck = self.decks.get_mut(player);
+        if self.draw.is_some_and(|d| d.tile() == tile) {
+            self.draw = None;
+        } else if deck.hand.contains(tile) {
+            deck.hand.remove(tile);
+            if let Some(draw) = self.draw.take() {
+                deck.hand.tiles.insert(draw.tile());
+            }
+        } else {
+            return Err(Reason::invalid_operation("You don't have this tile"));
         }
+        Ok(())
+    }
+}
+/// The noten penalty of an exhaustive draw on a table of `N` seats: the seats that are not
+/// ready pay 1000 points for every seat but one to the ones that are, 3000 on a full table and
+/// 2000 in sanma, split evenly on both sides.
+pub fn noten_payments<const N: usize>(tenpai: &[Player]) -> WindSet<i32, N> {
+    let pool = 1000 * (N as i32 - 1);
+    let ready = tenpai.len() as i32;
+    if ready == 0 || ready == N as i32 {
+        return WindSet::default();
     }
+    WindSet::from_fn(|p| {
+        if tenpai.contains(&p) {
+            pool / ready
+        } else {
+            -pool / (N as i32 - ready)
+        }
+    })
 }
+
+#[derive(Debug, Clone, Copy)]
 pub enum RoundState {
     Init,
     WaitDiscard(Player),
     WaitDiscardReaction(Discard),
-    WaitGanReaction(Gang),
+    /// The added kan of a seat, which the others may rob.
+    WaitGanReaction(Player, Gang),
     End,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WinKind {
+    Tsumo,
+    Ron,
+    Chankan,
+}
+
+/// Faces that complete a hand of `3n + 1` tiles, as a regular hand, seven pairs or thirteen
+/// orphans.
+pub fn waits(counts: &FaceCounts) -> Vec<TileFace> {
+    if counts.len() % 3 != 1 {
+        return Vec::new();
+    }
+    // only a ready hand of some form is worth trying every face on
+    let pairs = counts.iter().filter(|(_, count)| *count >= 2).count();
+    let orphans = ORPHANS.iter().filter(|f| counts.get(**f) > 0).count();
+    let seven_pairs = counts.len() == 13 && pairs == 6;
+    let thirteen_orphans = counts.len() == 13 && orphans >= 12;
+    if !seven_pairs && !thirteen_orphans && counts.shanten() > 0 {
+        return Vec::new();
+    }
+    (0..FACE_COUNT as u8)
+        .map(TileFace)
+        .filter(|face| {
+            let mut counts = *counts;
+            counts.add(*face);
+            counts.get(*face) <= 4
+                && (counts.is_complete() || counts.is_seven_pairs() || counts.is_thirteen_orphans())
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone)]
 #[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Action {
     Pass,
     Discard(Discard),
@@ -161,9 +830,12 @@ pub enum Action {
     Chi(Chi),
     Pon(Peng),
     Richii(Richii),
+    /// Set a north aside as bonus dora, only in rounds with nukidora.
+    Nukidora(TileId),
     Ryukyoku,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Reason {
     kind: ReasonKind,
     expr: Cow<'static, str>,
@@ -177,12 +849,38 @@ impl std::fmt::Debug for Reason {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ReasonKind {
     InvalidOperation = 0x1001,
+    /// Codes from `0x2000` are for the table around the round rather than the rules.
+    UnsupportedVersion = 0x2001,
+    NotSeated = 0x2002,
+    TableFull = 0x2003,
+    UnknownTable = 0x2004,
+    UnknownSession = 0x2005,
+    UnknownRecord = 0x2006,
+}
+
+impl ReasonKind {
+    pub const fn code(self) -> u16 {
+        self as u16
+    }
 }
 
 impl Reason {
+    pub fn new(kind: ReasonKind, expr: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            kind,
+            expr: expr.into(),
+        }
+    }
+    pub fn kind(&self) -> ReasonKind {
+        self.kind
+    }
+    pub fn expr(&self) -> &str {
+        &self.expr
+    }
     pub fn invalid_operation(expr: impl Into<Cow<'static, str>>) -> Self {
         Self {
             kind: ReasonKind::InvalidOperation,
@@ -190,39 +888,223 @@ impl Reason {
         }
     }
 }
-impl Round {
+impl<S: Tiles, const N: usize> Round<S, N> {
     pub fn apply(&mut self, action: &Action, source: Player) -> Result<(), Reason> {
         match (&self.state, action) {
+            (_, Action::Chi(_)) if !self.options.chi => {
+                return Err(Reason::invalid_operation(
+                    "Chi is not allowed in this round",
+                ));
+            }
             (RoundState::WaitDiscard(p), Action::Discard(d)) => {
-                if !(*p == d.source() && *p == source) {
+                let p = *p;
+                if !(p == d.source() && p == source) {
                     return Err(Reason::invalid_operation("Not your turn now"));
                 }
-                if self.decks.get(*p).hand.contains(d.tile()) {
-                    return Err(Reason::invalid_operation("You don't have this tile"));
-                }
-                if let Some(Action::Chi(chi)) = self.action_history.last() {
+                if let Some((_, Action::Chi(chi))) = self.action_history.last() {
                     if chi.claim.tile() == d.tile() {
                         return Err(Reason::invalid_operation("Cannot discard the claimed tile"));
                     }
                 }
-                self.decks.get_mut(*p).hand.remove(d.tile());
-                self.discards.add(*d);
+                if self.riichi.get(p).is_some()
+                    && self.draw.is_some_and(|draw| draw.tile() != d.tile())
+                {
+                    return Err(Reason::invalid_operation(
+                        "Only the drawn tile can go in riichi",
+                    ));
+                }
+                self.discard(*d)?;
+            }
+            (RoundState::WaitDiscard(p), Action::Richii(richii)) => {
+                let p = *p;
+                let face = richii.discard.tile().face();
+                let legal = self.legal_actions(source).iter().any(
+                    |legal| matches!(legal, Action::Richii(r) if r.discard.tile().face() == face),
+                );
+                if !(p == richii.discard.source() && p == source && legal) {
+                    return Err(Reason::invalid_operation("You can't declare riichi now"));
+                }
+                self.discard(richii.discard)?;
+                self.riichi.insert(p, Some(richii.clone()));
+            }
+            (RoundState::WaitDiscard(p), Action::Tsumo(tsumo)) => {
+                let p = *p;
+                if p != source || self.draw != Some(tsumo.tile) {
+                    return Err(Reason::invalid_operation("Not your turn now"));
+                }
+                if self.score(p, tsumo.tile.tile(), WinKind::Tsumo).is_none() {
+                    return Err(Reason::invalid_operation("This hand doesn't win"));
+                }
+                self.settle(vec![(p, Win::Tsumo { tile: tsumo.tile }, None)]);
+            }
+            (RoundState::WaitDiscard(p), Action::Kan(gang)) => {
+                let p = *p;
+                // any copy of the face will do, the round picks the tiles
+                let legal = self
+                    .legal_actions(source)
+                    .into_iter()
+                    .find_map(|legal| match legal {
+                        Action::Kan(legal)
+                            if std::mem::discriminant(&legal) == std::mem::discriminant(gang)
+                                && legal.face() == gang.face() =>
+                        {
+                            Some(legal)
+                        }
+                        _ => None,
+                    });
+                let Some(gang) = legal.filter(|_| p == source) else {
+                    return Err(Reason::invalid_operation("You can't kan this tile"));
+                };
+                if let Some(draw) = self.draw.take() {
+                    self.decks.get_mut(p).hand.tiles.insert(draw.tile());
+                }
                 self.clear_reaction();
-                self.reaction.get_mut(source).replace(Action::Pass);
-                self.state = RoundState::WaitDiscardReaction(*d);
+                self.reaction.get_mut(p).replace(Action::Pass);
+                if let Gang::JiaGang { claim, .. } = gang {
+                    // only a seat that can rob the kan is asked
+                    for other in Wind::enumerate().take(N).map(Player::from) {
+                        if other != p && !self.can_ron(other, claim, true) {
+                            self.reaction.get_mut(other).replace(Action::Pass);
+                        }
+                    }
+                    self.state = RoundState::WaitGanReaction(p, gang);
+                    self.action_history.push((source, Action::Kan(gang)));
+                    self.resolve_gang(p, gang);
+                    return Ok(());
+                }
+                self.decks.get_mut(p).gang(gang).expect("the kan was legal");
+                self.clear_reaction();
+                self.draw_rinshan(p);
+                self.action_history.push((source, Action::Kan(gang)));
+                return Ok(());
+            }
+            (RoundState::WaitDiscard(p), Action::Ryukyoku) => {
+                let p = *p;
+                if p != source
+                    || !self
+                        .legal_actions(p)
+                        .iter()
+                        .any(|a| matches!(a, Action::Ryukyoku))
+                {
+                    return Err(Reason::invalid_operation("You can't call an abortive draw"));
+                }
+                self.end = Some(RoundEnd::Aborted(p));
+                self.state = RoundState::End;
             }
             (RoundState::WaitDiscardReaction(d), Action::Pass) => {
+                let d = *d;
+                if self.reaction.get(source).is_some() {
+                    return Err(Reason::invalid_operation("You already reacted"));
+                }
+                if self.can_ron(source, d.tile(), false) {
+                    self.furiten.insert(source, true);
+                }
+                self.reaction.get_mut(source).replace(Action::Pass);
+                self.resolve_reaction(d);
+            }
+            (RoundState::WaitDiscardReaction(d), Action::Ron(ron)) => {
+                let d = *d;
+                if self.reaction.get(source).is_some()
+                    || ron.discard != d.tile()
+                    || !self.can_ron(source, d.tile(), false)
+                {
+                    return Err(Reason::invalid_operation("You can't ron this tile"));
+                }
+                self.reaction.get_mut(source).replace(action.clone());
+                self.resolve_reaction(d);
+            }
+            (RoundState::WaitDiscardReaction(d), Action::Kan(gang)) => {
+                let d = *d;
+                let offered = self
+                    .legal_actions(source)
+                    .iter()
+                    .any(|legal| matches!(legal, Action::Kan(legal) if legal == gang));
+                if !offered {
+                    return Err(Reason::invalid_operation("You can't kan this tile"));
+                }
+                self.reaction.get_mut(source).replace(action.clone());
+                self.resolve_reaction(d);
+            }
+            (RoundState::WaitDiscardReaction(d), Action::Pon(_) | Action::Chi(_)) => {
+                let d = *d;
+                // any copies of the offered faces will do
+                let (claim, tiles) = match action {
+                    Action::Pon(pon) => (pon.claim, pon.tiles),
+                    Action::Chi(chi) => (chi.claim, chi.tiles),
+                    _ => unreachable!(),
+                };
+                let faces = tiles.map(|t| t.face());
+                let same_call = |legal: &Action| match (legal, action) {
+                    (Action::Pon(Peng { claim: c, tiles: t }), Action::Pon(_))
+                    | (Action::Chi(Chi { claim: c, tiles: t }), Action::Chi(_)) => {
+                        *c == claim && t.map(|t| t.face()) == faces
+                    }
+                    _ => false,
+                };
+                let offered = self.legal_actions(source).iter().any(same_call);
+                let hand = &self.decks.get(source).hand;
+                let in_hand = tiles[0] != tiles[1] && tiles.iter().all(|t| hand.contains(*t));
+                if claim != d || !offered || !in_hand {
+                    return Err(Reason::invalid_operation("You can't call this tile"));
+                }
+                self.reaction.get_mut(source).replace(action.clone());
+                self.resolve_reaction(d);
+            }
+            (RoundState::WaitDiscard(p), Action::Nukidora(tile)) => {
+                let p = *p;
+                if !self.options.nukidora {
+                    return Err(Reason::invalid_operation(
+                        "Nukidora is not allowed in this round",
+                    ));
+                }
+                if p != source {
+                    return Err(Reason::invalid_operation("Not your turn now"));
+                }
+                if tile.face() != NORTH {
+                    return Err(Reason::invalid_operation("Only north can be set aside"));
+                }
+                self.take_tile(p, *tile)?;
+                self.decks.get_mut(p).bonus.push(*tile);
+                self.draw = self.wall.draw_replacement();
+                self.rinshan = true;
+                if self.draw.is_none() {
+                    self.exhaust();
+                }
+            }
+            (RoundState::WaitGanReaction(from, gang), Action::Pass) => {
+                let (from, gang) = (*from, *gang);
+                if self.reaction.get(source).is_some() {
+                    return Err(Reason::invalid_operation("You already reacted"));
+                }
+                self.furiten.insert(source, true);
                 self.reaction.get_mut(source).replace(Action::Pass);
-                if self.all_passed() {
-                    let player = d.source();
-                    self.draw_next(player.next());
+                self.action_history.push((source, action.clone()));
+                self.resolve_gang(from, gang);
+                return Ok(());
+            }
+            (RoundState::WaitGanReaction(from, gang), Action::Chankan(chankan)) => {
+                let (from, gang) = (*from, *gang);
+                let Gang::JiaGang { claim, .. } = gang else {
+                    return Err(Reason::invalid_operation("Only an added kan can be robbed"));
+                };
+                if self.reaction.get(source).is_some()
+                    || chankan.tile != claim
+                    || chankan.from != from
+                    || !self.can_ron(source, claim, true)
+                {
+                    return Err(Reason::invalid_operation("You can't rob this kan"));
                 }
+                self.reaction.get_mut(source).replace(action.clone());
+                self.action_history.push((source, action.clone()));
+                self.resolve_gang(from, gang);
+                return Ok(());
             }
 
             _ => {
                 return Err(Reason::invalid_operation("Rule violation"));
             }
         }
+        self.action_history.push((source, action.clone()));
         Ok(())
     }
 }
@@ -233,6 +1115,214 @@ fn test_round() {
     round.start().unwrap();
     let player_set = round.player_sight(Player::EAST);
     // post player set
-    // 
+    //
     dbg!(player_set);
 }
+
+#[test]
+fn test_calls() {
+    let mut round: Round = Round::with_seed(Jp, Wind::East, RoundOptions::default(), 7);
+    round.start().unwrap();
+    // play default actions until a seat may call, then call
+    let (caller, call) = loop {
+        let call = Wind::enumerate().map(Player::from).find_map(|p| {
+            let call = round
+                .legal_actions(p)
+                .into_iter()
+                .find(|a| matches!(a, Action::Pon(_) | Action::Chi(_)))?;
+            Some((p, call))
+        });
+        if let Some(call) = call {
+            break call;
+        }
+        let (player, action) = Wind::enumerate()
+            .map(Player::from)
+            .find_map(|p| Some((p, round.default_action(p)?)))
+            .expect("the round has not ended");
+        round.apply(&action, player).unwrap();
+    };
+    let hand_size = round.player_sight(caller).self_deck.hand.len();
+    round.apply(&call, caller).unwrap();
+    assert!(round.apply(&Action::Pass, caller).is_err());
+    for p in Wind::enumerate().map(Player::from) {
+        if round.default_action(p).is_some() {
+            round.apply(&Action::Pass, p).unwrap();
+        }
+    }
+    assert!(matches!(round.state(), RoundState::WaitDiscard(p) if *p == caller));
+    assert!(round.draw().is_none());
+    let deck = round.player_sight(caller).self_deck;
+    assert_eq!(deck.hand.len(), hand_size - 2);
+    assert_eq!(deck.melded.peng.len() + deck.melded.chi.len(), 1);
+}
+
+/// A started round where `player` holds `faces` and is to play `draw`, without dora so the
+/// points are those of the yaku.
+#[cfg(test)]
+fn rigged_round(player: Player, faces: &[TileFace], draw: TileFace) -> Round {
+    use crate::tile::TileIndex;
+    let mut round: Round = Round::with_seed(Jp, Wind::East, RoundOptions::default(), 7);
+    round.start().unwrap();
+    round.decks.get_mut(player).hand = Hand::from_faces(faces.iter().copied());
+    round.draw = Some(Draw::new(TileId::from_face_idx(draw, TileIndex::T3)));
+    round.state = RoundState::WaitDiscard(player);
+    round.dora_set = None;
+    round
+}
+
+#[test]
+fn test_tsumo() {
+    use crate::tile::*;
+    // pinfu waiting on 5m or 8m
+    let hand = [C2, C3, C4, D2, D3, D4, B6, B7, B8, C6, C7, D9, D9];
+    let mut round = rigged_round(Player::EAST, &hand, C8).with_counters(Wind::East, 1, 1);
+    let actions = round.legal_actions(Player::EAST);
+    let tsumo = actions
+        .iter()
+        .find(|a| matches!(a, Action::Tsumo(_)))
+        .expect("the hand is complete")
+        .clone();
+    assert!(round.apply(&tsumo, Player::SOUTH).is_err());
+    round.apply(&tsumo, Player::EAST).unwrap();
+    assert!(round.is_end());
+    let Some(RoundEnd::Won(wins)) = round.end() else {
+        panic!("the round was won");
+    };
+    assert_eq!(
+        wins[0].score.yaku,
+        vec![yaku::Yaku::MenzenTsumo, yaku::Yaku::Pinfu]
+    );
+    // 20 fu 2 han is 700 all from the dealer, one honba adds 100 each and the deposit goes
+    // to the winner
+    assert_eq!(
+        round.end().unwrap().payments(),
+        [
+            (Player::EAST, 3400),
+            (Player::SOUTH, -800),
+            (Player::WEST, -800),
+            (Player::NORTH, -800)
+        ]
+        .into_iter()
+        .collect()
+    );
+}
+
+#[test]
+fn test_ron() {
+    use crate::tile::*;
+    let hand = [C2, C3, C4, D2, D3, D4, B6, B7, B8, C6, C7, D9, D9];
+    let mut round = rigged_round(Player::EAST, &[EAST; 13], C8);
+    round.decks.get_mut(Player::SOUTH).hand = Hand::from_faces(hand);
+    let discard = Action::Discard(Player::EAST.discard(round.draw().unwrap().tile()));
+    round.apply(&discard, Player::EAST).unwrap();
+    let ron = round
+        .legal_actions(Player::SOUTH)
+        .into_iter()
+        .find(|a| matches!(a, Action::Ron(_)))
+        .expect("8m completes the hand");
+    assert!(!round.is_furiten(Player::SOUTH));
+    round.apply(&ron, Player::SOUTH).unwrap();
+    // the round waits for the other seats, a ron goes before any call
+    assert!(!round.is_end());
+    round.apply(&Action::Pass, Player::WEST).unwrap();
+    round.apply(&Action::Pass, Player::NORTH).unwrap();
+    let Some(RoundEnd::Won(wins)) = round.end() else {
+        panic!("the round was won");
+    };
+    assert_eq!(wins.len(), 1);
+    assert_eq!(wins[0].from, Some(Player::EAST));
+    // pinfu on a ron is 30 fu 1 han
+    assert_eq!((wins[0].score.han, wins[0].score.fu), (1, 30));
+    assert_eq!(*wins[0].payments.get(Player::EAST), -1000);
+    assert_eq!(*wins[0].payments.get(Player::SOUTH), 1000);
+
+    // letting the same tile go makes the seat furiten
+    let mut round = rigged_round(Player::EAST, &[EAST; 13], C8);
+    round.decks.get_mut(Player::SOUTH).hand = Hand::from_faces(hand);
+    round.apply(&discard, Player::EAST).unwrap();
+    round.apply(&Action::Pass, Player::SOUTH).unwrap();
+    assert!(round.is_furiten(Player::SOUTH));
+}
+
+#[test]
+fn test_kan_and_riichi() {
+    use crate::tile::*;
+    let hand = [C1, C1, C1, C2, C3, C4, D2, D3, D4, B6, B7, B8, D9];
+    let mut round = rigged_round(Player::EAST, &hand, C1);
+    round.dora_set = Some(round.wall.take_doras());
+    let wall = round.wall.len();
+    let kan = round
+        .legal_actions(Player::EAST)
+        .into_iter()
+        .find(|a| matches!(a, Action::Kan(Gang::AnGang { .. })))
+        .expect("four 1m make a kan");
+    round.apply(&kan, Player::EAST).unwrap();
+    let deck = round.player_sight(Player::EAST).self_deck;
+    assert_eq!(deck.melded.gang.len(), 1);
+    assert_eq!(deck.hand.len(), 10);
+    assert!(round.draw().is_some());
+    assert_eq!(round.player_sight(Player::EAST).dora_indicators.len(), 2);
+    assert_eq!(round.wall.len(), wall - 1);
+
+    // letting the rinshan tile go leaves a tanki on 9p
+    let draw = round.draw().unwrap().tile();
+    let riichi = Action::Richii(Richii {
+        discard: Player::EAST.discard(draw),
+    });
+    assert!(round.legal_actions(Player::EAST).iter().any(|a| matches!(
+        a,
+        Action::Richii(r) if r.discard.tile().face() == draw.face()
+    )));
+    round.apply(&riichi, Player::EAST).unwrap();
+    assert_eq!(*round.riichi_payments().get(Player::EAST), -1000);
+    while !matches!(round.state(), RoundState::WaitDiscard(p) if *p == Player::EAST) {
+        let (player, action) = Wind::enumerate()
+            .map(Player::from)
+            .find_map(|p| Some((p, round.default_action(p)?)))
+            .expect("the round has not ended");
+        round.apply(&action, player).unwrap();
+    }
+    // in riichi only the drawn tile can go
+    let discards = round
+        .legal_actions(Player::EAST)
+        .into_iter()
+        .filter(|a| matches!(a, Action::Discard(_)))
+        .count();
+    assert_eq!(discards, 1);
+}
+
+#[test]
+fn test_kyuushu() {
+    use crate::tile::*;
+    let hand = [C1, C9, B1, B9, D1, D9, EAST, SOUTH, WEST, C2, C3, C4, D2];
+    let mut round = rigged_round(Player::EAST, &hand, D3);
+    round.apply(&Action::Ryukyoku, Player::EAST).unwrap();
+    assert_eq!(round.end(), Some(&RoundEnd::Aborted(Player::EAST)));
+    assert_eq!(round.end().unwrap().payments(), WindSet::default());
+}
+
+#[test]
+fn test_sanma_dora_and_nukidora() {
+    use super::sanma::SanmaRound;
+    use crate::tile::*;
+    let mut round = SanmaRound::new_sanma(Wind::East);
+    round.start().unwrap();
+    let hand = [C9, C9, C9, B2, B3, B4, D2, D3, D4, B6, B7, B8, NORTH];
+    round.decks.get_mut(Player::EAST).hand = Hand::from_faces(hand);
+    round.draw = Some(Draw::new(TileId::from_face_idx(D9, TileIndex::T3)));
+    round.state = RoundState::WaitDiscard(Player::EAST);
+    // 2m is out of the wall, a 1m indicator points to 9m
+    round.dora_set.as_mut().unwrap().indicator = TileId::from_face_idx(C1, TileIndex::T0);
+    assert_eq!(round.dora(round.decks.get(Player::EAST), false), 3);
+
+    // the replacement of a nukidora is a rinshan draw, and the north counts as a dora
+    let north = TileId::from_face_idx(NORTH, TileIndex::T0);
+    round.apply(&Action::Nukidora(north), Player::EAST).unwrap();
+    assert!(round.draw().is_some());
+    assert!(round.rinshan);
+    assert_eq!(round.dora(round.decks.get(Player::EAST), false), 4);
+    let draw = round.draw().unwrap().tile();
+    let discard = Action::Discard(Player::EAST.discard(draw));
+    round.apply(&discard, Player::EAST).unwrap();
+    assert!(!round.rinshan);
+}
@@ -0,0 +1,161 @@
+use crate::{
+    player::{Player, WindSet},
+    tile::{TileFace, TileId, Wind, C1, C2, C8, C9},
+    Tiles,
+};
+
+use super::{
+    round::{Round, RoundOptions},
+    JP_TILES,
+};
+
+/// Three-player tiles, 2m to 8m are taken out which leaves 108 tiles.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Jp3;
+
+impl Tiles for Jp3 {
+    fn new_wall(&self) -> Vec<TileId> {
+        JP_TILES
+            .into_iter()
+            .filter(|t| !(C2..=C8).contains(&t.face()))
+            .collect()
+    }
+
+    fn size(&self) -> usize {
+        108
+    }
+
+    fn dora_successor(face: TileFace) -> TileFace {
+        // 2m to 8m are out of the wall, a 1m indicator points to 9m
+        if face == C1 {
+            C9
+        } else {
+            face.successor()
+        }
+    }
+}
+
+pub type SanmaRound = Round<Jp3, 3>;
+
+impl Round<Jp3, 3> {
+    /// A three-player round, there is no chi and north is set aside as nukidora.
+    pub fn new_sanma(dealer: Wind) -> Self {
+        Self::with_options(
+            Jp3,
+            dealer,
+            RoundOptions {
+                chi: false,
+                nukidora: true,
+                ..Default::default()
+            },
+        )
+    }
+}
+
+/// How the share of the missing north seat is handled on a tsumo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SanmaPayment {
+    /// ツモ損, the north share is simply not paid.
+    #[default]
+    TsumoLoss,
+    /// 北家折半, the north share is split between the two payers.
+    NorthBisection,
+}
+
+impl SanmaPayment {
+    /// Tsumo payments from `base` points (`fu * 2^(han + 2)`), every share is rounded up to
+    /// the next 100.
+    pub fn tsumo(self, base: u32, winner: Player, dealer: Wind) -> WindSet<i32, 3> {
+        let round_up = |points: u32| points.div_ceil(100) * 100;
+        let share = |payer: Wind| {
+            if payer == dealer || winner.wind() == dealer {
+                2 * base
+            } else {
+                base
+            }
+        };
+        let north = share(Wind::North);
+        let mut payments = WindSet::<i32, 3>::default();
+        for payer in [Wind::East, Wind::South, Wind::West] {
+            if payer == winner.wind() {
+                continue;
+            }
+            let pay = match self {
+                SanmaPayment::TsumoLoss => round_up(share(payer)),
+                SanmaPayment::NorthBisection => round_up(share(payer) + north / 2),
+            } as i32;
+            *payments.get_mut(payer) -= pay;
+            *payments.get_mut(winner) += pay;
+        }
+        payments
+    }
+}
+
+#[test]
+fn test_sanma_round() {
+    use super::round::Action;
+    use crate::tile::NORTH;
+    assert_eq!(Jp3.new_wall().len(), Jp3.size());
+    let mut round = SanmaRound::new_sanma(Wind::East);
+    round.start().unwrap();
+    let sight = round.player_sight(Player::EAST);
+    assert_eq!(sight.self_deck.hand.len(), 13);
+    assert_eq!(sight.other_hand_size.seats(), 3);
+    let north = sight
+        .self_deck
+        .hand
+        .tiles
+        .iter()
+        .chain(sight.draw.map(|d| d.tile()))
+        .find(|t| t.face() == NORTH);
+    if let Some(north) = north {
+        round.apply(&Action::Nukidora(north), Player::EAST).unwrap();
+        let sight = round.player_sight(Player::EAST);
+        assert_eq!(sight.bonus.get(Wind::East), &vec![north]);
+        assert_eq!(sight.self_deck.hand.len(), 13);
+        assert!(sight.draw.is_some());
+    }
+    let discard = round.player_sight(Player::EAST).draw.unwrap().tile();
+    round
+        .apply(
+            &Action::Discard(Player::EAST.discard(discard)),
+            Player::EAST,
+        )
+        .unwrap();
+    round.apply(&Action::Pass, Player::SOUTH).unwrap();
+    round.apply(&Action::Pass, Player::WEST).unwrap();
+    assert_eq!(
+        round.player_sight(Player::SOUTH).to_discard,
+        Some(Player::SOUTH)
+    );
+}
+
+#[test]
+fn test_sanma_payment() {
+    // a 1000 point non-dealer tsumo: 500 from the dealer, 300 from the other non-dealer
+    let loss = SanmaPayment::TsumoLoss.tsumo(240, Player::SOUTH, Wind::East);
+    assert_eq!(*loss.get(Wind::East), -500);
+    assert_eq!(*loss.get(Wind::West), -300);
+    assert_eq!(*loss.get(Wind::South), 800);
+    let bisection = SanmaPayment::NorthBisection.tsumo(240, Player::SOUTH, Wind::East);
+    assert_eq!(*bisection.get(Wind::East), -600);
+    assert_eq!(*bisection.get(Wind::West), -400);
+    assert_eq!(bisection.iter().map(|(_, p)| *p).sum::<i32>(), 0);
+}
+
+#[test]
+fn test_sanma_noten_payments() {
+    use super::round::noten_payments;
+    let one = noten_payments::<3>(&[Player::EAST]);
+    assert_eq!(*one.get(Wind::East), 2000);
+    assert_eq!(*one.get(Wind::South), -1000);
+    assert_eq!(*one.get(Wind::West), -1000);
+    let two = noten_payments::<3>(&[Player::EAST, Player::WEST]);
+    assert_eq!(*two.get(Wind::East), 1000);
+    assert_eq!(*two.get(Wind::South), -2000);
+    // a full table splits 3000
+    let four = noten_payments::<4>(&[Player::EAST]);
+    assert_eq!(*four.get(Wind::East), 3000);
+    assert_eq!(*four.get(Wind::North), -1000);
+}
@@ -1,24 +1,44 @@
-use crate::{draw::Draw, hand::Deck, player::Player, tile::TileId};
+use crate::{
+    draw::Draw,
+    player::{Player, WindSet},
+    tile::TileId,
+};
 
-#[derive(Debug, Clone)]
+use super::yaku::Score;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Win {
     ChanKan { tile: TileId, from: Player },
     Ron { discard: TileId },
     Tsumo { tile: Draw },
 }
-#[derive(Debug, Clone)]
-
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tsumo {
     pub tile: Draw,
 }
-#[derive(Debug, Clone)]
-
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ron {
     pub discard: TileId,
 }
-#[derive(Debug, Clone)]
-
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chankan {
     pub tile: TileId,
     pub from: Player,
 }
+
+/// A win at the end of a round and what it was worth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Agari<const N: usize = 4> {
+    pub player: Player,
+    pub win: Win,
+    /// Who dealt in, `None` for a tsumo.
+    pub from: Option<Player>,
+    pub score: Score,
+    /// The hand, the honba and the riichi deposits, which only go to the first winner.
+    pub payments: WindSet<i32, N>,
+}
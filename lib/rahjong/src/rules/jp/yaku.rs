@@ -0,0 +1,571 @@
+//! Yaku, fu and points of a winning riichi hand.
+use crate::{
+    hand::{
+        decompose::{FaceCounts, Meld},
+        Deck, Gang,
+    },
+    tile::{Honer, Num, SuitKind, TileFace, Wind, GREEN, RED, WHITE},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Yaku {
+    Riichi,
+    MenzenTsumo,
+    Tanyao,
+    Pinfu,
+    Iipeikou,
+    Ryanpeikou,
+    Haku,
+    Hatsu,
+    Chun,
+    SeatWind,
+    RoundWind,
+    /// A tsumo on the last tile of the wall.
+    Haitei,
+    /// A ron on the last discard.
+    Houtei,
+    Rinshan,
+    Chankan,
+    Chiitoitsu,
+    Toitoi,
+    Sanankou,
+    Sanshoku,
+    Ittsu,
+    Chanta,
+    Junchan,
+    Honroutou,
+    Shousangen,
+    Honitsu,
+    Chinitsu,
+    KokushiMusou,
+    Suuankou,
+    Daisangen,
+    Shousuushii,
+    Daisuushii,
+    Tsuuiisou,
+    Ryuuiisou,
+    Chinroutou,
+}
+
+impl Yaku {
+    /// The han in a concealed or an open hand, 0 for a yaku that needs a concealed hand.
+    pub const fn han(self, concealed: bool) -> u32 {
+        let closed = concealed as u32;
+        match self {
+            Yaku::Riichi | Yaku::MenzenTsumo | Yaku::Pinfu | Yaku::Iipeikou => closed,
+            Yaku::Tanyao
+            | Yaku::Haku
+            | Yaku::Hatsu
+            | Yaku::Chun
+            | Yaku::SeatWind
+            | Yaku::RoundWind
+            | Yaku::Haitei
+            | Yaku::Houtei
+            | Yaku::Rinshan
+            | Yaku::Chankan => 1,
+            Yaku::Chiitoitsu
+            | Yaku::Toitoi
+            | Yaku::Sanankou
+            | Yaku::Honroutou
+            | Yaku::Shousangen => 2,
+            Yaku::Sanshoku | Yaku::Ittsu | Yaku::Chanta => 1 + closed,
+            Yaku::Junchan | Yaku::Honitsu => 2 + closed,
+            Yaku::Ryanpeikou => 3 * closed,
+            Yaku::Chinitsu => 5 + closed,
+            Yaku::KokushiMusou
+            | Yaku::Suuankou
+            | Yaku::Daisangen
+            | Yaku::Shousuushii
+            | Yaku::Daisuushii
+            | Yaku::Tsuuiisou
+            | Yaku::Ryuuiisou
+            | Yaku::Chinroutou => 13,
+        }
+    }
+    pub const fn is_yakuman(self) -> bool {
+        matches!(
+            self,
+            Yaku::KokushiMusou
+                | Yaku::Suuankou
+                | Yaku::Daisangen
+                | Yaku::Shousuushii
+                | Yaku::Daisuushii
+                | Yaku::Tsuuiisou
+                | Yaku::Ryuuiisou
+                | Yaku::Chinroutou
+        )
+    }
+}
+
+/// What the round knows about a win beyond the tiles.
+#[derive(Debug, Clone, Copy)]
+pub struct WinContext {
+    pub seat_wind: Wind,
+    pub round_wind: Wind,
+    pub win_tile: TileFace,
+    pub tsumo: bool,
+    pub riichi: bool,
+    /// The wall is empty, the win is haitei or houtei.
+    pub last_tile: bool,
+    /// The tile was drawn after a kan.
+    pub rinshan: bool,
+    /// The tile was robbed from an added kan.
+    pub chankan: bool,
+    /// Dora, ura dora, red fives and nukidora in the hand, they only count with a yaku.
+    pub dora: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Score {
+    pub yaku: Vec<Yaku>,
+    pub dora: u32,
+    /// The han of the yaku and the dora, 13 for each yakuman.
+    pub han: u32,
+    pub fu: u32,
+}
+
+impl Score {
+    /// The base points, `fu * 2^(han + 2)` up to mangan and the limits above it.
+    pub fn base(&self) -> u32 {
+        let yakuman = self.yaku.iter().filter(|y| y.is_yakuman()).count() as u32;
+        if yakuman > 0 {
+            return 8000 * yakuman;
+        }
+        match self.han {
+            0..=4 => (self.fu * 2u32.pow(self.han + 2)).min(2000),
+            5 => 2000,
+            6 | 7 => 3000,
+            8..=10 => 4000,
+            11 | 12 => 6000,
+            _ => 8000,
+        }
+    }
+    /// What the discarder pays for a ron of the dealer or of another seat.
+    pub fn ron(&self, dealer: bool) -> u32 {
+        round_up(self.base() * if dealer { 6 } else { 4 })
+    }
+    /// What a seat pays for a tsumo, `dealer` when either the winner or the payer is the
+    /// dealer.
+    pub fn tsumo(&self, dealer: bool) -> u32 {
+        round_up(self.base() * if dealer { 2 } else { 1 })
+    }
+}
+
+/// Round up to the next 100 points.
+pub fn round_up(points: u32) -> u32 {
+    points.div_ceil(100) * 100
+}
+
+/// A set of the hand, as the win is read.
+#[derive(Debug, Clone, Copy)]
+struct Set {
+    meld: Meld,
+    /// A triplet that was not called and not completed by a ron.
+    concealed: bool,
+    kan: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wait {
+    Ryanmen,
+    Kanchan,
+    Penchan,
+    Tanki,
+    Shanpon,
+}
+
+/// Score a winning hand, `deck.hand` holds the winning tile. `None` when the hand is not
+/// complete or has no yaku.
+pub fn evaluate(deck: &Deck, ctx: &WinContext) -> Option<Score> {
+    let counts = deck.hand.face_counts();
+    let concealed = deck.melded.is_concealed();
+    let mut scores = Vec::new();
+    if deck.melded.is_empty() && counts.is_thirteen_orphans() {
+        scores.push(score(vec![Yaku::KokushiMusou], 0, ctx, true));
+    }
+    if deck.melded.is_empty() && counts.is_seven_pairs() {
+        scores.push(seven_pairs(&counts, ctx));
+    }
+    let open = open_sets(deck);
+    for d in counts.decompose() {
+        if d.melds.len() + open.len() != 4 {
+            continue;
+        }
+        let win = ctx.win_tile;
+        let pair = (d.pair == win).then_some(None);
+        let placed = d
+            .melds
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.faces().contains(&win));
+        for place in pair.into_iter().chain(placed.map(|(i, _)| Some(i))) {
+            let mut sets: Vec<Set> = (d.melds.iter().enumerate())
+                .map(|(i, meld)| Set {
+                    meld: *meld,
+                    concealed: ctx.tsumo || Some(i) != place,
+                    kan: false,
+                })
+                .collect();
+            let wait = match place.map(|i| d.melds[i]) {
+                None => Wait::Tanki,
+                Some(Meld::Triplet(_)) => Wait::Shanpon,
+                Some(Meld::Sequence(low)) => sequence_wait(low, win),
+            };
+            sets.extend_from_slice(&open);
+            scores.push(regular(d.pair, &sets, wait, concealed, ctx));
+        }
+    }
+    scores
+        .into_iter()
+        .filter(|s| !s.yaku.is_empty())
+        .max_by_key(|s| (s.base(), s.han, s.fu))
+}
+
+fn open_sets(deck: &Deck) -> Vec<Set> {
+    let chi = (deck.melded.chi.iter()).map(|chi| Set {
+        meld: chi.meld(),
+        concealed: false,
+        kan: false,
+    });
+    let pon = (deck.melded.peng.iter()).map(|pon| Set {
+        meld: Meld::Triplet(pon.claim.tile().face()),
+        concealed: false,
+        kan: false,
+    });
+    let kan = (deck.melded.gang.iter()).map(|gang| Set {
+        meld: Meld::Triplet(gang.face()),
+        concealed: matches!(gang, Gang::AnGang { .. }),
+        kan: true,
+    });
+    chi.chain(pon).chain(kan).collect()
+}
+
+fn sequence_wait(low: TileFace, win: TileFace) -> Wait {
+    let num = |face: TileFace| face.try_into_suit().map(|s| s.num);
+    match (num(low), win.into_inner() - low.into_inner()) {
+        (_, 1) => Wait::Kanchan,
+        (Some(Num::N1), 2) | (Some(Num::N7), 0) => Wait::Penchan,
+        _ => Wait::Ryanmen,
+    }
+}
+
+/// Terminals and honors.
+fn is_yaochuu(face: TileFace) -> bool {
+    face.is_terminal()
+}
+
+fn is_value(face: TileFace, ctx: &WinContext) -> bool {
+    match face.try_into_honer() {
+        Some(Honer::Dragon(_)) => true,
+        Some(Honer::Wind(wind)) => wind == ctx.seat_wind || wind == ctx.round_wind,
+        None => false,
+    }
+}
+
+/// The yaku every complete form shares: the situation of the win and the colors of the tiles.
+fn common_yaku(faces: &[TileFace], concealed: bool, ctx: &WinContext) -> Vec<Yaku> {
+    let mut yaku = Vec::new();
+    if ctx.riichi {
+        yaku.push(Yaku::Riichi);
+    }
+    if ctx.tsumo && concealed {
+        yaku.push(Yaku::MenzenTsumo);
+    }
+    if ctx.last_tile && !ctx.rinshan {
+        yaku.push(if ctx.tsumo {
+            Yaku::Haitei
+        } else {
+            Yaku::Houtei
+        });
+    }
+    if ctx.rinshan && ctx.tsumo {
+        yaku.push(Yaku::Rinshan);
+    }
+    if ctx.chankan {
+        yaku.push(Yaku::Chankan);
+    }
+    if faces.iter().all(|f| !is_yaochuu(*f)) {
+        yaku.push(Yaku::Tanyao);
+    }
+    let mut kinds = faces
+        .iter()
+        .filter_map(|f| f.try_into_suit())
+        .map(|s| s.kind);
+    let honors = faces.iter().any(|f| f.is_honor());
+    match kinds.next() {
+        None => yaku.push(Yaku::Tsuuiisou),
+        Some(kind) if kinds.all(|k: SuitKind| k == kind) => yaku.push(if honors {
+            Yaku::Honitsu
+        } else {
+            Yaku::Chinitsu
+        }),
+        _ => {}
+    }
+    if faces.iter().all(|f| f.is_green()) {
+        yaku.push(Yaku::Ryuuiisou);
+    }
+    if faces.iter().all(|f| is_yaochuu(*f)) {
+        yaku.push(if honors {
+            Yaku::Honroutou
+        } else {
+            Yaku::Chinroutou
+        });
+    }
+    yaku
+}
+
+fn seven_pairs(counts: &FaceCounts, ctx: &WinContext) -> Score {
+    let faces: Vec<TileFace> = counts.iter().map(|(face, _)| face).collect();
+    let mut yaku = common_yaku(&faces, true, ctx);
+    yaku.push(Yaku::Chiitoitsu);
+    score(yaku, 25, ctx, true)
+}
+
+fn regular(pair: TileFace, sets: &[Set], wait: Wait, concealed: bool, ctx: &WinContext) -> Score {
+    let faces: Vec<TileFace> = [pair, pair]
+        .into_iter()
+        .chain(sets.iter().flat_map(|s| s.meld.faces()))
+        .collect();
+    let mut yaku = common_yaku(&faces, concealed, ctx);
+    let triplets: Vec<TileFace> = (sets.iter())
+        .filter(|s| s.meld.is_triplet())
+        .map(|s| s.meld.face())
+        .collect();
+    let sequences: Vec<TileFace> = (sets.iter())
+        .filter(|s| s.meld.is_sequence())
+        .map(|s| s.meld.face())
+        .collect();
+    let pinfu = concealed && sequences.len() == 4 && !is_value(pair, ctx) && wait == Wait::Ryanmen;
+    if pinfu {
+        yaku.push(Yaku::Pinfu);
+    }
+    // identical runs among the concealed ones
+    let mut runs: Vec<TileFace> = (sets.iter())
+        .filter(|s| s.meld.is_sequence() && concealed)
+        .map(|s| s.meld.face())
+        .collect();
+    runs.sort();
+    let mut peikou = 0;
+    let mut i = 0;
+    while i + 1 < runs.len() {
+        if runs[i] == runs[i + 1] {
+            peikou += 1;
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    match peikou {
+        1 => yaku.push(Yaku::Iipeikou),
+        2 => yaku.push(Yaku::Ryanpeikou),
+        _ => {}
+    }
+    for face in &triplets {
+        match *face {
+            WHITE => yaku.push(Yaku::Haku),
+            GREEN => yaku.push(Yaku::Hatsu),
+            RED => yaku.push(Yaku::Chun),
+            _ => {}
+        }
+        if let Some(Honer::Wind(wind)) = face.try_into_honer() {
+            if wind == ctx.seat_wind {
+                yaku.push(Yaku::SeatWind);
+            }
+            if wind == ctx.round_wind {
+                yaku.push(Yaku::RoundWind);
+            }
+        }
+    }
+    if triplets.len() == 4 {
+        yaku.push(Yaku::Toitoi);
+    }
+    match sets
+        .iter()
+        .filter(|s| s.meld.is_triplet() && s.concealed)
+        .count()
+    {
+        3 => yaku.push(Yaku::Sanankou),
+        4 => yaku.push(Yaku::Suuankou),
+        _ => {}
+    }
+    let run = |kind: SuitKind, num: Num| {
+        sequences.contains(&TileFace::from_suit(crate::tile::Suit { kind, num }))
+    };
+    if Num::enumerate().any(|num| SuitKind::enumerate().all(|kind| run(kind, num))) {
+        yaku.push(Yaku::Sanshoku);
+    }
+    if SuitKind::enumerate().any(|kind| [Num::N1, Num::N4, Num::N7].iter().all(|n| run(kind, *n))) {
+        yaku.push(Yaku::Ittsu);
+    }
+    let outside = is_yaochuu(pair)
+        && sets
+            .iter()
+            .all(|s| s.meld.faces().iter().any(|f| is_yaochuu(*f)));
+    if outside && !sequences.is_empty() {
+        let honors = faces.iter().any(|f| f.is_honor());
+        yaku.push(if honors { Yaku::Chanta } else { Yaku::Junchan });
+    }
+    let dragon = |f: &TileFace| matches!(f.try_into_honer(), Some(Honer::Dragon(_)));
+    let wind = |f: &TileFace| matches!(f.try_into_honer(), Some(Honer::Wind(_)));
+    match (triplets.iter().filter(|f| dragon(f)).count(), dragon(&pair)) {
+        (3, _) => yaku.push(Yaku::Daisangen),
+        (2, true) => yaku.push(Yaku::Shousangen),
+        _ => {}
+    }
+    match (triplets.iter().filter(|f| wind(f)).count(), wind(&pair)) {
+        (4, _) => yaku.push(Yaku::Daisuushii),
+        (3, true) => yaku.push(Yaku::Shousuushii),
+        _ => {}
+    }
+    let fu = if pinfu {
+        if ctx.tsumo {
+            20
+        } else {
+            30
+        }
+    } else {
+        fu(pair, sets, wait, concealed, ctx)
+    };
+    score(yaku, fu, ctx, concealed)
+}
+
+fn fu(pair: TileFace, sets: &[Set], wait: Wait, concealed: bool, ctx: &WinContext) -> u32 {
+    let mut fu = 20;
+    if concealed && !ctx.tsumo {
+        fu += 10;
+    }
+    if ctx.tsumo {
+        fu += 2;
+    }
+    for set in sets.iter().filter(|s| s.meld.is_triplet()) {
+        let mut set_fu = 2;
+        if set.concealed {
+            set_fu *= 2;
+        }
+        if is_yaochuu(set.meld.face()) {
+            set_fu *= 2;
+        }
+        if set.kan {
+            set_fu *= 4;
+        }
+        fu += set_fu;
+    }
+    if let Some(honer) = pair.try_into_honer() {
+        match honer {
+            Honer::Dragon(_) => fu += 2,
+            Honer::Wind(wind) => {
+                fu += 2 * ((wind == ctx.seat_wind) as u32 + (wind == ctx.round_wind) as u32)
+            }
+        }
+    }
+    if matches!(wait, Wait::Kanchan | Wait::Penchan | Wait::Tanki) {
+        fu += 2;
+    }
+    // an open hand without fu still scores 30
+    match fu.div_ceil(10) * 10 {
+        20 if !concealed => 30,
+        fu => fu,
+    }
+}
+
+/// Keep the yakuman alone if there are any, and add up the han.
+fn score(mut yaku: Vec<Yaku>, fu: u32, ctx: &WinContext, concealed: bool) -> Score {
+    if yaku.iter().any(|y| y.is_yakuman()) {
+        yaku.retain(|y| y.is_yakuman());
+    }
+    yaku.retain(|y| y.han(concealed) > 0);
+    let mut han: u32 = yaku.iter().map(|y| y.han(concealed)).sum();
+    let yakuman = yaku.iter().any(|y| y.is_yakuman());
+    let dora = if yaku.is_empty() || yakuman {
+        0
+    } else {
+        ctx.dora
+    };
+    han += dora;
+    Score {
+        yaku,
+        dora,
+        han,
+        fu,
+    }
+}
+
+#[cfg(test)]
+fn context(win_tile: TileFace, tsumo: bool) -> WinContext {
+    WinContext {
+        seat_wind: Wind::South,
+        round_wind: Wind::East,
+        win_tile,
+        tsumo,
+        riichi: false,
+        last_tile: false,
+        rinshan: false,
+        chankan: false,
+        dora: 0,
+    }
+}
+
+#[test]
+fn test_pinfu_tsumo() {
+    use crate::{hand::Hand, tile::*};
+    let deck = Deck {
+        hand: Hand::from_faces([C2, C3, C4, D4, D5, D6, B6, B7, B8, C6, C7, C8, D9, D9]),
+        ..Default::default()
+    };
+    let score = evaluate(&deck, &context(C8, true)).unwrap();
+    assert_eq!(score.yaku, vec![Yaku::MenzenTsumo, Yaku::Pinfu]);
+    assert_eq!(score.fu, 20);
+    assert_eq!(score.han, 2);
+    // 20 fu 2 han: 700 from the dealer and 400 from the others
+    assert_eq!(score.tsumo(true), 700);
+    assert_eq!(score.tsumo(false), 400);
+    // a kanchan on 7m is no pinfu, the hand has no yaku on a ron
+    assert!(evaluate(&deck, &context(C7, false)).is_none());
+}
+
+#[test]
+fn test_yakuhai_and_fu() {
+    use crate::{hand::Hand, tile::*};
+    // 111m 345p 678s RED RED RED, 99s waiting on a shanpon
+    let deck = Deck {
+        hand: Hand::from_faces([C1, C1, C1, D3, D4, D5, B6, B7, B8, RED, RED, RED, B9, B9]),
+        ..Default::default()
+    };
+    let score = evaluate(&deck, &context(RED, false)).unwrap();
+    assert_eq!(score.yaku, vec![Yaku::Chun]);
+    // 20 + 10 concealed ron + 8 for 111m + 4 for the red triplet completed on a ron
+    assert_eq!(score.fu, 50);
+    assert_eq!(score.ron(false), 1600);
+    assert_eq!(score.ron(true), 2400);
+}
+
+#[test]
+fn test_seven_pairs_and_yakuman() {
+    use crate::{hand::Hand, tile::*};
+    let pairs = Deck {
+        hand: Hand::from_faces([C2, C2, C4, C4, D3, D3, D7, D7, B2, B2, B5, B5, B8, B8]),
+        ..Default::default()
+    };
+    let score = evaluate(
+        &pairs,
+        &WinContext {
+            dora: 2,
+            ..context(B8, false)
+        },
+    )
+    .unwrap();
+    assert_eq!(score.yaku, vec![Yaku::Tanyao, Yaku::Chiitoitsu]);
+    assert_eq!((score.han, score.fu), (5, 25));
+    assert_eq!(score.base(), 2000);
+    let orphans = Deck {
+        hand: Hand::from_faces([
+            C1, C9, B1, B9, D1, D9, EAST, SOUTH, WEST, NORTH, RED, GREEN, WHITE, WHITE,
+        ]),
+        ..Default::default()
+    };
+    let score = evaluate(&orphans, &context(WHITE, true)).unwrap();
+    assert_eq!(score.yaku, vec![Yaku::KokushiMusou]);
+    assert_eq!(score.tsumo(true), 16000);
+}
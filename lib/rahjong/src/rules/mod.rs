@@ -1,7 +1,10 @@
 use crate::{hand::Deck, tile::TileId};
 
+pub mod gd;
+pub mod hk;
 pub mod jp;
 pub mod sc;
+pub mod tw;
 
 
 pub trait Rule {
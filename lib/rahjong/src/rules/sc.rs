@@ -275,6 +275,7 @@ mod tests {
         let deck = Deck {
             hand: Hand::new(tiles),
             melded: Melded::new(),
+            bonus: Vec::new(),
         };
         assert!(SichuanRules::is_seven_pairs(&deck));
     }
@@ -299,6 +300,7 @@ mod tests {
         let deck = Deck {
             hand: Hand::new(tiles),
             melded: Melded::new(),
+            bonus: Vec::new(),
         };
         assert!(SichuanRules::is_dragon_seven_pairs(&deck));
     }
@@ -0,0 +1,403 @@
+use rand::Rng;
+
+use crate::{
+    hand::{decompose::Meld, Deck, Hand},
+    player::{Player, WindSet},
+    tile::{Bonus, Dragon, Honer, SuitKind, TileFace, TileId, Wind},
+    wall::Wall,
+    Tiles, WithBonus,
+};
+
+use super::jp::Jp;
+
+/// Taiwanese 16 tile mahjong, the 136 standard tiles plus flowers and seasons.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tw;
+
+impl Tiles for Tw {
+    const HAND_SIZE: usize = HAND_SIZE;
+
+    fn new_wall(&self) -> Vec<TileId> {
+        WithBonus(Jp).new_wall()
+    }
+
+    fn size(&self) -> usize {
+        144
+    }
+}
+
+pub const HAND_SIZE: usize = 16;
+/// A winning hand is five melds and a pair.
+pub const SETS: usize = 5;
+
+/// Deal sixteen tiles to every seat starting from east, bonus tiles are declared and replaced.
+pub fn deal<R>(wall: &mut Wall<Tw, R>) -> WindSet<Deck> {
+    let hands = wall.deal::<4>(HAND_SIZE);
+    hands
+        .into_iter()
+        .enumerate()
+        .map(|(i, hand)| {
+            let mut deck = Deck {
+                hand: Hand::new(hand),
+                ..Default::default()
+            };
+            wall.replace_bonus(&mut deck);
+            (Wind::from_index(i), deck)
+        })
+        .collect()
+}
+
+impl<R: Rng> Wall<Tw, R> {
+    pub fn new_tw(rng: R) -> Self {
+        let mut wall = Wall::new(Tw, rng);
+        wall.shuffle();
+        wall
+    }
+}
+
+/// Payment is `base + tai * per_tai` (底/台) for every paying seat.
+#[derive(Debug, Clone)]
+pub struct TwRules {
+    pub base: u32,
+    pub per_tai: u32,
+}
+
+impl Default for TwRules {
+    fn default() -> Self {
+        Self {
+            base: 100,
+            per_tai: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WinContext {
+    pub seat: Wind,
+    pub prevailing: Wind,
+    pub dealer: Wind,
+    /// How many hands the dealer has kept the seat for (連莊).
+    pub lianzhuang: u8,
+    pub self_drawn: bool,
+    pub win_tile: TileId,
+}
+
+impl WinContext {
+    /// The dealer tai, one for being dealer and two for each consecutive hand (連n拉n).
+    pub const fn dealer_tai(&self) -> u32 {
+        1 + 2 * self.lianzhuang as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tai {
+    Concealed,
+    SelfDrawn,
+    /// 不求人, concealed and self-drawn, replaces both.
+    ConcealedSelfDrawn,
+    SeatFlower(Bonus),
+    /// 花槓, all four flowers or all four seasons.
+    FourBonus,
+    DragonPung(Dragon),
+    SeatWind,
+    PrevailingWind,
+    AllChows,
+    AllPungs,
+    ThreeConcealedPungs,
+    FourConcealedPungs,
+    FiveConcealedPungs,
+    HalfFlush,
+    FullFlush,
+    SmallThreeDragons,
+    BigThreeDragons,
+    SmallFourWinds,
+    BigFourWinds,
+    AllHonors,
+}
+
+impl Tai {
+    pub const fn value(self) -> u32 {
+        match self {
+            Tai::Concealed
+            | Tai::SelfDrawn
+            | Tai::SeatFlower(_)
+            | Tai::DragonPung(_)
+            | Tai::SeatWind
+            | Tai::PrevailingWind => 1,
+            Tai::AllChows | Tai::ThreeConcealedPungs | Tai::FourBonus => 2,
+            Tai::ConcealedSelfDrawn => 3,
+            Tai::AllPungs | Tai::HalfFlush | Tai::SmallThreeDragons => 4,
+            Tai::FourConcealedPungs => 5,
+            Tai::FullFlush
+            | Tai::FiveConcealedPungs
+            | Tai::BigThreeDragons
+            | Tai::SmallFourWinds => 8,
+            Tai::BigFourWinds | Tai::AllHonors => 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TwScore {
+    pub tais: Vec<Tai>,
+    pub tai: u32,
+}
+
+impl TwRules {
+    /// Score a complete hand of five melds and a pair, the winning tile must already be in
+    /// `deck.hand`. The dealer tai are not included, they depend on who pays.
+    pub fn evaluate(&self, deck: &Deck, ctx: &WinContext) -> Option<TwScore> {
+        let open = deck.melded.melds();
+        let concealed = deck.melded.is_concealed();
+        deck.hand
+            .decompose()
+            .into_iter()
+            .filter(|d| d.melds.len() + open.len() == SETS)
+            .map(|d| {
+                // a discard completes an open triplet, unless the tile can go to the pair or a
+                // run instead which leaves a triplet of the same face concealed
+                let win = ctx.win_tile.face();
+                let ron_triplet = !ctx.self_drawn
+                    && d.pair != win
+                    && !(d.melds.iter()).any(|m| m.is_sequence() && m.faces().contains(&win));
+                let hidden_pungs = d
+                    .melds
+                    .iter()
+                    .filter(|m| m.is_triplet())
+                    .filter(|m| !(ron_triplet && m.face() == win))
+                    .count()
+                    + deck.melded.gang.iter().filter(|g| g.is_concealed()).count();
+                let mut melds = d.melds;
+                melds.extend_from_slice(&open);
+                let tais = Self::tais(d.pair, &melds, hidden_pungs, deck, ctx, concealed);
+                let tai = tais.iter().map(|t| t.value()).sum();
+                TwScore { tais, tai }
+            })
+            .max_by_key(|score| score.tai)
+    }
+
+    fn tais(
+        pair: TileFace,
+        melds: &[Meld],
+        hidden_pungs: usize,
+        deck: &Deck,
+        ctx: &WinContext,
+        concealed: bool,
+    ) -> Vec<Tai> {
+        let mut tais = Vec::new();
+        match (concealed, ctx.self_drawn) {
+            (true, true) => tais.push(Tai::ConcealedSelfDrawn),
+            (true, false) => tais.push(Tai::Concealed),
+            (false, true) => tais.push(Tai::SelfDrawn),
+            (false, false) => {}
+        }
+        let faces = || [pair].into_iter().chain(melds.iter().map(Meld::face));
+        let mut kinds = faces().filter_map(|f| f.try_into_suit()).map(|s| s.kind);
+        let has_honor = faces().any(|f| f.is_honor());
+        match kinds.next() {
+            None => tais.push(Tai::AllHonors),
+            Some(kind) if kinds.all(|k: SuitKind| k == kind) => tais.push(if has_honor {
+                Tai::HalfFlush
+            } else {
+                Tai::FullFlush
+            }),
+            _ => {}
+        }
+        if melds.iter().all(Meld::is_sequence) && !has_honor && deck.bonus.is_empty() {
+            tais.push(Tai::AllChows);
+        }
+        if melds.iter().all(Meld::is_triplet) {
+            tais.push(Tai::AllPungs);
+        }
+        match hidden_pungs {
+            3 => tais.push(Tai::ThreeConcealedPungs),
+            4 => tais.push(Tai::FourConcealedPungs),
+            5 => tais.push(Tai::FiveConcealedPungs),
+            _ => {}
+        }
+        let mut dragons = Vec::new();
+        let mut winds = Vec::new();
+        for face in melds.iter().filter(|m| m.is_triplet()).map(Meld::face) {
+            match face.try_into_honer() {
+                Some(Honer::Dragon(dragon)) => dragons.push(dragon),
+                Some(Honer::Wind(wind)) => winds.push(wind),
+                None => {}
+            }
+        }
+        let dragon_pair = matches!(pair.try_into_honer(), Some(Honer::Dragon(_)));
+        let wind_pair = matches!(pair.try_into_honer(), Some(Honer::Wind(_)));
+        match (dragons.len(), dragon_pair) {
+            (3, _) => tais.push(Tai::BigThreeDragons),
+            (2, true) => tais.push(Tai::SmallThreeDragons),
+            _ => tais.extend(dragons.iter().copied().map(Tai::DragonPung)),
+        }
+        match (winds.len(), wind_pair) {
+            (4, _) => tais.push(Tai::BigFourWinds),
+            (3, true) => tais.push(Tai::SmallFourWinds),
+            _ => {
+                if winds.contains(&ctx.seat) {
+                    tais.push(Tai::SeatWind);
+                }
+                if winds.contains(&ctx.prevailing) {
+                    tais.push(Tai::PrevailingWind);
+                }
+            }
+        }
+        let bonus: Vec<Bonus> = deck
+            .bonus
+            .iter()
+            .filter_map(|t| t.face().try_into_bonus())
+            .collect();
+        let flowers = bonus
+            .iter()
+            .filter(|b| matches!(b, Bonus::Flower(_)))
+            .count();
+        let seasons = bonus.len() - flowers;
+        tais.extend((0..(flowers / 4 + seasons / 4)).map(|_| Tai::FourBonus));
+        tais.extend(
+            bonus
+                .into_iter()
+                .filter(|b| b.wind() == ctx.seat)
+                .map(Tai::SeatFlower),
+        );
+        tais
+    }
+
+    /// Point changes for everyone, `discarder` is `None` for a self-drawn win where all three
+    /// opponents pay. Any payment between the dealer and another seat carries the dealer tai.
+    pub fn payments(
+        &self,
+        score: &TwScore,
+        ctx: &WinContext,
+        winner: Player,
+        discarder: Option<Player>,
+    ) -> WindSet<i32> {
+        let mut payments = WindSet::<i32>::default();
+        let payers = Wind::enumerate()
+            .filter(|w| *w != winner.wind())
+            .filter(|w| discarder.is_none_or(|d| d.wind() == *w));
+        for wind in payers {
+            let mut tai = score.tai;
+            if ctx.dealer == winner.wind() || ctx.dealer == wind {
+                tai += ctx.dealer_tai();
+            }
+            let pay = (self.base + tai * self.per_tai) as i32;
+            *payments.get_mut(wind) -= pay;
+            *payments.get_mut(winner) += pay;
+        }
+        payments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::*;
+    use rand::SeedableRng;
+
+    fn context(win_tile: TileFace, self_drawn: bool) -> WinContext {
+        WinContext {
+            seat: Wind::South,
+            prevailing: Wind::East,
+            dealer: Wind::East,
+            lianzhuang: 0,
+            self_drawn,
+            win_tile: TileId::from_face_idx(win_tile, TileIndex::T0),
+        }
+    }
+
+    #[test]
+    fn test_deal() {
+        let mut wall = Wall::new_tw(rand::rngs::StdRng::seed_from_u64(16));
+        let decks = deal(&mut wall);
+        let mut bonus = 0;
+        for (_, deck) in decks.iter() {
+            assert_eq!(deck.hand.len(), HAND_SIZE);
+            bonus += deck.bonus.len();
+        }
+        assert_eq!(wall.len() + bonus + 4 * HAND_SIZE, 144);
+    }
+
+    #[test]
+    fn test_round() {
+        use crate::rules::jp::round::{Action, Round, RoundOptions};
+        let mut round: Round<Tw> = Round::with_seed(Tw, Wind::East, RoundOptions::default(), 16);
+        round.start().unwrap();
+        for seat in Wind::enumerate().map(Player::from) {
+            let sight = round.player_sight(seat);
+            assert_eq!(sight.self_deck.hand.len(), HAND_SIZE);
+            assert!(sight.self_deck.hand.tiles.iter().all(|t| !t.is_bonus()));
+        }
+        let draw = round.draw().unwrap().tile();
+        assert!(!draw.is_bonus());
+        round
+            .apply(&Action::Discard(Player::EAST.discard(draw)), Player::EAST)
+            .unwrap();
+        for seat in [Player::SOUTH, Player::WEST, Player::NORTH] {
+            round.apply(&Action::Pass, seat).unwrap();
+        }
+        let sight = round.player_sight(Player::SOUTH);
+        assert_eq!(sight.to_discard, Some(Player::SOUTH));
+        assert_eq!(sight.self_deck.hand.len(), HAND_SIZE);
+        assert_eq!(sight.other_hand_size.get(Wind::East), &(HAND_SIZE as u8));
+    }
+
+    #[test]
+    fn test_seventeen_tiles() {
+        let deck = Deck {
+            hand: Hand::from_faces([
+                C1, C1, C1, C4, C5, C6, C7, C8, C9, D2, D2, D2, RED, RED, RED, SOUTH, SOUTH,
+            ]),
+            ..Default::default()
+        };
+        let rules = TwRules::default();
+        let score = rules.evaluate(&deck, &context(C5, true)).unwrap();
+        assert!(score.tais.contains(&Tai::ConcealedSelfDrawn));
+        assert!(score.tais.contains(&Tai::ThreeConcealedPungs));
+        assert!(score.tais.contains(&Tai::DragonPung(Dragon::Red)));
+        // fourteen tiles are not a win here
+        let deck = Deck {
+            hand: Hand::from_faces([C1, C1, C1, C4, C5, C6, C7, C8, C9, D2, D2, D2, RED, RED]),
+            ..Default::default()
+        };
+        assert!(rules.evaluate(&deck, &context(C5, true)).is_none());
+    }
+
+    #[test]
+    fn test_ron_completes_a_run() {
+        // the 5m on a ron completes 345m, the 555m triplet stays concealed
+        let deck = Deck {
+            hand: Hand::from_faces([
+                C3, C4, C5, C5, C5, C5, D2, D2, D2, RED, RED, RED, B7, B8, B9, SOUTH, SOUTH,
+            ]),
+            ..Default::default()
+        };
+        let rules = TwRules::default();
+        let score = rules.evaluate(&deck, &context(C5, false)).unwrap();
+        assert!(score.tais.contains(&Tai::ThreeConcealedPungs));
+        // the 2p can only complete the triplet
+        let score = rules.evaluate(&deck, &context(D2, false)).unwrap();
+        assert!(!score.tais.contains(&Tai::ThreeConcealedPungs));
+    }
+
+    #[test]
+    fn test_dealer_streak() {
+        let rules = TwRules::default();
+        let score = TwScore {
+            tais: vec![Tai::AllPungs],
+            tai: 4,
+        };
+        let ctx = WinContext {
+            lianzhuang: 2,
+            ..context(C1, true)
+        };
+        let payments = rules.payments(&score, &ctx, Player::SOUTH, None);
+        assert_eq!(payments.iter().map(|(_, p)| *p).sum::<i32>(), 0);
+        // the dealer pays 4 + 1 + 2 * 2 tai, the others pay 4
+        assert_eq!(*payments.get(Wind::East), -(100 + 9 * 20));
+        assert_eq!(*payments.get(Wind::West), -(100 + 4 * 20));
+        let payments = rules.payments(&score, &ctx, Player::SOUTH, Some(Player::WEST));
+        assert_eq!(*payments.get(Wind::East), 0);
+        assert_eq!(*payments.get(Wind::South), 180);
+    }
+}
@@ -11,6 +11,7 @@ pub mod tile_set;
 ///```
 #[derive(PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Hash)]
 #[repr(transparent)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileId {
     uid: u8,
 }
@@ -23,6 +24,7 @@ impl std::fmt::Debug for TileId {
 }
 #[derive(PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Hash)]
 #[repr(transparent)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileFace(pub(crate) u8);
 
 impl std::fmt::Debug for TileFace {
@@ -83,6 +85,61 @@ impl TileFace {
         }
     }
 
+    pub const fn try_into_bonus(self) -> Option<Bonus> {
+        match self {
+            PLUM => Some(Bonus::Flower(Flower::Plum)),
+            ORCHID => Some(Bonus::Flower(Flower::Orchid)),
+            CHRYSANTHEMUM => Some(Bonus::Flower(Flower::Chrysanthemum)),
+            BAMBOO => Some(Bonus::Flower(Flower::Bamboo)),
+            SPRING => Some(Bonus::Season(Season::Spring)),
+            SUMMER => Some(Bonus::Season(Season::Summer)),
+            AUTUMN => Some(Bonus::Season(Season::Autumn)),
+            WINTER => Some(Bonus::Season(Season::Winter)),
+            _ => None,
+        }
+    }
+
+    pub const fn is_bonus(&self) -> bool {
+        self.try_into_bonus().is_some()
+    }
+
+    pub const fn from_bonus(bonus: Bonus) -> Self {
+        match bonus {
+            Bonus::Flower(f) => match f {
+                Flower::Plum => PLUM,
+                Flower::Orchid => ORCHID,
+                Flower::Chrysanthemum => CHRYSANTHEMUM,
+                Flower::Bamboo => BAMBOO,
+            },
+            Bonus::Season(s) => match s {
+                Season::Spring => SPRING,
+                Season::Summer => SUMMER,
+                Season::Autumn => AUTUMN,
+                Season::Winter => WINTER,
+            },
+        }
+    }
+
+    /// The face after this one: 9 wraps to 1, winds and dragons cycle in order. This is the
+    /// dora for an indicator, or the ghost for a flipped tile.
+    pub const fn successor(self) -> TileFace {
+        if let Some(suit) = self.try_into_suit() {
+            let num = match suit.num.next() {
+                Some(num) => num,
+                None => Num::N1,
+            };
+            return TileFace::from_suit(Suit {
+                kind: suit.kind,
+                num,
+            });
+        }
+        match self.try_into_honer() {
+            Some(Honer::Wind(wind)) => TileFace::from_honer(Honer::Wind(wind.next())),
+            Some(Honer::Dragon(dragon)) => TileFace::from_honer(Honer::Dragon(dragon.next())),
+            None => self,
+        }
+    }
+
     pub fn is_terminal(&self) -> bool {
         self.is_honor()
             || self
@@ -116,6 +173,12 @@ impl From<Honer> for TileFace {
         TileFace::from_honor(honer)
     }
 }
+
+impl From<Bonus> for TileFace {
+    fn from(bonus: Bonus) -> Self {
+        TileFace::from_bonus(bonus)
+    }
+}
 macro_rules! const_tiles {
     (
         $(
@@ -162,7 +225,29 @@ const_tiles! {
     RED: '🀄'
     GREEN: '🀅'
     WHITE: '🀆'
+
+    PLUM: '🀢'
+    ORCHID: '🀣'
+    CHRYSANTHEMUM: '🀤'
+    BAMBOO: '🀥'
+
+    SPRING: '🀦'
+    SUMMER: '🀧'
+    AUTUMN: '🀨'
+    WINTER: '🀩'
 }
+
+/// Flowers and seasons, each face has exactly one copy which is always [`TileIndex::T0`].
+pub const BONUS_TILES: [TileId; 8] = [
+    TileId::from_face_idx(PLUM, TileIndex::T0),
+    TileId::from_face_idx(ORCHID, TileIndex::T0),
+    TileId::from_face_idx(CHRYSANTHEMUM, TileIndex::T0),
+    TileId::from_face_idx(BAMBOO, TileIndex::T0),
+    TileId::from_face_idx(SPRING, TileIndex::T0),
+    TileId::from_face_idx(SUMMER, TileIndex::T0),
+    TileId::from_face_idx(AUTUMN, TileIndex::T0),
+    TileId::from_face_idx(WINTER, TileIndex::T0),
+];
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum TileIndex {
     T0 = 0b0001,
@@ -249,6 +334,9 @@ impl TileId {
         let (face, _) = self.into_face_idx();
         face.into()
     }
+    pub const fn is_bonus(self) -> bool {
+        self.face().is_bonus()
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -446,6 +534,13 @@ impl Dragon {
     pub fn enumerate() -> <[Self; 3] as IntoIterator>::IntoIter {
         [Dragon::Red, Dragon::Green, Dragon::White].into_iter()
     }
+    pub const fn next(self) -> Self {
+        match self {
+            Dragon::Red => Dragon::Green,
+            Dragon::Green => Dragon::White,
+            Dragon::White => Dragon::Red,
+        }
+    }
     pub const fn unicode(self) -> char {
         match self {
             Dragon::Red => '🀄',
@@ -456,6 +551,74 @@ impl Dragon {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Bonus {
+    Flower(Flower),
+    Season(Season),
+}
+
+impl Bonus {
+    pub fn enumerate() -> <[Self; 8] as IntoIterator>::IntoIter {
+        [
+            Bonus::Flower(Flower::Plum),
+            Bonus::Flower(Flower::Orchid),
+            Bonus::Flower(Flower::Chrysanthemum),
+            Bonus::Flower(Flower::Bamboo),
+            Bonus::Season(Season::Spring),
+            Bonus::Season(Season::Summer),
+            Bonus::Season(Season::Autumn),
+            Bonus::Season(Season::Winter),
+        ]
+        .into_iter()
+    }
+    /// The seat this bonus tile belongs to, plum and spring are east's.
+    pub const fn wind(self) -> Wind {
+        match self {
+            Bonus::Flower(f) => f.wind(),
+            Bonus::Season(s) => s.wind(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Flower {
+    Plum,
+    Orchid,
+    Chrysanthemum,
+    Bamboo,
+}
+
+impl Flower {
+    pub const fn wind(self) -> Wind {
+        match self {
+            Flower::Plum => Wind::East,
+            Flower::Orchid => Wind::South,
+            Flower::Chrysanthemum => Wind::West,
+            Flower::Bamboo => Wind::North,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    pub const fn wind(self) -> Wind {
+        match self {
+            Season::Spring => Wind::East,
+            Season::Summer => Wind::South,
+            Season::Autumn => Wind::West,
+            Season::Winter => Wind::North,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Wind {
     East = 0,
     South = 1,
@@ -1,6 +1,7 @@
 use super::{TileFace, TileId, TileIndex};
 
 #[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileSet {
     memory: [u8; 32],
 }
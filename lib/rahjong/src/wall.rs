@@ -1,6 +1,6 @@
 use std::usize;
 
-use crate::{draw::Draw, tile::TileId, Tiles};
+use crate::{draw::Draw, hand::Deck, tile::TileId, Tiles};
 use rand::{seq::SliceRandom, Rng};
 
 pub struct Wall<S, R> {
@@ -42,6 +42,38 @@ impl<S: Tiles, R> Wall<S, R> {
             }
         }
     }
+    /// Draw a replacement tile from the back of the wall, used after a flower or a kong.
+    pub fn draw_replacement(&mut self) -> Option<Draw> {
+        let tile = self.tiles.iter_mut().find_map(Option::take)?;
+        self.size = self.size.saturating_sub(1);
+        Some(Draw::new(tile))
+    }
+    /// Draw the next tile for `deck`, flowers and seasons are declared onto the deck and
+    /// replaced until a normal tile comes out.
+    pub fn draw_with_bonus(&mut self, deck: &mut Deck) -> Option<Draw> {
+        let mut draw = self.draw_next()?;
+        while draw.tile().is_bonus() {
+            deck.bonus.push(draw.tile());
+            draw = self.draw_replacement()?;
+        }
+        Some(draw)
+    }
+    /// Declare the bonus tiles of a freshly dealt hand, the hand is refilled from the back.
+    pub fn replace_bonus(&mut self, deck: &mut Deck) {
+        let mut owed = deck.declare_bonus();
+        while owed > 0 {
+            owed -= 1;
+            let Some(draw) = self.draw_replacement() else {
+                return;
+            };
+            if draw.tile().is_bonus() {
+                deck.bonus.push(draw.tile());
+                owed += 1;
+            } else {
+                deck.hand.tiles.insert(draw.tile());
+            }
+        }
+    }
     pub fn take_nth(&mut self, n: usize) -> Option<TileId> {
         self.size = self.size.saturating_sub(1);
         self.tiles.get_mut(n)?.take()
@@ -80,6 +112,16 @@ impl<S: Tiles, R> Wall<S, R> {
         }
         hands
     }
+    /// Deal `size` tiles to each of `N` seats, one tile around the table at a time.
+    pub fn deal<const N: usize>(&mut self, size: usize) -> [Vec<TileId>; N] {
+        let mut hands: [Vec<TileId>; N] = std::array::from_fn(|_| Vec::with_capacity(size));
+        for _ in 0..size {
+            for hand in &mut hands {
+                hand.extend(self.draw_next().map(Draw::into_tile));
+            }
+        }
+        hands
+    }
 }
 
 impl<S, R> std::fmt::Debug for Wall<S, R> {
@@ -113,6 +155,32 @@ pub trait AsyncWall {
         &self,
     ) -> impl std::future::Future<Output = Result<usize, Self::Error>> + Send + 'static;
 }
+#[test]
+fn test_bonus_replacement() {
+    use crate::{hand::Hand, rules::jp::Jp, WithBonus};
+    use rand::SeedableRng;
+    let mut wall = Wall::new(WithBonus(Jp), rand::rngs::StdRng::seed_from_u64(7));
+    assert_eq!(wall.len(), 144);
+    wall.shuffle();
+    let hands = wall.draw_init::<4, 13>();
+    let mut bonus = 0;
+    for hand in hands {
+        let mut deck = Deck {
+            hand: Hand::new(hand),
+            ..Default::default()
+        };
+        wall.replace_bonus(&mut deck);
+        assert_eq!(deck.hand.len(), 13);
+        assert!(deck.hand.tiles.iter().all(|t| !t.is_bonus()));
+        bonus += deck.bonus.len();
+    }
+    let mut deck = Deck::default();
+    while let Some(draw) = wall.draw_with_bonus(&mut deck) {
+        assert!(!draw.tile().is_bonus());
+    }
+    assert_eq!(bonus + deck.bonus.len(), 8);
+}
+
 #[test]
 fn test_game() {
     let mut wall = Wall::new(crate::rules::jp::Jp, rand::rngs::ThreadRng::default());